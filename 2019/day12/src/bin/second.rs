@@ -1,63 +1,109 @@
-use std::io;
+use std::io::{self, Write};
+use std::env;
 use std::fs;
 use std::cmp::Ordering;
-use nom::branch::alt;
+use aoc_common::parse::signed;
 use nom::bytes::complete::tag;
-use nom::character::complete::{digit1, space0};
-use nom::combinator::map;
-use nom::sequence::preceded;
+use nom::character::complete::{alpha1, space0};
+use nom::multi::separated_list1;
+use nom::sequence::{delimited, separated_pair, tuple};
 use nom::IResult;
 
 #[derive(Debug, Default, Eq, PartialEq, Clone, Ord, PartialOrd)]
-struct Point{
-  x: isize,
-  y: isize,
-  z: isize,
+struct Moon1D {
+  position: isize,
+  velocity: isize,
 }
 
-#[derive(Debug, Default, Eq, PartialEq, Clone, Ord, PartialOrd)]
-struct Vector {
-  x: isize,
-  y: isize,
-  z: isize,
+/// Any number of bodies in any number of dimensions, pulling on each other
+/// one step at a time. Every axis moves on its own, so the state is kept per
+/// axis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Simulation {
+  names: Vec<String>,
+  /// For every axis, every body along it
+  axes: Vec<Vec<Moon1D>>,
+  steps: usize,
 }
 
-#[derive(Debug, Default, Eq, PartialEq, Clone, Ord, PartialOrd)]
-struct Moon {
-  position: Point,
-  velocity: Vector,
+/// `<x=-1, y=0, z=2>`, with as many coordinates as there are.
+fn parse_point(input: &str) -> IResult<&str, Vec<(&str, isize)>> {
+  delimited(
+    tag("<"),
+    separated_list1(tuple((tag(","), space0)), separated_pair(alpha1, tag("="), signed)),
+    tag(">"),
+  )(input)
 }
 
-#[derive(Debug, Default, Eq, PartialEq, Clone, Ord, PartialOrd)]
-struct Moon1D {
-  position: isize,
-  velocity: isize,
-}
+impl Simulation {
+  fn new(names: Vec<String>, positions: &[Vec<isize>]) -> Simulation {
+    let axes = (0..names.len())
+      .map(|axis| positions.iter().map(|position| Moon1D{position: position[axis], velocity: 0}).collect())
+      .collect();
+    Simulation{names, axes, steps: 0}
+  }
 
-fn parse_num(input: &str) -> IResult<&str, isize> {
-  alt((
-    map(digit1, |digit_str: &str| digit_str.parse::<isize>().unwrap()),
-    map(preceded(tag("-"), digit1), |digit_str: &str| 
-      -1 * digit_str.parse::<isize>().unwrap()),
-  ))(input)
-}
+  fn bodies(&self) -> usize {
+    self.axes.first().map_or(0, |axis| axis.len())
+  }
+
+  fn step(&mut self) {
+    for axis in &mut self.axes {
+      apply_gravity(axis);
+      apply_velocity(axis);
+    }
+    self.steps += 1;
+  }
+
+  /// Potential energy times kinetic energy, for every body.
+  fn energy(&self) -> isize {
+    (0..self.bodies()).map(|body| {
+      let potential: isize = self.axes.iter().map(|axis| axis[body].position.abs()).sum();
+      let kinetic: isize = self.axes.iter().map(|axis| axis[body].velocity.abs()).sum();
+      potential * kinetic
+    }).sum()
+  }
+
+  /// Steps until everything is back where it started. Every step can be
+  /// undone, so the first state to come back is the one it started with, and
+  /// the axes can be done apart.
+  fn period(&self) -> u128 {
+    self.axes.iter().map(|axis| find_cycle_1d(&mut axis.to_vec())).fold(1, find_common_freq_gcd)
+  }
 
-fn parse_point(input: &str) -> IResult<&str, Point> {
-  let (input, _) = tag("<x=")(input)?;
-  let (input, x) = parse_num(input)?;
-  let (input, _) = tag(",")(input)?;
-  let (input, _) = space0(input)?;
-  let (input, _) = tag("y=")(input)?;
-  let (input, y) = parse_num(input)?;
-  let (input, _) = tag(",")(input)?;
-  let (input, _) = space0(input)?;
-  let (input, _) = tag("z=")(input)?;
-  let (input, z) = parse_num(input)?;
-  let (input, _) = tag(">")(input)?;
-  Ok((input, Point{x, y ,z}))
+  fn csv_header(&self) -> String {
+    let positions = self.names.iter().cloned();
+    let velocities = self.names.iter().map(|name| format!("v{}", name));
+    let columns = positions.chain(velocities).collect::<Vec<_>>();
+    format!("step,body,{}\n", columns.join(","))
+  }
+
+  /// A line for every body, as it is now.
+  fn csv_rows(&self) -> String {
+    let mut rows = String::new();
+    for body in 0..self.bodies() {
+      let positions = self.axes.iter().map(|axis| axis[body].position);
+      let velocities = self.axes.iter().map(|axis| axis[body].velocity);
+      let values = positions.chain(velocities).map(|value| value.to_string()).collect::<Vec<_>>();
+      rows.push_str(&format!("{},{},{}\n", self.steps, body, values.join(",")));
+    }
+    rows
+  }
+
+  /// Writes where everything is and how fast it's going, from now until
+  /// `steps` steps from now, for plotting.
+  fn write_csv(&mut self, steps: usize, mut writer: impl Write) -> io::Result<()> {
+    writer.write_all(self.csv_header().as_bytes())?;
+    writer.write_all(self.csv_rows().as_bytes())?;
+    for _ in 0..steps {
+      self.step();
+      writer.write_all(self.csv_rows().as_bytes())?;
+    }
+    Ok(())
+  }
 }
 
-fn apply_gravity(moons: &mut Vec<Moon1D>) {
+fn apply_gravity(moons: &mut [Moon1D]) {
   for i1 in 0..moons.len() {
     for i2 in 0..moons.len() {
       if i1 == i2 {
@@ -72,22 +118,29 @@ fn apply_gravity(moons: &mut Vec<Moon1D>) {
   }
 }
 
-fn apply_velocity(moons: &mut Vec<Moon1D>) {
+fn apply_velocity(moons: &mut [Moon1D]) {
   for moon in moons.iter_mut() {
     moon.position += moon.velocity;
   }
 }
 
-fn string_to_moons(string: &str) -> Vec<Moon> {
-  let mut moons: Vec<Moon> = Vec::new();
+fn string_to_simulation(string: &str) -> Simulation {
+  let mut names = Vec::new();
+  let mut positions = Vec::new();
   for line in string.lines() {
-    let (_, position) = parse_point(line).unwrap();
-    moons.push(Moon{position, ..Default::default()});
+    let (_, point) = parse_point(line.trim()).unwrap();
+    let line_names = point.iter().map(|(name, _)| name.to_string()).collect::<Vec<_>>();
+    if positions.is_empty() {
+      names = line_names;
+    } else {
+      assert_eq!(names, line_names, "every body needs the same coordinates");
+    }
+    positions.push(point.iter().map(|&(_, value)| value).collect());
   }
-  moons
+  Simulation::new(names, &positions)
 }
 
-fn gcd (mut a: isize, mut b: isize) -> isize {
+fn gcd(mut a: u128, mut b: u128) -> u128 {
   while b != 0 {
     let remain = b;
     b = a % b;
@@ -96,39 +149,34 @@ fn gcd (mut a: isize, mut b: isize) -> isize {
   a
 }
 
-fn find_common_freq_gcd(a: isize, b: isize) -> isize {
+fn find_common_freq_gcd(a: u128, b: u128) -> u128 {
   a / gcd(a, b) * b
 }
 
-fn find_cycle_1d(moons: &mut Vec<Moon1D>) -> isize {
+fn find_cycle_1d(moons: &mut [Moon1D]) -> u128 {
   let mut cycle = 0;
-//  let mut history: BTreeMap<Moon, isize> = BTreeMap::new();
   let initial = moons.to_vec();
   loop {
     apply_gravity(moons);
     apply_velocity(moons);
     cycle += 1;
-    if *moons == initial {
-      println!("fucking loop!");
+    if *moons == initial[..] {
       break;
     }
   }
   cycle
 }
 
-fn find_cycle(moons: &mut Vec<Moon>) -> isize {
-  let mut x: Vec<Moon1D> = moons.iter().map(|m| Moon1D{position: m.position.x, velocity: m.velocity.x}).collect();
-  let mut y: Vec<Moon1D> = moons.iter().map(|m| Moon1D{position: m.position.y, velocity: m.velocity.y}).collect();
-  let mut z: Vec<Moon1D> = moons.iter().map(|m| Moon1D{position: m.position.z, velocity: m.velocity.z}).collect();
-  let x_freq = find_cycle_1d(&mut x);
-  let y_freq = find_cycle_1d(&mut y);
-  let z_freq = find_cycle_1d(&mut z);
-  find_common_freq_gcd(find_common_freq_gcd(x_freq, y_freq), z_freq)
-}
-
 fn main() -> io::Result<()> {
-  let mut moons = string_to_moons(&fs::read_to_string("input.txt")?);
-  println!("{}", find_cycle(&mut moons));
+  let simulation = string_to_simulation(&fs::read_to_string("input.txt")?);
+  let args = env::args().skip(1).collect::<Vec<_>>();
+  if let [csv, steps] = args.as_slice() {
+    let steps = steps.parse().expect("not a number of steps");
+    let mut trajectory = simulation.clone();
+    trajectory.write_csv(steps, io::BufWriter::new(fs::File::create(csv)?))?;
+    println!("energy after {} steps: {}", steps, trajectory.energy());
+  }
+  println!("{}", simulation.period());
   Ok(())
 }
 
@@ -137,31 +185,76 @@ mod tests {
   use super::*;
   use test_case::test_case;
 
-  #[test_case("<x=-1, y=0, z=2>
+  const EXAMPLE_1: &str = "<x=-1, y=0, z=2>
 <x=2, y=-10, z=-7>
 <x=4, y=-8, z=8>
-<x=3, y=5, z=-1>" => 2772 ; "example 1 part 2")]
-  #[test_case("<x=-8, y=-10, z=0>
+<x=3, y=5, z=-1>";
+
+  const EXAMPLE_2: &str = "<x=-8, y=-10, z=0>
 <x=5, y=5, z=10>
 <x=2, y=-7, z=3>
-<x=9, y=-8, z=-3>" => 4686774924 ; "example 2 part 2")]
-  fn test_find_cycle(input: &str) -> isize {
-    let mut moons = string_to_moons(input);
-    find_cycle(&mut moons)
+<x=9, y=-8, z=-3>";
+
+  #[test_case(EXAMPLE_1 => 2772 ; "example 1 part 2")]
+  #[test_case(EXAMPLE_2 => 4686774924 ; "example 2 part 2")]
+  #[test_case("<x=3>" => 1 ; "all alone")]
+  #[test_case("<x=1, y=2>
+<x=-1, y=0>
+<x=4, y=4>" => 102 ; "flat")]
+  fn test_find_cycle(input: &str) -> u128 {
+    let simulation = string_to_simulation(input);
+    let period = simulation.period();
+    if period < 10_000 {
+      let mut after = simulation.clone();
+      for _ in 0..period {
+        after.step();
+      }
+      after.steps = 0;
+      assert_eq!(after, simulation);
+    }
+    period
+  }
+
+  #[test_case(EXAMPLE_1, 10 => 179 ; "example 1")]
+  #[test_case(EXAMPLE_2, 100 => 1940 ; "example 2")]
+  fn test_energy(input: &str, steps: usize) -> isize {
+    let mut simulation = string_to_simulation(input);
+    for _ in 0..steps {
+      simulation.step();
+    }
+    simulation.energy()
+  }
+
+  #[test]
+  fn test_write_csv() {
+    let mut simulation = string_to_simulation("<x=0, w=5>
+<x=2, w=5>");
+    let mut csv = Vec::new();
+    simulation.write_csv(2, &mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "step,body,x,w,vx,vw
+0,0,0,5,0,0
+0,1,2,5,0,0
+1,0,1,5,1,0
+1,1,1,5,-1,0
+2,0,2,5,1,0
+2,1,0,5,-1,0
+");
+    assert_eq!(simulation.steps, 2);
   }
 
   #[test_case(vec![6, 9] => 18)]
   #[test_case(vec![6, 9, 9] => 18)]
   #[test_case(vec![18, 6, 9, 9] => 18)]
   #[test_case(vec![924, 2772, 924, 2772] => 2772)]
-  fn test_find_common_freq(input: Vec<isize>) -> isize {
+  #[test_case(vec![1 << 62, 3 << 62] => 3 << 62 ; "beyond isize")]
+  fn test_find_common_freq(input: Vec<u128>) -> u128 {
     input.iter().fold(input[0], |a, b| find_common_freq_gcd(a, *b))
   }
 
   #[test_case(6, 9 => 3)]
   #[test_case(9, 6 => 3)]
   #[test_case(1071, 462 => 21)]
-  fn test_gcd(a: isize, b: isize) -> isize {
+  fn test_gcd(a: u128, b: u128) -> u128 {
     gcd(a, b)
   }
 }
@@ -1,5 +1,6 @@
-use std::io;
+use std::io::{self, Write};
 use std::fs;
+use std::env;
 use std::fmt;
 use std::thread;
 use std::cmp::Ordering;
@@ -7,38 +8,11 @@ use std::time::Duration;
 use std::collections::VecDeque;
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq)]
-enum ParamMode {
-  Position,
-  Immediate,
-  Relative,
-}
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-enum PartState {
-  Run,
-  Exit,
-  NeedInput,
-}
+use crossterm::{cursor, event, execute, queue, style, terminal};
+use intcode::{Machine, Status};
 
-impl Default for PartState {
-  fn default() -> Self { PartState::Run }
-}
-
-#[derive(Debug)]
-enum OpCode {
-  Add = 1,
-  Multiply = 2,
-  Read = 3,
-  Write = 4,
-  JumpIfTrue = 5,
-  JumpIfFalse = 6,
-  LessThan = 7,
-  EqualTo = 8,
-  AdjustBase = 9,
-  Exit = 99,
-}
-use OpCode::*;
+/// How long a frame lasts when someone is watching.
+const FRAME: Duration = Duration::from_millis(50);
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Default)]
 struct Point {
@@ -46,413 +20,274 @@ struct Point {
   y: isize,
 }
 
-trait PipelinePart {
-  fn get_input_queue(&self) -> VecDeque<isize>;
-  fn get_output_queue(&self) -> VecDeque<isize>;
-  fn push_input(&mut self, input: isize);
-  fn pop_output(&mut self) -> isize;
-  fn output_available(&self) -> bool;
-  fn get_state(&self) -> PartState;
-  fn get_previous(&self) -> Option<usize>;
-  fn run(&mut self);
-  fn broken_pipe(&mut self);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tile {
+  Empty,
+  Wall,
+  Block,
+  Paddle,
+  Ball,
 }
 
-impl fmt::Debug for dyn PipelinePart {
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "state={:?} input={:?} output={:?})", self.get_state(), self.get_input_queue(), self.get_output_queue())
+impl Tile {
+  fn from_id(id: isize) -> Tile {
+    match id {
+      0 => Tile::Empty,
+      1 => Tile::Wall,
+      2 => Tile::Block,
+      3 => Tile::Paddle,
+      4 => Tile::Ball,
+      _ => panic!("invalid TileID: {}", id),
+    }
+  }
+
+  fn character(self) -> char {
+    match self {
+      Tile::Empty => ' ',
+      Tile::Wall => '#',
+      Tile::Block => '+',
+      Tile::Paddle => '_',
+      Tile::Ball => 'o',
+    }
   }
 }
 
+/// Everything the cabinet has drawn so far.
 #[derive(Debug, Default)]
-struct IntCode {
-  input_queue: VecDeque<isize>,
-  output_queue: VecDeque<isize>,
-  state: PartState,
-  previous: Option<usize>,
-  program: Vec<isize>,
-  ip: usize,
-  rel_base: isize,
+struct Screen {
+  tiles: HashMap<Point, Tile>,
+  score: isize,
+  ball: Option<Point>,
+  paddle: Option<Point>,
 }
 
-impl PipelinePart for IntCode {
-  fn get_input_queue(&self) -> VecDeque<isize> {
-    self.input_queue.clone()
-  }
-  fn get_output_queue(&self) -> VecDeque<isize> {
-    self.output_queue.clone()
-  }
-  fn push_input(&mut self, input: isize) {
-    self.input_queue.push_back(input);
-  }
-  fn pop_output(&mut self) -> isize {
-    self.output_queue.pop_front().unwrap()
-  }
-  fn output_available(&self) -> bool {
-    self.output_queue.len() > 0
-  }
-  fn get_state(&self) -> PartState {
-    self.state
-  }
-  fn get_previous(&self) -> Option<usize> {
-    self.previous
-  }
-  fn run(&mut self) {
-    self.state = run_program(self);
+impl Screen {
+  /// Draws what the cabinet output, three values at a time.
+  fn update(&mut self, output: &[isize]) {
+    for chunk in output.chunks(3) {
+      match *chunk {
+        [-1, 0, score] => self.score = score,
+        [x, y, id] => {
+          let point = Point{x, y};
+          let tile = Tile::from_id(id);
+          match tile {
+            Tile::Ball => self.ball = Some(point),
+            Tile::Paddle => self.paddle = Some(point),
+            _ => (),
+          }
+          self.tiles.insert(point, tile);
+        }
+        _ => panic!("incomplete tile: {:?}", chunk),
+      }
+    }
   }
-  fn broken_pipe(&mut self) {
-    panic!("IntCode expects input, but provider exited");
+
+  fn blocks(&self) -> usize {
+    self.tiles.values().filter(|&&tile| tile == Tile::Block).count()
   }
 }
 
-#[derive(Debug, PartialEq)]
-enum ConsoleState {
-  X,
-  Y,
-  TileID,
-  Score,
+impl fmt::Display for Screen {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "score: {}", self.score)?;
+    if self.tiles.is_empty() {
+      return Ok(());
+    }
+    let min_x = self.tiles.keys().map(|p| p.x).min().unwrap();
+    let max_x = self.tiles.keys().map(|p| p.x).max().unwrap();
+    let min_y = self.tiles.keys().map(|p| p.y).min().unwrap();
+    let max_y = self.tiles.keys().map(|p| p.y).max().unwrap();
+    for y in min_y..=max_y {
+      for x in min_x..=max_x {
+        let tile = self.tiles.get(&Point{x, y}).copied().unwrap_or(Tile::Empty);
+        write!(f, "{}", tile.character())?;
+      }
+      writeln!(f)?;
+    }
+    Ok(())
+  }
 }
 
-impl Default for ConsoleState {
-  fn default() -> Self { ConsoleState::X }
+/// Where the joystick goes every time the cabinet asks.
+trait Joystick {
+  /// -1 for left, 0 for neutral and 1 for right, or `None` to stop playing.
+  fn tilt(&mut self, screen: &Screen) -> io::Result<Option<isize>>;
 }
 
-#[derive(Debug, Default)]
-struct Console {
-  input_queue: VecDeque<isize>,
-  output_queue: VecDeque<isize>,
-  state: PartState,
-  previous: Option<usize>,
-  screen: HashMap<Point, isize>,
-  point: Point,
-  score: isize,
-  internal_state: ConsoleState,
-}
+/// Keeps the paddle under the ball, going by what's on the screen.
+struct Autoplay;
 
-impl PipelinePart for Console {
-  fn get_input_queue(&self) -> VecDeque<isize> {
-    self.input_queue.clone()
-  }
-  fn get_output_queue(&self) -> VecDeque<isize> {
-    self.output_queue.clone()
-  }
-  fn push_input(&mut self, input: isize) {
-    self.input_queue.push_back(input);
-  }
-  fn pop_output(&mut self) -> isize {
-    self.output_queue.pop_front().unwrap()
-  }
-  fn output_available(&self) -> bool {
-    self.output_queue.len() > 0
-  }
-  fn get_state(&self) -> PartState {
-    self.state
-  }
-  fn get_previous(&self) -> Option<usize> {
-    self.previous
-  }
-  fn run(&mut self) {
-    self.state = run_console(self);
-  }
-  fn broken_pipe(&mut self) {
-    self.state = exit_console(self);
+impl Joystick for Autoplay {
+  fn tilt(&mut self, screen: &Screen) -> io::Result<Option<isize>> {
+    let tilt = match (screen.paddle, screen.ball) {
+      (Some(paddle), Some(ball)) => match paddle.x.cmp(&ball.x) {
+        Ordering::Less => 1,
+        Ordering::Equal => 0,
+        Ordering::Greater => -1,
+      },
+      _ => 0,
+    };
+    Ok(Some(tilt))
   }
 }
 
-fn param_mode(digit: isize) -> ParamMode {
-  match digit {
-      0 => ParamMode::Position,
-      1 => ParamMode::Immediate,
-      2 => ParamMode::Relative,
-      _ => panic!("invalid parameter mode: {}", digit),
-  }
+/// Plays back a recorded log, and stops when it runs out.
+struct Replay {
+  tilts: VecDeque<isize>,
 }
 
-fn param_to_position(program: &mut Vec<isize>, param_pos: usize, mode: ParamMode, rel_base: isize) -> usize {
-  let result = match mode {
-    ParamMode::Position => program[param_pos] as usize,
-    ParamMode::Relative => (program[param_pos] + rel_base) as usize,
-    ParamMode::Immediate => panic!("Immediate can not be a position"),
-  };
-  if result >= program.len() {
-    program.resize(result + 1, 0);
+impl Joystick for Replay {
+  fn tilt(&mut self, _screen: &Screen) -> io::Result<Option<isize>> {
+    Ok(self.tilts.pop_front())
   }
-  result
 }
 
-fn param_to_value(program: &mut Vec<isize>, param_pos: usize, mode: ParamMode, rel_base: isize) -> isize {
-  match mode {
-    ParamMode::Position | ParamMode::Relative => {
-      let abs_pos = param_to_position(program, param_pos, mode, rel_base);
-      program[abs_pos]
-    },
-    ParamMode::Immediate => program[param_pos],
+/// The arrow keys or `a` and `d`, read while the frame lasts. Nothing pressed
+/// is neutral, and `q` or escape stops.
+struct Keyboard;
+
+impl Joystick for Keyboard {
+  fn tilt(&mut self, _screen: &Screen) -> io::Result<Option<isize>> {
+    let mut tilt = 0;
+    let mut left = FRAME;
+    while let Some(wait) = left.checked_sub(Duration::from_millis(1)) {
+      let start = std::time::Instant::now();
+      if !event::poll(wait)? {
+        break;
+      }
+      if let event::Event::Key(key) = event::read()? {
+        if key.kind != event::KeyEventKind::Release {
+          match key.code {
+            event::KeyCode::Left | event::KeyCode::Char('a') => tilt = -1,
+            event::KeyCode::Right | event::KeyCode::Char('d') => tilt = 1,
+            event::KeyCode::Char('q') | event::KeyCode::Esc => return Ok(None),
+            _ => (),
+          }
+        }
+      }
+      left = left.saturating_sub(start.elapsed());
+    }
+    Ok(Some(tilt))
   }
 }
 
-fn in_in_out(opcode: OpCode, inval1: isize, inval2: isize) -> isize {
-  match opcode {
-    Add => inval1 + inval2,
-    Multiply => inval1 * inval2,
-    LessThan => match inval1 < inval2 {
-      true => 1,
-      false => 0,
-    },
-    EqualTo => match inval1 == inval2 {
-      true => 1,
-      false => 0,
-    },
-    _ => panic!("program error"),
-  }
+fn parse_log(text: &str) -> Vec<isize> {
+  text.lines().map(|line| line.trim().parse().expect("not a tilt")).collect()
 }
 
-fn in_in_jump(opcode: OpCode, inval1: isize, inval2: isize, ip: &mut usize) {
-  match opcode {
-    JumpIfTrue => {
-      // jump if true
-      if inval1 != 0 {
-        *ip = inval2 as usize;
-      } else {
-        *ip += 3;
-      }
-    },
-    JumpIfFalse => {
-      // jump if false
-      if inval1 == 0 {
-        *ip = inval2 as usize;
-      } else {
-        *ip += 3;
-      }
-    },
-    _ => panic!("program error"),
-  }  
+fn format_log(tilts: &[isize]) -> String {
+  tilts.iter().map(|tilt| format!("{}\n", tilt)).collect()
 }
 
-fn run_program(intcode: &mut IntCode) -> PartState {
-  let program = &mut intcode.program;
-  let ip = &mut intcode.ip;
-  let rel_base = &mut intcode.rel_base;
-  loop {
-    let mut digits = Vec::new();
-    let mut remain = program[*ip];
-    for i in (2..5).rev() {
-      digits.push(remain / 10_isize.pow(i));
-      remain = remain % 10_isize.pow(i);
-    }
-    let opcode = match remain {
-      1 => Add,
-      2 => Multiply,
-      3 => Read,
-      4 => Write,
-      5 => JumpIfTrue,
-      6 => JumpIfFalse,
-      7 => LessThan,
-      8 => EqualTo,
-      9 => AdjustBase,
-      99 => Exit,
-      _ => panic!("invalid opcode: {}", remain),
-    };
-    let param_3_mode = param_mode(digits[0]);
-    let param_2_mode = param_mode(digits[1]);
-    let param_1_mode = param_mode(digits[2]);
-    match opcode {
-      // in in out
-      Add | Multiply | LessThan | EqualTo => {
-        let inval1 = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-        let inval2 = param_to_value(program, *ip + 2, param_2_mode, *rel_base);
-        let outpos = param_to_position(program, *ip + 3, param_3_mode, *rel_base);
-        program[outpos] = in_in_out(opcode, inval1, inval2);
-        *ip += 4;
-      },
-      // out
-      Read => {
-        // read from input
-        let outpos = param_to_position(program, *ip + 1, param_1_mode, *rel_base);
-        if intcode.input_queue.len() <= 0 {
-          return PartState::NeedInput;
-        }
-        program[outpos] = intcode.input_queue.pop_front().unwrap();
-        *ip += 2;
-      },
-      // in
-      Write | AdjustBase => {
-        let inval = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-        match opcode {
-          Write => {
-            intcode.output_queue.push_back(inval);
-            *ip += 2;
-            return PartState::Run;
-          },
-          AdjustBase => *rel_base += inval,
-          _ => panic!("program error"),          
-        }
-        *ip += 2;
-      },
-      // in in
-      JumpIfTrue | JumpIfFalse => {
-        let inval1 = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-        let inval2 = param_to_value(program, *ip + 2, param_2_mode, *rel_base);
-        in_in_jump(opcode, inval1, inval2, ip);
-      },
-      Exit => break,
+/// The cabinet, set to free play, with everything the joystick did.
+struct Arcade {
+  machine: Machine,
+  screen: Screen,
+  log: Vec<isize>,
+}
+
+impl Arcade {
+  fn new(mut program: Vec<isize>) -> Arcade {
+    program[0] = 2;
+    Arcade {
+      machine: Machine::new(program),
+      screen: Screen::default(),
+      log: Vec::new(),
     }
   }
-  PartState::Exit
-}
 
-fn show_screen(console: &mut Console) {
-  let min_x = console.screen.keys().map(|p| p.x).min().unwrap();
-  let max_x = console.screen.keys().map(|p| p.x).max().unwrap();
-  let min_y = console.screen.keys().map(|p| p.y).min().unwrap();
-  let max_y = console.screen.keys().map(|p| p.y).max().unwrap();
-  println!("score: {}", console.score);
-  for y in (min_y..=max_y).rev() {
-    for x in min_x..=max_x {
-      let tile = console.screen.get(&Point{x, y});
-      let character = match tile {
-        Some(0) => ' ',
-        Some(1) => '#',
-        Some(2) => '+',
-        Some(3) => '_',
-        Some(4) => 'o',
-        Some(_) => panic!("invalid TileID"),
-        None => ' ',
-      };
-      print!("{}", character);
+  /// Plays until the game is over or the joystick stops. `show` gets the
+  /// screen every time the cabinet waits for the joystick, and once more at
+  /// the end.
+  fn play(&mut self, joystick: &mut impl Joystick, mut show: impl FnMut(&Screen) -> io::Result<()>)
+    -> io::Result<Status> {
+    loop {
+      let status = self.machine.run_until_input().map_err(io::Error::other)?;
+      self.screen.update(&self.machine.take_output());
+      show(&self.screen)?;
+      if status == Status::Halted {
+        return Ok(status);
+      }
+      match joystick.tilt(&self.screen)? {
+        Some(tilt) => {
+          self.log.push(tilt);
+          self.machine.push_input(tilt);
+        }
+        None => return Ok(status),
+      }
     }
-    println!("");
   }
 }
 
-fn cheat(console: &mut Console) {
-  let paddle_x: isize = console.screen.iter().filter(|(_, &t)| t == 3).map(|(&p, _)| p.x).sum();
-  let ball_x: isize = console.screen.iter().filter(|(_, &t)| t == 4).map(|(&p, _)| p.x).sum();
-  thread::sleep(Duration::from_millis(50));
-  console.output_queue.truncate(0);
-  console.output_queue.push_back(match paddle_x.cmp(&ball_x) {
-    Ordering::Less => 1,
-    Ordering::Equal => 0,
-    Ordering::Greater => -1,
-  });
+/// Takes over the terminal for as long as it's around.
+struct Terminal {
+  stdout: io::Stdout,
 }
 
-fn run_console(console: &mut Console) -> PartState {
-  if console.internal_state == ConsoleState::X {
-    let x = console.input_queue.pop_front();
-    match x {
-      Some(x) => console.point.x = x,
-      None => return PartState::NeedInput,
-    }
-    console.internal_state = ConsoleState::Y;
+impl Terminal {
+  fn new() -> io::Result<Terminal> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    Ok(Terminal{stdout})
   }
-  if console.internal_state == ConsoleState::Y {
-    let y = console.input_queue.pop_front();
-    match y {
-      Some(y) => console.point.y = y,
-      None => return PartState::NeedInput,
-    }
-    if console.point == (Point{x: -1, y: 0}) {
-      console.internal_state = ConsoleState::Score;
-    } else {
-      console.internal_state = ConsoleState::TileID;      
-    }
-  }
-  if console.internal_state == ConsoleState::TileID {
-    let id = match console.input_queue.pop_front() {
-      Some(id) => id,
-      None => return PartState::NeedInput,
-    };
-    console.screen.insert(console.point, id);
-    show_screen(console);
-    if id == 4 {
-      cheat(console);
+
+  fn show(&mut self, screen: &Screen) -> io::Result<()> {
+    queue!(self.stdout, terminal::Clear(terminal::ClearType::All))?;
+    for (row, line) in screen.to_string().lines().enumerate() {
+      queue!(self.stdout, cursor::MoveTo(0, row as u16), style::Print(line))?;
     }
-    console.internal_state = ConsoleState::X;    
-  }
-  if console.internal_state == ConsoleState::Score {
-    console.score = match console.input_queue.pop_front() {
-      Some(score) => score,
-      None => return PartState::NeedInput,
-    };
-    show_screen(console);
-    console.internal_state = ConsoleState::X;    
+    self.stdout.flush()
   }
-  PartState::Run
 }
 
-fn exit_console(console: &mut Console) -> PartState {
-  println!("Final score: {}", console.score);
-  PartState::Exit
-}
-
-fn run_pipe(pipeline: &mut Vec<Box<dyn PipelinePart>>) -> io::Result<()> {
-  let mut exited = 0;
-  while exited < pipeline.len() {
-    exited = 0;
-    for index in 0..pipeline.len() {
-      loop {
-        match pipeline[index].get_state() {
-          PartState::Exit => {
-            exited += 1;
-            break; // go to the next part
-          },
-          PartState::Run => {
-            pipeline[index].run();
-            if pipeline[index].output_available() {
-              break;
-            }
-          },
-          PartState::NeedInput => {
-            match pipeline[index].get_previous() {
-              None => {
-                println!("please provide input: ");
-                let mut buf = String::new();
-                io::stdin().read_line(&mut buf).expect("input error");
-                let temp = match buf.trim().parse() {
-                  Ok(x) => x,
-                  Err(_) => break,
-                };
-                pipeline[index].push_input(temp);
-                pipeline[index].run();
-              },
-              Some(previdx) => if pipeline[previdx].output_available() {
-                let temp = pipeline[previdx].pop_output();
-                pipeline[index].push_input(temp);
-                pipeline[index].run();
-              } else {
-                match pipeline[previdx].get_state() {
-                  PartState::Exit => pipeline[index].broken_pipe(),
-                  _ => break, // go to the next part
-                }
-              },
-            }
-          }
-        }
-      }
-    }
+impl Drop for Terminal {
+  fn drop(&mut self) {
+    let _ = execute!(self.stdout, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
   }
-  Ok(())
 }
 
 fn main() -> io::Result<()> {
-  let mut program = fs::read_to_string("input.txt")?
+  let program = fs::read_to_string("input.txt")?
                     .trim()
                     .split(',')
                     .map(|x| x.parse().expect("not a number"))
                     .collect::<Vec<isize>>();
-  program[0] = 2;
-  let mut pipeline: Vec<Box<dyn PipelinePart>> = vec![
-    Box::new(IntCode{
-      program: program.to_vec(),
-      previous: Some(1),
-      ..Default::default()
-    }),
-    Box::new(Console{
-      previous: Some(0),
-      ..Default::default()
-    })
-  ];
-  run_pipe(&mut pipeline).expect("something went wrong");
+  let args = env::args().skip(1).collect::<Vec<_>>();
+  let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+  let mut arcade = Arcade::new(program);
+  match args.as_slice() {
+    [] => {
+      arcade.play(&mut Autoplay, |_| Ok(()))?;
+    }
+    ["watch"] => {
+      let mut terminal = Terminal::new()?;
+      arcade.play(&mut Autoplay, |screen| {
+        thread::sleep(FRAME);
+        terminal.show(screen)
+      })?;
+    }
+    ["play"] | ["play", _] => {
+      let mut terminal = Terminal::new()?;
+      arcade.play(&mut Keyboard, |screen| terminal.show(screen))?;
+      drop(terminal);
+      if let ["play", log] = args.as_slice() {
+        fs::write(log, format_log(&arcade.log))?;
+      }
+    }
+    ["replay", log] => {
+      let tilts = parse_log(&fs::read_to_string(log)?).into();
+      arcade.play(&mut Replay{tilts}, |_| Ok(()))?;
+    }
+    _ => {
+      eprintln!("usage: second [watch | play [log] | replay <log>]");
+      return Ok(());
+    }
+  }
+  println!("{}", arcade.screen);
+  println!("Blocks left: {}", arcade.screen.blocks());
+  println!("Final score: {}", arcade.screen.score);
   Ok(())
 }
 
@@ -460,151 +295,88 @@ fn main() -> io::Result<()> {
 mod tests {
   use super::*;
 
+  use intcode::asm::assemble;
   use test_case::test_case;
 
-  #[test]
-  fn day9_test_1() {
-    let program = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
-    let mut intcode = IntCode{
-        program: program.to_vec(),
-        ..Default::default()
-    };
-    while intcode.get_state() == PartState::Run {
-      intcode.run();
-    }
-    let output = intcode.output_queue.iter().map(|x| *x).collect::<Vec<isize>>();
-    assert_eq!(program, output);
+  /// A tiny cabinet: the ball bounces between x 0 and 5 along the top, and
+  /// the paddle has to be under it every time the joystick moves. The score
+  /// counts the catches, up to 10. Free play turns the first instruction
+  /// into a `mul`, which doesn't do anything either.
+  const CABINET: &str = "
+            add  [t], [t], [t]
+    loop:   out  [px]
+            out  #2
+            out  #3
+            out  [bx]
+            out  #1
+            out  #4
+            out  #-1
+            out  #0
+            out  [score]
+            eq   [score], #10, [t]
+            jnz  [t], #end
+            in   [j]
+            add  [px], [j], [px]
+            eq   [px], [bx], [t]
+            jz   [t], #end
+            add  [score], #1, [score]
+            add  [bx], [dx], [bx]
+            eq   [bx], #0, [t]
+            jnz  [t], #bounce
+            eq   [bx], #5, [t]
+            jz   [t], #loop
+    bounce: mul  [dx], #-1, [dx]
+            jz   #0, #loop
+    end:    hlt
+    px:     data 2
+    bx:     data 2
+    dx:     data 1
+    j:      data 0
+    t:      data 0
+    score:  data 0";
+
+  fn cabinet() -> Arcade {
+    Arcade::new(assemble(CABINET).unwrap())
+  }
+
+  #[test_case("0\n1\n1\n1\n-1\n-1\n-1\n-1\n-1\n1\n" => (Status::Halted, 10); "perfect")]
+  #[test_case("0\n1\n1\n0\n" => (Status::Halted, 3); "missed")]
+  #[test_case("0\n1\n" => (Status::NeedInput, 2); "ran out")]
+  fn test_replay(log: &str) -> (Status, isize) {
+    let mut arcade = cabinet();
+    let tilts = parse_log(log).into();
+    let status = arcade.play(&mut Replay{tilts}, |_| Ok(())).unwrap();
+    assert_eq!(format_log(&arcade.log), log);
+    (status, arcade.screen.score)
   }
 
   #[test]
-  fn day9_test_2() {
-    let program = vec![1102,34915192,34915192,7,4,7,99,0];
-    let mut intcode = IntCode{
-        program: program.to_vec(),
-        ..Default::default()
-    };
-    intcode.run();
-    assert_eq!(format!("{}", intcode.pop_output()).len(), 16);
+  fn test_autoplay() {
+    let mut arcade = cabinet();
+    let mut frames = 0;
+    let status = arcade.play(&mut Autoplay, |_| {
+      frames += 1;
+      Ok(())
+    });
+    assert_eq!(status.unwrap(), Status::Halted);
+    assert_eq!(arcade.screen.score, 10);
+    assert_eq!(frames, 11);
+
+    // And the log plays back the same game
+    let mut replay = cabinet();
+    let tilts = arcade.log.iter().copied().collect();
+    replay.play(&mut Replay{tilts}, |_| Ok(())).unwrap();
+    assert_eq!(replay.screen.score, 10);
   }
 
   #[test]
-  fn day9_test_3() {
-    let program = vec![104,1125899906842624,99];
-    let mut intcode = IntCode{
-        program: program.to_vec(),
-        ..Default::default()
-    };
-    intcode.run();
-    assert_eq!(intcode.pop_output(), 1125899906842624);
+  fn test_screen() {
+    let mut screen = Screen::default();
+    screen.update(&[0, 0, 1, 1, 0, 2, 2, 0, 1, 1, 1, 4, 1, 2, 3, -1, 0, 42]);
+    assert_eq!(screen.score, 42);
+    assert_eq!(screen.blocks(), 1);
+    assert_eq!(screen.ball, Some(Point{x: 1, y: 1}));
+    assert_eq!(screen.paddle, Some(Point{x: 1, y: 2}));
+    assert_eq!(screen.to_string(), "score: 42\n#+#\n o \n _ \n");
   }
-
-  #[test_case( vec![1,9,10,3,2,3,11,0,99,30,40,50] => vec![3500,9,10,70,2,3,11,0,99,30,40,50] ; "day 2 example 1")]
-  #[test_case( vec![1,0,0,0,99] => vec![2,0,0,0,99] ; "day 2 example 2")]
-  #[test_case( vec![2,3,0,3,99] => vec![2,3,0,6,99] ; "day 2 example 3")]
-  #[test_case( vec![2,4,4,5,99,0] => vec![2,4,4,5,99,9801] ; "day 2 example 4")]
-  #[test_case( vec![1,1,1,4,99,5,6,0,99] => vec![30,1,1,4,2,5,6,0,99] ; "day 2 example 5")]
-
-  #[test_case( vec![1002,4,3,4,33] => vec![1002,4,3,4,99] ; "day 5 example 1")]
-  #[test_case( vec![1101,100,-1,4,0] => vec![1101,100,-1,4,99] ; "day 5 example 2")]
-  fn pre_input_output(program: Vec<isize>) -> Vec<isize> {
-    let mut intcode = IntCode{
-        program: program.to_vec(),
-        ..Default::default()
-    };
-    intcode.run();
-    intcode.program.to_vec()
-  }
-
-  #[test_case( vec![3,9,8,9,10,9,4,9,99,-1,8], 8 => 1 ; "day 5 example 3a - equal to position mode")]
-  #[test_case( vec![3,9,8,9,10,9,4,9,99,-1,8], 234 => 0 ; "day 5 example 3b - equal to position mode")]
-
-  #[test_case( vec![3,9,7,9,10,9,4,9,99,-1,8], 7 => 1 ; "day 5 example 4a - less than position mode")]
-  #[test_case( vec![3,9,7,9,10,9,4,9,99,-1,8], 8 => 0 ; "day 5 example 4b - less than position mode")]
-  #[test_case( vec![3,9,7,9,10,9,4,9,99,-1,8], 9 => 0 ; "day 5 example 4c - less than position mode")]
-  #[test_case( vec![3,9,7,9,10,9,4,9,99,-1,8], -9 => 1 ; "day 5 example 4d - less than position mode")]
-
-  #[test_case( vec![3,3,1108,-1,8,3,4,3,99], 8 => 1 ; "day 5 example 5a - equal to immediate mode")]
-  #[test_case( vec![3,3,1108,-1,8,3,4,3,99], 234 => 0 ; "day 5 example 5b - equal to immediate mode")]
-
-  #[test_case( vec![3,3,1107,-1,8,3,4,3,99], 7 => 1 ; "day 5 example 6a - less than immediate mode")]
-  #[test_case( vec![3,3,1107,-1,8,3,4,3,99], 8 => 0 ; "day 5 example 6b - less than immediate mode")]
-  #[test_case( vec![3,3,1107,-1,8,3,4,3,99], 9 => 0 ; "day 5 example 6c - less than immediate mode")]
-  #[test_case( vec![3,3,1107,-1,8,3,4,3,99], -9 => 1 ; "day 5 example 6d - less than immediate mode")]
-
-  #[test_case( vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], 0 => 0 ; "day 5 example 7a - jump position mode")]
-  #[test_case( vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], 1 => 1 ; "day 5 example 7b - jump position mode")]
-  #[test_case( vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], -99 => 1 ; "day 5 example 7c - jump position mode")]
-
-  #[test_case( vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1], 0 => 0 ; "day 5 example 8a - jump immediate mode")]
-  #[test_case( vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1], 1 => 1 ; "day 5 example 8b - jump immediate mode")]
-  #[test_case( vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1], -99 => 1 ; "day 5 example 8c - jump immediate mode")]
-
-  #[test_case( vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104
-    ,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99], -99 => 999 ; "day 5 example 9a - long example")]
-  #[test_case( vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104
-    ,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99], 8 => 1000 ; "day 5 example 9b - long example")]
-  #[test_case( vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104
-    ,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99], 1337 => 1001 ; "day 5 example 9c - long example")]
-
-  fn simple_input_output(program: Vec<isize>, input: isize) -> isize {
-    let mut pipeline: Vec<Box<dyn PipelinePart>> = vec![
-      Box::new(IntCode{
-        program: program.to_vec(),
-        input_queue: VecDeque::from(vec![input]),
-        ..Default::default()
-      }),
-    ];
-    run_pipe(&mut pipeline).expect("something went wrong");
-    pipeline[0].pop_output()
-  }
-
-  #[test_case( vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0], vec![4,3,2,1,0], 0 => 43210 ; "day 7 example 1")]
-  #[test_case( vec![3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0], vec![0,1,2,3,4],
-    0 => 54321 ; "day 7 example 2")]
-  #[test_case( vec![3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0],
-    vec![1,0,4,3,2], 0 => 65210 ; "day 7 example 3")]
-  fn amp_chain(program: Vec<isize>, settings: Vec<isize>, input: isize) -> isize {
-    let mut pipeline: Vec<Box<dyn PipelinePart>> = Vec::new();
-    for (i, s) in settings.iter().enumerate() {
-      pipeline.push(
-        Box::new(IntCode{
-          program: program.to_vec(),
-          input_queue: VecDeque::from(vec![*s]),
-          previous: match i {
-            0 => None,
-            _ => Some(i - 1),
-          },
-          ..Default::default()
-        })
-      );
-    }
-    pipeline[0].push_input(input);
-    run_pipe(&mut pipeline).expect("something went wrong");
-    pipeline[settings.len() - 1].pop_output()
-  }
-
-  #[test_case( vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5],
-    vec![9,8,7,6,5], 0 => 139629729 ; "day 7 example 4")]
-  #[test_case( vec![3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54
-    ,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10], vec![9,7,8,5,6], 0 => 18216 ; "day 7 example 5")]
-  fn amp_chain_feedback(program: Vec<isize>, settings: Vec<isize>, input: isize) -> isize {
-    let mut pipeline: Vec<Box<dyn PipelinePart>> = Vec::new();
-    for (i, s) in settings.iter().enumerate() {
-      pipeline.push(
-        Box::new(IntCode{
-          program: program.to_vec(),
-          input_queue: VecDeque::from(vec![*s]),
-          previous: match i {
-            0 => Some(settings.len() - 1),
-            _ => Some(i - 1),
-          },
-          ..Default::default()
-        })
-      );
-    }
-    pipeline[0].push_input(input);
-    run_pipe(&mut pipeline).expect("something went wrong");
-    pipeline[settings.len() - 1].pop_output()
-  }
-
 }
@@ -1,10 +1,10 @@
 use std::io;
 use std::fs;
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::env;
+use std::collections::{BTreeMap, HashMap};
+use aoc_common::parse::unsigned;
 use nom::bytes::complete::tag;
-use nom::character::complete::{digit1, space1, alpha1};
-use nom::combinator::map;
+use nom::character::complete::{space1, alpha1};
 use nom::sequence::pair;
 use nom::multi::separated_list1;
 use nom::IResult;
@@ -19,17 +19,19 @@ struct Input {
 struct Reaction {
   output_count: usize,
   inputs: Vec<Input>,
-  spares: usize,
 }
 
-const MAX_ORE: usize = 1000000000000;
-
-fn parse_num(input: &str) -> IResult<&str, usize> {
-  map(digit1, |digit_str: &str| digit_str.parse::<usize>().unwrap())(input)
+/// What it takes to make something, and what's left over after.
+#[derive(Debug, Default, PartialEq)]
+struct Production {
+  ore: usize,
+  leftovers: BTreeMap<String, usize>,
 }
 
+const MAX_ORE: usize = 1000000000000;
+
 fn parse_input(input: &str) -> IResult<&str, Input> {
-  let (input, count) = parse_num(input)?;
+  let (input, count) = unsigned(input)?;
   let (input, _) = space1(input)?;
   let (input, name) = alpha1(input)?;
   Ok((input, Input{name: name.to_string(), count}))
@@ -40,10 +42,10 @@ fn parse_reaction(input: &str) -> IResult<&str, (String, Reaction)> {
   let (input, _) = space1(input)?;
   let (input, _) = tag("=>")(input)?;
   let (input, _) = space1(input)?;
-  let (input, output_count) = parse_num(input)?;
+  let (input, output_count) = unsigned(input)?;
   let (input, _) = space1(input)?;
   let (input, name) = alpha1(input)?;
-  Ok((input, (name.to_string(), Reaction{output_count, inputs, spares: 0})))
+  Ok((input, (name.to_string(), Reaction{output_count, inputs})))
 }
 
 fn string_to_reactions(string: &str) -> HashMap<String, Reaction> {
@@ -55,77 +57,117 @@ fn string_to_reactions(string: &str) -> HashMap<String, Reaction> {
   reactions
 }
 
-fn breakdown(reactions: &mut HashMap<String, Reaction>, output: &String, mut needed: usize) -> usize {
-  if output == "ORE" {
-    return needed;
-  }
-  let reaction = reactions.get(output).unwrap();
-  if needed <= reaction.spares {
-    if let Some(reaction) = reactions.get_mut(output) {
-      reaction.spares -= needed;
-      return 0;
-    } else {
-      panic!("No reaction for {} found", output);
+/// Every chemical after everything that's made from it, so by the time a
+/// chemical comes up, all of it that's ever going to be needed is known.
+fn topological_order(reactions: &HashMap<String, Reaction>) -> Vec<String> {
+  let mut users: HashMap<&str, usize> = HashMap::new();
+  for reaction in reactions.values() {
+    for input in &reaction.inputs {
+      *users.entry(&input.name).or_default() += 1;
     }
   }
-  if reaction.spares > 0 {
-    needed -= reaction.spares;
-  }
-  let mut multiplier: usize;
-  if reaction.output_count >= needed {
-    multiplier = 1;
-  } else {
-    multiplier = needed / reaction.output_count;
-    if needed % reaction.output_count > 0 {
-      multiplier += 1;
+  let mut ready = reactions.keys().map(String::as_str).filter(|name| !users.contains_key(name)).collect::<Vec<_>>();
+  ready.sort_unstable();
+  let mut order = Vec::new();
+  while let Some(name) = ready.pop() {
+    order.push(name.to_string());
+    let inputs = reactions.get(name).map_or(&[][..], |reaction| &reaction.inputs);
+    for input in inputs {
+      let count = users.get_mut(input.name.as_str()).unwrap();
+      *count -= 1;
+      if *count == 0 {
+        ready.push(&input.name);
+      }
     }
   }
-  let inputs = reaction.inputs.clone(); 
-  let total = inputs.iter().map(|input| breakdown(reactions, &input.name, input.count * multiplier)).sum();
-  if let Some(reaction) = reactions.get_mut(output) {
-    reaction.spares = (reaction.output_count * multiplier) - needed;
-  } else {
+  assert!(users.values().all(|&count| count == 0), "reactions go round in circles");
+  order
+}
+
+/// Makes `needed` of `output` with as few reactions as it takes, going from
+/// the end product back to the ore. Panics if no reaction makes `output`.
+fn produce(reactions: &HashMap<String, Reaction>, output: &str, needed: usize) -> Production {
+  if output != "ORE" && !reactions.contains_key(output) {
     panic!("No reaction for {} found", output);
   }
-  total
+  let mut wanted: HashMap<String, usize> = HashMap::new();
+  wanted.insert(output.to_string(), needed);
+  let mut production = Production::default();
+  for name in topological_order(reactions) {
+    let amount = wanted.get(&name).copied().unwrap_or(0);
+    if name == "ORE" {
+      production.ore = amount;
+      continue;
+    }
+    let reaction = match reactions.get(&name) {
+      Some(reaction) => reaction,
+      None => panic!("No reaction for {} found", name),
+    };
+    let runs = amount.div_ceil(reaction.output_count);
+    let leftover = runs * reaction.output_count - amount;
+    if leftover > 0 {
+      production.leftovers.insert(name.clone(), leftover);
+    }
+    for input in &reaction.inputs {
+      *wanted.entry(input.name.clone()).or_default() += input.count * runs;
+    }
+  }
+  production
 }
 
-fn approx_breakdown(reactions: &HashMap<String, Reaction>, output: &String, needed: f32) -> f32 {
-  if output == "ORE" {
-    return needed;
+/// The most `output` that can be made from `ore`. Ore per unit never goes up
+/// as more gets made, so a binary search finds it.
+fn output_for_ore(reactions: &HashMap<String, Reaction>, output: &str, ore: usize) -> usize {
+  let fits = |count| produce(reactions, output, count).ore <= ore;
+  let (mut low, mut high) = (0, 1);
+  while fits(high) {
+    low = high;
+    high *= 2;
+  }
+  // fits(low) and !fits(high)
+  while high - low > 1 {
+    let middle = low + (high - low) / 2;
+    if fits(middle) {
+      low = middle;
+    } else {
+      high = middle;
+    }
   }
-  let reaction = reactions.get(output).unwrap();
-  let multiplier = needed / reaction.output_count as f32;
-  let inputs = reaction.inputs.clone(); 
-  inputs.iter().map(|input| approx_breakdown(reactions, &input.name, input.count as f32 * multiplier)).sum()
+  low
 }
 
-fn output_for_size(mut reactions: &mut HashMap<String, Reaction>, output: &String) -> usize {
-  let mut needed = MAX_ORE / approx_breakdown(&reactions, output, 1 as f32) as usize;
-  println!("starting at {}", needed);
-  let ore = breakdown(&mut reactions, output, needed);
-  let mut adjustment: usize = 0;
-  println!("ore count: {:?}", ore);
-  match ore.cmp(&MAX_ORE) {
-    Ordering::Less => {
-      adjustment = 1;
-      while breakdown(&mut reactions, output, needed) < MAX_ORE {
-        needed += 1;
-      }
-    },
-    Ordering::Greater => {
-      while breakdown(&mut reactions, output, needed) > MAX_ORE {
-        needed -= 1;
-      }
+/// The reactions as a Graphviz graph, with arrows from ingredients to what
+/// they make.
+fn to_dot(reactions: &HashMap<String, Reaction>) -> String {
+  let mut dot = String::from("digraph reactions {\n  rankdir=LR;\n");
+  let mut names = reactions.keys().collect::<Vec<_>>();
+  names.sort();
+  for name in names {
+    let reaction = &reactions[name];
+    dot.push_str(&format!("  \"{}\" [label=\"{} {}\"];\n", name, reaction.output_count, name));
+    for input in &reaction.inputs {
+      dot.push_str(&format!("  \"{}\" -> \"{}\" [label=\"{}\"];\n", input.name, name, input.count));
     }
-    Ordering::Equal => return needed,
   }
-  needed - adjustment
+  dot.push_str("}\n");
+  dot
 }
 
 fn main() -> io::Result<()> {
-  let mut reactions = string_to_reactions(&fs::read_to_string("input.txt").unwrap());
-  println!("{}", output_for_size(&mut reactions, &"FUEL".to_string()));
+  let reactions = string_to_reactions(&fs::read_to_string("input.txt")?);
+  let args = env::args().skip(1).collect::<Vec<_>>();
+  match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+    [] => println!("{}", output_for_ore(&reactions, "FUEL", MAX_ORE)),
+    ["dot"] => print!("{}", to_dot(&reactions)),
+    ["leftovers", fuel] => {
+      let production = produce(&reactions, "FUEL", fuel.parse().expect("not a number"));
+      println!("ore: {}", production.ore);
+      for (name, count) in production.leftovers {
+        println!("{} {}", count, name);
+      }
+    }
+    _ => eprintln!("usage: second [dot | leftovers <fuel>]"),
+  }
   Ok(())
 }
 
@@ -186,8 +228,8 @@ mod tests {
 7 XCVML => 6 RJRHP
 5 BHXH, 4 VRPVC => 5 LTCX", 1, "FUEL" => 2210736 ; "example 5")]
   fn test(input: &str, needed: usize, name: &str) -> usize {
-    let mut reactions = string_to_reactions(input);
-    breakdown(&mut reactions, &name.to_string(), needed)
+    let reactions = string_to_reactions(input);
+    produce(&reactions, name, needed).ore
   }
 
   #[test_case("157 ORE => 5 NZVS
@@ -229,7 +271,63 @@ mod tests {
 7 XCVML => 6 RJRHP
 5 BHXH, 4 VRPVC => 5 LTCX", "FUEL" => 460664 ; "example 3")]
   fn test_part2(input: &str, name: &str) -> usize {
-    let mut reactions = string_to_reactions(input);
-    output_for_size(&mut reactions, &name.to_string())
+    let reactions = string_to_reactions(input);
+    output_for_ore(&reactions, name, MAX_ORE)
+  }
+
+  const EXAMPLE_1: &str = "10 ORE => 10 A
+1 ORE => 1 B
+7 A, 1 B => 1 C
+7 A, 1 C => 1 D
+7 A, 1 D => 1 E
+7 A, 1 E => 1 FUEL";
+
+  #[test_case(1 => (31, vec!["2 A".to_string()]) ; "one")]
+  #[test_case(2 => (62, vec!["4 A".to_string()]) ; "two")]
+  #[test_case(5 => (145, vec![]) ; "five")]
+  fn test_leftovers(fuel: usize) -> (usize, Vec<String>) {
+    let production = produce(&string_to_reactions(EXAMPLE_1), "FUEL", fuel);
+    let leftovers = production.leftovers.iter().map(|(name, count)| format!("{} {}", count, name)).collect();
+    (production.ore, leftovers)
+  }
+
+  #[test_case(EXAMPLE_1, 31 => 1 ; "just enough")]
+  #[test_case(EXAMPLE_1, 30 => 0 ; "not enough")]
+  #[test_case(EXAMPLE_1, 145 => 5 ; "no leftovers")]
+  fn test_output_for_ore(input: &str, ore: usize) -> usize {
+    output_for_ore(&string_to_reactions(input), "FUEL", ore)
+  }
+
+  #[test]
+  fn test_topological_order() {
+    let reactions = string_to_reactions(EXAMPLE_1);
+    assert_eq!(topological_order(&reactions), ["FUEL", "E", "D", "C", "B", "A", "ORE"]);
+  }
+
+  #[test]
+  #[should_panic(expected = "round in circles")]
+  fn test_cycle() {
+    topological_order(&string_to_reactions("1 ORE, 1 B => 1 A\n1 A => 1 B\n1 A => 1 FUEL"));
+  }
+
+  #[test]
+  #[should_panic(expected = "No reaction for GOLD found")]
+  fn test_unknown_output() {
+    let reactions = string_to_reactions("10 ORE => 10 A\n7 A, 1 ORE => 1 FUEL");
+    output_for_ore(&reactions, "GOLD", MAX_ORE);
+  }
+
+  #[test]
+  fn test_to_dot() {
+    let reactions = string_to_reactions("10 ORE => 10 A\n7 A, 1 ORE => 1 FUEL");
+    assert_eq!(to_dot(&reactions), "digraph reactions {
+  rankdir=LR;
+  \"A\" [label=\"10 A\"];
+  \"ORE\" -> \"A\" [label=\"10\"];
+  \"FUEL\" [label=\"1 FUEL\"];
+  \"A\" -> \"FUEL\" [label=\"7\"];
+  \"ORE\" -> \"FUEL\" [label=\"1\"];
+}
+");
   }
 }
\ No newline at end of file
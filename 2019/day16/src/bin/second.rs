@@ -1,16 +1,20 @@
+#![cfg_attr(test, feature(test))]
+
 use std::fs;
 use std::io;
 use std::cmp;
-use std::time::Instant;
 use rayon::prelude::*;
 
+#[cfg(test)]
 const BASE_PATTERN: [isize; 4] = [0, 1, 0, -1];
 
+#[cfg(test)]
 fn generate_multiplier(inpos: usize, outpos: usize) -> isize {
   let inpos = inpos + 1; // since we always want to skip the first output
   BASE_PATTERN[(inpos / (outpos + 1) % 4) as usize]
 }
 
+#[cfg(test)]
 fn repeat(input: Vec<isize>, times: usize) -> Vec<isize> {
   let mut output: Vec<isize> = Vec::with_capacity(10000 * input.len());
   for _ in 0..times {
@@ -19,6 +23,9 @@ fn repeat(input: Vec<isize>, times: usize) -> Vec<isize> {
   output
 }
 
+// The straightforward version, kept around to check and benchmark `fft`
+// against.
+#[cfg(test)]
 fn selective_output(mut input: Vec<isize>, needed_output: &[usize], phases: usize) -> Vec<isize> {
   let inlen = input.len();
   let mut output = Vec::with_capacity(inlen);
@@ -27,7 +34,6 @@ fn selective_output(mut input: Vec<isize>, needed_output: &[usize], phases: usiz
   if phases > 1 {
     input = selective_output(input, &needed_output, phases - 1);
   }
-  let phasestart = Instant::now();
   let alltemp: Vec<(usize, isize)> = needed_output.par_chunks(CHUNKLEN).flat_map(|chunk| {
     let chunktemp: Vec<(usize, isize)> = chunk.iter().map(|o| {
       let mut total: isize = 0;
@@ -53,29 +59,58 @@ fn selective_output(mut input: Vec<isize>, needed_output: &[usize], phases: usiz
   for temp in alltemp {
     output[temp.0] = temp.1;
   }
-  println!("phase {} done after {:?}", phases, phasestart.elapsed());
   output
 }
 
-fn calc_message(input: Vec<isize>) -> String {
-  let mut offset = input[0] as usize;
-  for i in &input[1..7] {
-    offset *= 10;
-    offset += *i as usize;
-  }
-  let mut needed_output: Vec<usize> = Vec::new();
-  let inlen = input.len();
-  for u in offset..inlen {
-    needed_output.push(u);
-  }
-//  let needed_output = needed_output.iter().map(|x| *x).collect();
-  let output = selective_output(input, &needed_output, 100);
-  let mut message = output[offset] as usize;
-  for i in &output[(offset+1)..(offset+8)] {
-    message *= 10;
-    message += *i as usize;
+/// Runs `phases` phases over `input` repeated `times` times, and returns
+/// `count` digits of the result starting at `offset`.
+///
+/// An output digit never depends on the input before it, so only the
+/// signal from `offset` onwards is kept. Each phase then builds prefix sums
+/// over that, so every block of ones or minus ones in the pattern costs a
+/// single subtraction, whichever half of the signal the offset is in.
+fn fft(input: &[isize], times: usize, offset: usize, count: usize, phases: usize) -> Vec<isize> {
+  let total = input.len() * times;
+  assert!(offset + count <= total, "can't read {} digits at {} from a signal of {}", count, offset, total);
+  let mut signal: Vec<isize> = (offset..total).map(|i| input[i % input.len()]).collect();
+  let len = signal.len();
+  let mut sums: Vec<isize> = vec![0; len + 1];
+  for _ in 0..phases {
+    for (i, value) in signal.iter().enumerate() {
+      sums[i + 1] = sums[i] + value;
+    }
+    let block_sum = |start: usize, width: usize| {
+      sums[cmp::min(start + width, len)] - sums[start]
+    };
+    signal = (0..len).into_par_iter().map(|i| {
+      // pattern blocks are as wide as the absolute output position + 1,
+      // and the first one of ones starts right at the output position
+      let width = offset + i + 1;
+      let mut total = 0;
+      let mut start = i;
+      while start < len {
+        total += block_sum(start, width);
+        if start + 2 * width < len {
+          total -= block_sum(start + 2 * width, width);
+        }
+        start += 4 * width;
+      }
+      total.abs() % 10
+    }).collect();
   }
-  format!("{}", message)
+  signal.truncate(count);
+  signal
+}
+
+fn digits_to_string(digits: &[isize]) -> String {
+  digits.iter().map(|d| d.to_string()).collect()
+}
+
+/// The message in `input` repeated `times` times, found at the offset given
+/// by its first seven digits.
+fn calc_message(input: &[isize], times: usize) -> String {
+  let offset = input[..7].iter().fold(0, |offset, digit| offset * 10 + *digit as usize);
+  digits_to_string(&fft(input, times, offset, 8, 100))
 }
 
 fn main() -> io::Result<()> {
@@ -84,10 +119,7 @@ fn main() -> io::Result<()> {
               .chars()
               .map(|x| x.to_digit(10).unwrap() as isize)
               .collect::<Vec<_>>();
-  println!("{:?}", input.len());
-  let input = repeat(input, 10000);
-  println!("{:?}", input.len());
-  println!("{:?}", calc_message(input));
+  println!("{:?}", calc_message(&input, 10000));
   Ok(())
 }
 
@@ -95,7 +127,15 @@ fn main() -> io::Result<()> {
 mod tests {
   use super::*;
 
+  extern crate test as std_test;
   use test_case::test_case;
+  use std_test::{black_box, Bencher};
+
+  fn digits(input: &str) -> Vec<isize> {
+    input.chars()
+    .map(|x| x.to_digit(10).unwrap() as isize)
+    .collect()
+  }
 
   #[test]
   fn check_generator() {
@@ -118,10 +158,50 @@ mod tests {
   #[test_case("02935109699940807407585447034323" => "78725270"; "example 2")]
   #[test_case("03081770884921959731165446850517" => "53553731"; "example 3")]
   fn test_part2(input: &str) -> String {
-    let input = input.chars()
-                .map(|x| x.to_digit(10).unwrap() as isize)
-                .collect::<Vec<_>>();
-    let input = repeat(input, 10000);
-    calc_message(input)
+    calc_message(&digits(input), 10000)
+  }
+
+  #[test_case("12345678", 1 => "48226158"; "example 1")]
+  #[test_case("12345678", 2 => "34040438"; "example 2")]
+  #[test_case("12345678", 3 => "03415518"; "example 3")]
+  #[test_case("12345678", 4 => "01029498"; "example 4")]
+  #[test_case("80871224585914546619083218645595", 100 => "24176176"; "example 5")]
+  #[test_case("19617804207202209144916044189917", 100 => "73745418"; "example 6")]
+  #[test_case("69317163492948606335995924319873", 100 => "52432133"; "example 7")]
+  fn test_part1(input: &str, phases: usize) -> String {
+    digits_to_string(&fft(&digits(input), 1, 0, 8, phases))
+  }
+
+  #[test_case(0; "from the start")]
+  #[test_case(7; "in the first half")]
+  #[test_case(101; "around the middle")]
+  #[test_case(250; "in the second half")]
+  fn test_against_naive(offset: usize) {
+    let input = digits("03036732577212944063491565474664");
+    let repeated = repeat(input.clone(), 10);
+    let len = repeated.len();
+    let needed_output: Vec<usize> = (offset..len).collect();
+    let naive = selective_output(repeated, &needed_output, 10);
+    assert_eq!(fft(&input, 10, offset, len - offset, 10), naive[offset..]);
+  }
+
+  #[test]
+  #[should_panic(expected = "can't read 8 digits at 30")]
+  fn test_offset_past_the_end() {
+    fft(&digits("12345678901234567890123456789012"), 1, 30, 8, 1);
+  }
+
+  #[bench]
+  fn bench_naive(b: &mut Bencher) {
+    let input = digits("80871224585914546619083218645595");
+    let repeated = repeat(input, 100);
+    let needed_output: Vec<usize> = (0..repeated.len()).collect();
+    b.iter(|| selective_output(black_box(repeated.clone()), &needed_output, 10));
+  }
+
+  #[bench]
+  fn bench_prefix_sums(b: &mut Bencher) {
+    let input = digits("80871224585914546619083218645595");
+    b.iter(|| fft(black_box(&input), 100, 0, input.len() * 100, 10));
   }
 }
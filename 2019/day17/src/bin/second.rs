@@ -1,40 +1,26 @@
-use std::char;
 use std::collections::HashMap;
-use std::collections::VecDeque;
-use std::fmt;
 use std::fs;
 use std::io;
 use std::ops;
-use std::process;
 use std::thread;
 use std::time::Duration;
 
-#[derive(Debug, PartialEq)]
-enum ParamMode {
-    Position,
-    Immediate,
-    Relative,
-}
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-enum PartState {
-    Run,
-    Exit,
-    NeedInput,
-}
-
-impl Default for PartState {
-    fn default() -> Self {
-        PartState::Run
-    }
-}
+use intcode::{Ascii, Machine, Status};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Turn {
     Left,
     Right,
 }
 
+/// A turn and how far to go after it.
+type Move = (Turn, usize);
+
+/// Longest line the robot takes, not counting the newline.
+const MAX_LENGTH: usize = 20;
+
+const FUNCTIONS: usize = 3;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum Direction {
     North = 1,
@@ -102,21 +88,6 @@ impl ops::Neg for Direction {
     }
 }
 
-#[derive(Debug)]
-enum OpCode {
-    Add = 1,
-    Multiply = 2,
-    Read = 3,
-    Write = 4,
-    JumpIfTrue = 5,
-    JumpIfFalse = 6,
-    LessThan = 7,
-    EqualTo = 8,
-    AdjustBase = 9,
-    Exit = 99,
-}
-use OpCode::*;
-
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Default)]
 struct Point {
     x: isize,
@@ -160,71 +131,6 @@ impl ops::AddAssign<Direction> for Point {
     }
 }
 
-trait PipelinePart {
-    fn get_input_queue(&self) -> VecDeque<isize>;
-    fn get_output_queue(&self) -> VecDeque<isize>;
-    fn push_input(&mut self, input: isize);
-    fn pop_output(&mut self) -> isize;
-    fn output_available(&self) -> bool;
-    fn get_state(&self) -> PartState;
-    fn get_previous(&self) -> Option<usize>;
-    fn run(&mut self);
-    fn broken_pipe(&mut self);
-}
-
-impl fmt::Debug for dyn PipelinePart {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "state={:?} input={:?} output={:?})",
-            self.get_state(),
-            self.get_input_queue(),
-            self.get_output_queue()
-        )
-    }
-}
-
-#[derive(Debug, Default)]
-struct IntCode {
-    input_queue: VecDeque<isize>,
-    output_queue: VecDeque<isize>,
-    state: PartState,
-    previous: Option<usize>,
-    program: Vec<isize>,
-    ip: usize,
-    rel_base: isize,
-}
-
-impl PipelinePart for IntCode {
-    fn get_input_queue(&self) -> VecDeque<isize> {
-        self.input_queue.clone()
-    }
-    fn get_output_queue(&self) -> VecDeque<isize> {
-        self.output_queue.clone()
-    }
-    fn push_input(&mut self, input: isize) {
-        self.input_queue.push_back(input);
-    }
-    fn pop_output(&mut self) -> isize {
-        self.output_queue.pop_front().unwrap()
-    }
-    fn output_available(&self) -> bool {
-        !self.output_queue.is_empty()
-    }
-    fn get_state(&self) -> PartState {
-        self.state
-    }
-    fn get_previous(&self) -> Option<usize> {
-        self.previous
-    }
-    fn run(&mut self) {
-        self.state = run_program(self);
-    }
-    fn broken_pipe(&mut self) {
-        panic!("IntCode expects input, but provider exited");
-    }
-}
-
 #[derive(Debug, PartialEq, Clone)]
 enum TileType {
     Scaffold,
@@ -236,10 +142,6 @@ type TileMap = HashMap<Point, TileType>;
 
 #[derive(Debug, Default)]
 struct Camera {
-    input_queue: VecDeque<isize>,
-    output_queue: VecDeque<isize>,
-    state: PartState,
-    previous: Option<usize>,
     map: TileMap,
     position: Point,
     draw_counter: usize,
@@ -249,234 +151,13 @@ struct Camera {
     program: Vec<isize>,
 }
 
-impl PipelinePart for Camera {
-    fn get_input_queue(&self) -> VecDeque<isize> {
-        self.input_queue.clone()
-    }
-    fn get_output_queue(&self) -> VecDeque<isize> {
-        self.output_queue.clone()
-    }
-    fn push_input(&mut self, input: isize) {
-        self.input_queue.push_back(input);
-    }
-    fn pop_output(&mut self) -> isize {
-        self.output_queue.pop_front().unwrap()
-    }
-    fn output_available(&self) -> bool {
-        !self.output_queue.is_empty()
-    }
-    fn get_state(&self) -> PartState {
-        self.state
-    }
-    fn get_previous(&self) -> Option<usize> {
-        self.previous
-    }
-    fn run(&mut self) {
-        self.state = run_camera(self);
-    }
-    fn broken_pipe(&mut self) {
-        self.state = exit_camera(self);
-    }
-}
-
 #[derive(Debug, Default)]
 struct BotDriver {
-    input_queue: VecDeque<isize>,
-    output_queue: VecDeque<isize>,
-    state: PartState,
-    previous: Option<usize>,
     map: TileMap,
     bot_position: Point,
     bot_direction: Direction,
 }
 
-impl PipelinePart for BotDriver {
-    fn get_input_queue(&self) -> VecDeque<isize> {
-        self.input_queue.clone()
-    }
-    fn get_output_queue(&self) -> VecDeque<isize> {
-        self.output_queue.clone()
-    }
-    fn push_input(&mut self, input: isize) {
-        self.input_queue.push_back(input);
-    }
-    fn pop_output(&mut self) -> isize {
-        self.output_queue.pop_front().unwrap()
-    }
-    fn output_available(&self) -> bool {
-        !self.output_queue.is_empty()
-    }
-    fn get_state(&self) -> PartState {
-        self.state
-    }
-    fn get_previous(&self) -> Option<usize> {
-        self.previous
-    }
-    fn run(&mut self) {
-        self.state = run_driver(self);
-    }
-    fn broken_pipe(&mut self) {
-        self.state = exit_driver();
-    }
-}
-
-fn param_mode(digit: isize) -> ParamMode {
-    match digit {
-        0 => ParamMode::Position,
-        1 => ParamMode::Immediate,
-        2 => ParamMode::Relative,
-        _ => panic!("invalid parameter mode: {}", digit),
-    }
-}
-
-fn param_to_position(
-    program: &mut Vec<isize>,
-    param_pos: usize,
-    mode: ParamMode,
-    rel_base: isize,
-) -> usize {
-    let result = match mode {
-        ParamMode::Position => program[param_pos] as usize,
-        ParamMode::Relative => (program[param_pos] + rel_base) as usize,
-        ParamMode::Immediate => panic!("Immediate can not be a position"),
-    };
-    if result >= program.len() {
-        program.resize(result + 1, 0);
-    }
-    result
-}
-
-fn param_to_value(
-    program: &mut Vec<isize>,
-    param_pos: usize,
-    mode: ParamMode,
-    rel_base: isize,
-) -> isize {
-    match mode {
-        ParamMode::Position | ParamMode::Relative => {
-            let abs_pos = param_to_position(program, param_pos, mode, rel_base);
-            program[abs_pos]
-        }
-        ParamMode::Immediate => program[param_pos],
-    }
-}
-
-fn in_in_out(opcode: OpCode, inval1: isize, inval2: isize) -> isize {
-    match opcode {
-        Add => inval1 + inval2,
-        Multiply => inval1 * inval2,
-        LessThan => {
-            if inval1 < inval2 {
-                1
-            } else {
-                0
-            }
-        }
-        EqualTo => {
-            if inval1 == inval2 {
-                1
-            } else {
-                0
-            }
-        }
-        _ => panic!("program error"),
-    }
-}
-
-fn in_in_jump(opcode: OpCode, inval1: isize, inval2: isize, ip: &mut usize) {
-    match opcode {
-        JumpIfTrue => {
-            // jump if true
-            if inval1 != 0 {
-                *ip = inval2 as usize;
-            } else {
-                *ip += 3;
-            }
-        }
-        JumpIfFalse => {
-            // jump if false
-            if inval1 == 0 {
-                *ip = inval2 as usize;
-            } else {
-                *ip += 3;
-            }
-        }
-        _ => panic!("program error"),
-    }
-}
-
-fn run_program(intcode: &mut IntCode) -> PartState {
-    let program = &mut intcode.program;
-    let ip = &mut intcode.ip;
-    let rel_base = &mut intcode.rel_base;
-    loop {
-        let mut digits = Vec::new();
-        let mut remain = program[*ip];
-        for i in (2..5).rev() {
-            digits.push(remain / 10_isize.pow(i));
-            remain %= 10_isize.pow(i);
-        }
-        let opcode = match remain {
-            1 => Add,
-            2 => Multiply,
-            3 => Read,
-            4 => Write,
-            5 => JumpIfTrue,
-            6 => JumpIfFalse,
-            7 => LessThan,
-            8 => EqualTo,
-            9 => AdjustBase,
-            99 => Exit,
-            _ => panic!("invalid opcode: {}", remain),
-        };
-        let param_3_mode = param_mode(digits[0]);
-        let param_2_mode = param_mode(digits[1]);
-        let param_1_mode = param_mode(digits[2]);
-        match opcode {
-            // in in out
-            Add | Multiply | LessThan | EqualTo => {
-                let inval1 = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-                let inval2 = param_to_value(program, *ip + 2, param_2_mode, *rel_base);
-                let outpos = param_to_position(program, *ip + 3, param_3_mode, *rel_base);
-                program[outpos] = in_in_out(opcode, inval1, inval2);
-                *ip += 4;
-            }
-            // out
-            Read => {
-                // read from input
-                let outpos = param_to_position(program, *ip + 1, param_1_mode, *rel_base);
-                if intcode.input_queue.is_empty() {
-                    return PartState::NeedInput;
-                }
-                program[outpos] = intcode.input_queue.pop_front().unwrap();
-                *ip += 2;
-            }
-            // in
-            Write | AdjustBase => {
-                let inval = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-                match opcode {
-                    Write => {
-                        intcode.output_queue.push_back(inval);
-                        *ip += 2;
-                        return PartState::Run;
-                    }
-                    AdjustBase => *rel_base += inval,
-                    _ => panic!("program error"),
-                }
-                *ip += 2;
-            }
-            // in in
-            JumpIfTrue | JumpIfFalse => {
-                let inval1 = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-                let inval2 = param_to_value(program, *ip + 2, param_2_mode, *rel_base);
-                in_in_jump(opcode, inval1, inval2, ip);
-            }
-            Exit => break,
-        }
-    }
-    PartState::Exit
-}
-
 fn show_map(camera: &mut Camera, force: bool) {
     println!();
     println!();
@@ -508,105 +189,78 @@ fn show_map(camera: &mut Camera, force: bool) {
     thread::sleep(Duration::from_millis(100));
 }
 
-fn run_camera(camera: &mut Camera) -> PartState {
-    if camera.input_queue.is_empty() {
-        return PartState::NeedInput;
-    }
-    let input = camera.input_queue.pop_front().unwrap();
-    if input > 255 {
-        println!("final output: {}", input);
-        process::exit(0);
-    }
-    match input as u8 {
-        46 => {
+fn run_camera(camera: &mut Camera, input: char) {
+    match input {
+        '.' => {
             camera.map.insert(camera.position, TileType::Open);
         }
-        35 => {
+        '#' => {
             camera.map.insert(camera.position, TileType::Scaffold);
         }
-        10 => (),
-        b'v' | b'^' | b'<' | b'>' => {
+        '\n' => (),
+        'v' | '^' | '<' | '>' => {
             camera.map.insert(camera.position, TileType::Scaffold);
             camera.bot_position = camera.position;
-            camera.bot_direction = match input as u8 {
-                b'v' => Direction::South,
-                b'^' => Direction::North,
-                b'<' => Direction::West,
-                b'>' => Direction::East,
+            camera.bot_direction = match input {
+                'v' => Direction::South,
+                '^' => Direction::North,
+                '<' => Direction::West,
+                '>' => Direction::East,
                 _ => panic!("should not happen"),
             }
         }
         _ => {
-            println!("unexpected char: {} {:?}", input, char::from_u32(input as u32));
+            println!("unexpected char: {} {:?}", input as u32, input);
         }
     }
-    if input == 10 {
+    if input == '\n' {
         camera.position.x = 0;
         camera.position.y += 1;
     } else {
         camera.position.x += 1;
     }
-    PartState::Run
 }
 
 fn is_scaffold(map: &TileMap, mut tile: Point, to: Direction) -> bool {
     tile += to;
-    if let Some(TileType::Scaffold) = map.get(&tile) {
-        println!("is_scaffold {:?} {:?} true", tile, to);
-        return true;
-    }
-    println!("is_scaffold {:?} {:?} false", tile, to);
-    false
+    matches!(map.get(&tile), Some(TileType::Scaffold))
 }
 
-fn exit_camera(camera: &mut Camera) -> PartState {
+/// Shows what the camera saw and, if it's meant to, drives the robot around
+/// the scaffold. Returns the dust the robot collected.
+fn exit_camera(camera: &mut Camera) -> Option<isize> {
     show_map(camera, true);
     println!("bot: {:?} {:?}", camera.bot_direction, camera.bot_position);
-    if camera.run_driver {
-        camera.program[0] = 2;
-        let mut bot_pipeline: Vec<Box<dyn PipelinePart>> = vec![
-            Box::new(BotDriver {
-                previous: None,
-                map: camera.map.clone(),
-                bot_position: camera.bot_position,
-                bot_direction: camera.bot_direction,
-                ..Default::default()
-            }),
-            Box::new(IntCode {
-                program: camera.program.to_vec(),
-                previous: Some(0),
-                ..Default::default()
-            }),
-            Box::new(Camera {
-                previous: Some(1),
-                run_driver: false,
-                ..Default::default()
-            }),
-        ];
-        run_pipe(&mut bot_pipeline).expect("something went wrong");
+    if !camera.run_driver {
+        return None;
+    }
+    camera.program[0] = 2;
+    let mut driver = BotDriver {
+        map: camera.map.clone(),
+        bot_position: camera.bot_position,
+        bot_direction: camera.bot_direction,
+    };
+    let mut ascii = Ascii::new(Machine::new(camera.program.to_vec()));
+    ascii.send(&run_driver(&mut driver));
+    match ascii.run() {
+        Ok(Status::Halted) => (),
+        Ok(status) => panic!("robot fucked: {:?}", status),
+        Err(error) => panic!("program error: {}", error),
     }
-    PartState::Exit
+    ascii.values().first().copied()
 }
 
-fn run_driver(driver: &mut BotDriver) -> PartState {
-//    let pos = &mut driver.bot_position;
-//    let dir = &mut driver.bot_direction;
-//    let map = &mut driver.map;
-    let mut steps: Vec<(Turn, usize)> = Vec::new();
+/// Walks the scaffold to its end, turning only where it has to.
+fn find_path(driver: &mut BotDriver) -> Vec<Move> {
+    let mut steps = Vec::new();
     loop {
-        println!("loop");
         let turn = if is_scaffold(&driver.map, driver.bot_position, driver.bot_direction + Turn::Left) {
-            Some(Turn::Left)
+            Turn::Left
         } else if is_scaffold(&driver.map, driver.bot_position, driver.bot_direction + Turn::Right) {
-            Some(Turn::Right)
+            Turn::Right
         } else {
-            None
+            return steps;
         };
-        if let None = turn {
-            println!("fuck");
-            break;
-        }
-        let turn = turn.unwrap();
         let mut length: usize = 0;
         driver.bot_direction += turn;
         while is_scaffold(&driver.map, driver.bot_position, driver.bot_direction) {
@@ -615,71 +269,107 @@ fn run_driver(driver: &mut BotDriver) -> PartState {
         }
         steps.push((turn, length));
     }
-    println!("steps: {:?}", steps);
-    let program = "A,B,A,B,C,C,B,A,B,C
-L,8,R,12,R,12,R,10
-R,10,R,12,R,10
-L,10,R,10,L,6
-y
-";
-    program.chars().for_each(|c| driver.output_queue.push_back(c as isize));
-    println!("{:?}", driver.output_queue);
-    PartState::Exit
 }
 
-fn exit_driver() -> PartState {
-    PartState::Exit
+fn format_moves(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|(turn, length)| match turn {
+            Turn::Left => format!("L,{}", length),
+            Turn::Right => format!("R,{}", length),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
-fn run_pipe(pipeline: &mut Vec<Box<dyn PipelinePart>>) -> io::Result<()> {
-    let mut exited = 0;
-    while exited < pipeline.len() {
-        exited = 0;
-        for index in 0..pipeline.len() {
-            loop {
-                match pipeline[index].get_state() {
-                    PartState::Exit => {
-                        exited += 1;
-                        break; // go to the next part
-                    }
-                    PartState::Run => {
-                        pipeline[index].run();
-                        if pipeline[index].output_available() {
-                            break;
-                        }
-                    }
-                    PartState::NeedInput => {
-                        match pipeline[index].get_previous() {
-                            None => {
-                                println!("please provide input: ");
-                                let mut buf = String::new();
-                                io::stdin().read_line(&mut buf).expect("input error");
-                                let temp = match buf.trim().parse() {
-                                    Ok(x) => x,
-                                    Err(_) => break,
-                                };
-                                pipeline[index].push_input(temp);
-                                pipeline[index].run();
-                            }
-                            Some(previdx) => {
-                                if pipeline[previdx].output_available() {
-                                    let temp = pipeline[previdx].pop_output();
-                                    pipeline[index].push_input(temp);
-                                    pipeline[index].run();
-                                } else {
-                                    match pipeline[previdx].get_state() {
-                                        PartState::Exit => pipeline[index].broken_pipe(),
-                                        _ => break, // go to the next part
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+/// The main routine, as indices into the functions, and the functions.
+#[derive(Debug, PartialEq)]
+struct Routines {
+    main: Vec<usize>,
+    functions: Vec<Vec<Move>>,
+}
+
+impl Routines {
+    /// Everything the robot asks for, one line each, ending with whether it
+    /// should show a video feed.
+    fn to_input(&self, video: bool) -> String {
+        let main = self
+            .main
+            .iter()
+            .map(|&function| ((b'A' + function as u8) as char).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut input = format!("{}\n", main);
+        for function in 0..FUNCTIONS {
+            let moves = self.functions.get(function).map_or(&[][..], |moves| moves);
+            input.push_str(&format_moves(moves));
+            input.push('\n');
+        }
+        input.push_str(if video { "y\n" } else { "n\n" });
+        input
+    }
+}
+
+/// Splits `path` into a main routine calling up to three functions, with no
+/// line longer than `max_length` characters.
+///
+/// Every function has to start where the path so far ends, so at every point
+/// this tries the functions it already has, and then, while there's room for
+/// another one, every prefix of what's left that fits on a line. Dead ends
+/// backtrack to the last choice.
+fn compress(path: &[Move], max_length: usize) -> Option<Routines> {
+    let mut routines = Routines {
+        main: Vec::new(),
+        functions: Vec::new(),
+    };
+    if compress_from(path, max_length, &mut routines) {
+        Some(routines)
+    } else {
+        None
+    }
+}
+
+fn compress_from(path: &[Move], max_length: usize, routines: &mut Routines) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+    // Every call takes a letter and a comma
+    if 2 * (routines.main.len() + 1) - 1 > max_length {
+        return false;
+    }
+    for function in 0..routines.functions.len() {
+        let moves = &routines.functions[function];
+        if path.starts_with(moves) {
+            let rest = &path[moves.len()..];
+            routines.main.push(function);
+            if compress_from(rest, max_length, routines) {
+                return true;
             }
+            routines.main.pop();
         }
     }
-    Ok(())
+    if routines.functions.len() == FUNCTIONS {
+        return false;
+    }
+    for length in (1..=path.len()).rev() {
+        if format_moves(&path[..length]).len() > max_length {
+            continue;
+        }
+        routines.functions.push(path[..length].to_vec());
+        routines.main.push(routines.functions.len() - 1);
+        if compress_from(&path[length..], max_length, routines) {
+            return true;
+        }
+        routines.main.pop();
+        routines.functions.pop();
+    }
+    false
+}
+
+fn run_driver(driver: &mut BotDriver) -> String {
+    let path = find_path(driver);
+    let routines = compress(&path, MAX_LENGTH).expect("path doesn't fit in three functions");
+    routines.to_input(false)
 }
 
 fn main() -> io::Result<()> {
@@ -688,20 +378,23 @@ fn main() -> io::Result<()> {
         .split(',')
         .map(|x| x.parse().expect("not a number"))
         .collect::<Vec<isize>>();
-    let mut camera_pipeline: Vec<Box<dyn PipelinePart>> = vec![
-        Box::new(IntCode {
-            program: program.to_vec(),
-            previous: Some(1),
-            ..Default::default()
-        }),
-        Box::new(Camera {
-            previous: Some(0),
-            run_driver: true,
-            program: program.to_vec(),
-            ..Default::default()
-        }),
-    ];
-    run_pipe(&mut camera_pipeline).expect("something went wrong");
+    let mut ascii = Ascii::new(Machine::new(program.to_vec()));
+    match ascii.run() {
+        Ok(Status::Halted) => (),
+        Ok(status) => panic!("camera fucked: {:?}", status),
+        Err(error) => panic!("program error: {}", error),
+    }
+    let mut camera = Camera {
+        run_driver: true,
+        program: program.to_vec(),
+        ..Default::default()
+    };
+    for input in ascii.take_text().chars() {
+        run_camera(&mut camera, input);
+    }
+    if let Some(dust) = exit_camera(&mut camera) {
+        println!("{}", dust);
+    }
     Ok(())
 }
 
@@ -711,141 +404,65 @@ mod tests {
 
     use test_case::test_case;
 
-    #[test]
-    fn day9_test_1() {
-        let program = vec![
-            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
-        ];
-        let mut intcode = IntCode {
-            program: program.to_vec(),
-            ..Default::default()
-        };
-        while intcode.get_state() == PartState::Run {
-            intcode.run();
-        }
-        let output = intcode
-            .output_queue
+    /// Reads `R,8,L,4` and the like.
+    fn parse_moves(string: &str) -> Vec<Move> {
+        let parts = string.split(',').collect::<Vec<_>>();
+        parts
+            .chunks(2)
+            .map(|pair| {
+                let turn = match pair[0] {
+                    "L" => Turn::Left,
+                    "R" => Turn::Right,
+                    turn => panic!("not a turn: {}", turn),
+                };
+                (turn, pair[1].parse().unwrap())
+            })
+            .collect()
+    }
+
+    /// Runs the main routine to get the path back.
+    fn expand(routines: &Routines) -> Vec<Move> {
+        routines
+            .main
             .iter()
-            .map(|x| *x)
-            .collect::<Vec<isize>>();
-        assert_eq!(program, output);
+            .flat_map(|&function| routines.functions[function].iter().copied())
+            .collect()
+    }
+
+    #[test_case("R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2", 20; "example")]
+    #[test_case("L,8,R,12,R,12,R,10,R,10,R,12,R,10,L,8,R,12,R,12,R,10,R,10,R,12,R,10,L,10,R,10,L,6,L,10,R,10,L,6,\
+        R,10,R,12,R,10,L,8,R,12,R,12,R,10,R,10,R,12,R,10,L,10,R,10,L,6", 20; "puzzle input")]
+    #[test_case("R,1,L,2,R,3,L,4,R,5,L,6", 12; "short lines")]
+    fn test_compress(path: &str, max_length: usize) {
+        let path = parse_moves(path);
+        let routines = compress(&path, max_length).unwrap();
+        assert_eq!(expand(&routines), path);
+        assert!(routines.functions.len() <= FUNCTIONS);
+        for line in routines.to_input(false).lines() {
+            assert!(line.len() <= max_length, "{:?} is too long", line);
+        }
     }
 
-    #[test]
-    fn day9_test_2() {
-        let program = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
-        let mut intcode = IntCode {
-            program: program.to_vec(),
-            ..Default::default()
-        };
-        intcode.run();
-        assert_eq!(format!("{}", intcode.pop_output()).len(), 16);
+    #[test_case("R,1,L,2,R,3,L,4", 4; "too many functions")]
+    #[test_case("R,10,L,10,R,10,L,10", 3; "move too long")]
+    #[test_case("R,1,R,1,R,1,R,1,R,1,R,1,R,1,R,1,R,1,R,1,R,1", 3; "main too long")]
+    fn test_compress_fails(path: &str, max_length: usize) {
+        assert_eq!(compress(&parse_moves(path), max_length), None);
     }
 
     #[test]
-    fn day9_test_3() {
-        let program = vec![104, 1125899906842624, 99];
-        let mut intcode = IntCode {
-            program: program.to_vec(),
-            ..Default::default()
-        };
-        intcode.run();
-        assert_eq!(intcode.pop_output(), 1125899906842624);
-    }
-
-    #[test_case( vec![1,9,10,3,2,3,11,0,99,30,40,50] => vec![3500,9,10,70,2,3,11,0,99,30,40,50] ; "day 2 example 1")]
-    #[test_case( vec![1,0,0,0,99] => vec![2,0,0,0,99] ; "day 2 example 2")]
-    #[test_case( vec![2,3,0,3,99] => vec![2,3,0,6,99] ; "day 2 example 3")]
-    #[test_case( vec![2,4,4,5,99,0] => vec![2,4,4,5,99,9801] ; "day 2 example 4")]
-    #[test_case( vec![1,1,1,4,99,5,6,0,99] => vec![30,1,1,4,2,5,6,0,99] ; "day 2 example 5")]
-    #[test_case( vec![1002,4,3,4,33] => vec![1002,4,3,4,99] ; "day 5 example 1")]
-    #[test_case( vec![1101,100,-1,4,0] => vec![1101,100,-1,4,99] ; "day 5 example 2")]
-    fn pre_input_output(program: Vec<isize>) -> Vec<isize> {
-        let mut intcode = IntCode {
-            program: program.to_vec(),
-            ..Default::default()
+    fn test_to_input() {
+        let routines = Routines {
+            main: vec![0, 1, 0, 2],
+            functions: vec![
+                parse_moves("R,8,R,8"),
+                parse_moves("R,4,R,4,R,8"),
+                parse_moves("L,6,L,2"),
+            ],
         };
-        intcode.run();
-        intcode.program.to_vec()
-    }
-
-    #[test_case( vec![3,9,8,9,10,9,4,9,99,-1,8], 8 => 1 ; "day 5 example 3a - equal to position mode")]
-    #[test_case( vec![3,9,8,9,10,9,4,9,99,-1,8], 234 => 0 ; "day 5 example 3b - equal to position mode")]
-    #[test_case( vec![3,9,7,9,10,9,4,9,99,-1,8], 7 => 1 ; "day 5 example 4a - less than position mode")]
-    #[test_case( vec![3,9,7,9,10,9,4,9,99,-1,8], 8 => 0 ; "day 5 example 4b - less than position mode")]
-    #[test_case( vec![3,9,7,9,10,9,4,9,99,-1,8], 9 => 0 ; "day 5 example 4c - less than position mode")]
-    #[test_case( vec![3,9,7,9,10,9,4,9,99,-1,8], -9 => 1 ; "day 5 example 4d - less than position mode")]
-    #[test_case( vec![3,3,1108,-1,8,3,4,3,99], 8 => 1 ; "day 5 example 5a - equal to immediate mode")]
-    #[test_case( vec![3,3,1108,-1,8,3,4,3,99], 234 => 0 ; "day 5 example 5b - equal to immediate mode")]
-    #[test_case( vec![3,3,1107,-1,8,3,4,3,99], 7 => 1 ; "day 5 example 6a - less than immediate mode")]
-    #[test_case( vec![3,3,1107,-1,8,3,4,3,99], 8 => 0 ; "day 5 example 6b - less than immediate mode")]
-    #[test_case( vec![3,3,1107,-1,8,3,4,3,99], 9 => 0 ; "day 5 example 6c - less than immediate mode")]
-    #[test_case( vec![3,3,1107,-1,8,3,4,3,99], -9 => 1 ; "day 5 example 6d - less than immediate mode")]
-    #[test_case( vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], 0 => 0 ; "day 5 example 7a - jump position mode")]
-    #[test_case( vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], 1 => 1 ; "day 5 example 7b - jump position mode")]
-    #[test_case( vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], -99 => 1 ; "day 5 example 7c - jump position mode")]
-    #[test_case( vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1], 0 => 0 ; "day 5 example 8a - jump immediate mode")]
-    #[test_case( vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1], 1 => 1 ; "day 5 example 8b - jump immediate mode")]
-    #[test_case( vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1], -99 => 1 ; "day 5 example 8c - jump immediate mode")]
-    #[test_case( vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104
-    ,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99], -99 => 999 ; "day 5 example 9a - long example")]
-    #[test_case( vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104
-    ,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99], 8 => 1000 ; "day 5 example 9b - long example")]
-    #[test_case( vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104
-    ,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99], 1337 => 1001 ; "day 5 example 9c - long example")]
-
-    fn simple_input_output(program: Vec<isize>, input: isize) -> isize {
-        let mut pipeline: Vec<Box<dyn PipelinePart>> = vec![Box::new(IntCode {
-            program: program.to_vec(),
-            input_queue: VecDeque::from(vec![input]),
-            ..Default::default()
-        })];
-        run_pipe(&mut pipeline).expect("something went wrong");
-        pipeline[0].pop_output()
-    }
-
-    #[test_case( vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0], vec![4,3,2,1,0], 0 => 43210 ; "day 7 example 1")]
-    #[test_case( vec![3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0], vec![0,1,2,3,4],
-    0 => 54321 ; "day 7 example 2")]
-    #[test_case( vec![3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0],
-    vec![1,0,4,3,2], 0 => 65210 ; "day 7 example 3")]
-    fn amp_chain(program: Vec<isize>, settings: Vec<isize>, input: isize) -> isize {
-        let mut pipeline: Vec<Box<dyn PipelinePart>> = Vec::new();
-        for (i, s) in settings.iter().enumerate() {
-            pipeline.push(Box::new(IntCode {
-                program: program.to_vec(),
-                input_queue: VecDeque::from(vec![*s]),
-                previous: match i {
-                    0 => None,
-                    _ => Some(i - 1),
-                },
-                ..Default::default()
-            }));
-        }
-        pipeline[0].push_input(input);
-        run_pipe(&mut pipeline).expect("something went wrong");
-        pipeline[settings.len() - 1].pop_output()
-    }
-
-    #[test_case( vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5],
-    vec![9,8,7,6,5], 0 => 139629729 ; "day 7 example 4")]
-    #[test_case( vec![3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54
-    ,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10], vec![9,7,8,5,6], 0 => 18216 ; "day 7 example 5")]
-    fn amp_chain_feedback(program: Vec<isize>, settings: Vec<isize>, input: isize) -> isize {
-        let mut pipeline: Vec<Box<dyn PipelinePart>> = Vec::new();
-        for (i, s) in settings.iter().enumerate() {
-            pipeline.push(Box::new(IntCode {
-                program: program.to_vec(),
-                input_queue: VecDeque::from(vec![*s]),
-                previous: match i {
-                    0 => Some(settings.len() - 1),
-                    _ => Some(i - 1),
-                },
-                ..Default::default()
-            }));
-        }
-        pipeline[0].push_input(input);
-        run_pipe(&mut pipeline).expect("something went wrong");
-        pipeline[settings.len() - 1].pop_output()
+        assert_eq!(
+            routines.to_input(false),
+            "A,B,A,C\nR,8,R,8\nR,4,R,4,R,8\nL,6,L,2\nn\n"
+        );
     }
 }
@@ -0,0 +1,276 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::ops;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Direction {
+    North = 1,
+    South = 2,
+    West = 3,
+    East = 4,
+}
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::West,
+    Direction::East,
+];
+
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Default)]
+struct Point {
+    x: isize,
+    y: isize,
+}
+
+#[allow(clippy::suspicious_arithmetic_impl)]
+impl ops::Add<Direction> for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Direction) -> Point {
+        match rhs {
+            Direction::North => Point {
+                x: self.x,
+                y: self.y - 1,
+            },
+            Direction::South => Point {
+                x: self.x,
+                y: self.y + 1,
+            },
+            Direction::West => Point {
+                x: self.x - 1,
+                y: self.y,
+            },
+            Direction::East => Point {
+                x: self.x + 1,
+                y: self.y,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+enum TileType {
+    Wall,
+    Open,
+    Door(char),
+    Key(char),
+}
+
+type TileMap = HashMap<Point, TileType>;
+
+const ROBOTS: usize = 4;
+
+/// Bit for a key or the door it opens, `a` is the lowest.
+fn key_bit(c: char) -> u32 {
+    1 << (c.to_ascii_lowercase() as u32 - 'a' as u32)
+}
+
+fn string_to_data(string: &str) -> (TileMap, Vec<Point>) {
+    let mut map: TileMap = HashMap::new();
+    let mut starts = Vec::new();
+    for (y, line) in string.lines().enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            let point = Point {
+                x: x as isize,
+                y: y as isize,
+            };
+            let tile = match c {
+                '#' => TileType::Wall,
+                '.' => TileType::Open,
+                '@' => {
+                    starts.push(point);
+                    TileType::Open
+                }
+                'a'..='z' => TileType::Key(c),
+                'A'..='Z' => TileType::Door(c),
+                _ => panic!("unexpected character: {:?}", c),
+            };
+            map.insert(point, tile);
+        }
+    }
+    (map, starts)
+}
+
+/// Walls off the middle of the vault around a single entrance, leaving a
+/// robot in each corner.
+fn split_vault(map: &mut TileMap, start: Point) -> Vec<Point> {
+    let mut starts = Vec::new();
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            let point = Point {
+                x: start.x + dx,
+                y: start.y + dy,
+            };
+            if dx != 0 && dy != 0 {
+                starts.push(point);
+                map.insert(point, TileType::Open);
+            } else {
+                map.insert(point, TileType::Wall);
+            }
+        }
+    }
+    starts
+}
+
+/// The way from one place to a key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Edge {
+    key: usize,
+    steps: usize,
+    /// Doors on the way, as key bits
+    doors: u32,
+}
+
+/// Every key reachable from `from` without passing another key, with the
+/// shortest way there. Walking over a key picks it up, so the keys behind it
+/// are reached through it.
+fn find_keys(map: &TileMap, from: Point) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    let mut seen = HashSet::new();
+    seen.insert(from);
+    let mut expand_from = vec![(from, 0)];
+    let mut steps = 0;
+    while !expand_from.is_empty() {
+        steps += 1;
+        let mut new_expand_from = Vec::new();
+        for (point, doors) in expand_from {
+            for &direction in DIRECTIONS.iter() {
+                let next = point + direction;
+                if !seen.insert(next) {
+                    continue;
+                }
+                match map.get(&next) {
+                    None | Some(TileType::Wall) => (),
+                    Some(TileType::Open) => new_expand_from.push((next, doors)),
+                    Some(TileType::Door(c)) => new_expand_from.push((next, doors | key_bit(*c))),
+                    Some(TileType::Key(c)) => {
+                        edges.push(Edge {
+                            key: (*c as u8 - b'a') as usize,
+                            steps,
+                            doors,
+                        });
+                    }
+                }
+            }
+        }
+        expand_from = new_expand_from;
+    }
+    edges
+}
+
+/// Keys are nodes 0 to 25, the robots start at the nodes after that.
+fn build_graph(map: &TileMap, starts: &[Point]) -> Vec<Vec<Edge>> {
+    let mut graph = vec![Vec::new(); 26 + starts.len()];
+    for (point, tile) in map {
+        if let TileType::Key(c) = tile {
+            graph[(*c as u8 - b'a') as usize] = find_keys(map, *point);
+        }
+    }
+    for (robot, start) in starts.iter().enumerate() {
+        graph[26 + robot] = find_keys(map, *start);
+    }
+    graph
+}
+
+/// Dijkstra over where every robot is and which keys they have.
+fn find_min_steps(graph: &[Vec<Edge>]) -> usize {
+    let all_keys = graph
+        .iter()
+        .flatten()
+        .fold(0, |keys, edge| keys | 1 << edge.key);
+    let start: [usize; ROBOTS] = std::array::from_fn(|robot| 26 + robot);
+    let mut best = HashMap::new();
+    best.insert((start, 0), 0);
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse((0, start, 0)));
+    while let Some(Reverse((steps, positions, keys))) = queue.pop() {
+        if keys == all_keys {
+            return steps;
+        }
+        if best.get(&(positions, keys)) < Some(&steps) {
+            continue;
+        }
+        for (robot, &position) in positions.iter().enumerate() {
+            for edge in &graph[position] {
+                // Going back to a key we have is how to get past it
+                if edge.doors & !keys != 0 {
+                    continue;
+                }
+                let mut positions = positions;
+                positions[robot] = edge.key;
+                let (keys, steps) = (keys | 1 << edge.key, steps + edge.steps);
+                let known = best.entry((positions, keys)).or_insert(usize::MAX);
+                if steps < *known {
+                    *known = steps;
+                    queue.push(Reverse((steps, positions, keys)));
+                }
+            }
+        }
+    }
+    panic!("can't get all keys");
+}
+
+fn actual_main(input: &str) -> usize {
+    let (mut map, mut starts) = string_to_data(input);
+    if starts.len() == 1 {
+        starts = split_vault(&mut map, starts[0]);
+    }
+    assert_eq!(
+        starts.len(),
+        ROBOTS,
+        "the vault has to have one or four entrances"
+    );
+    let graph = build_graph(&map, &starts);
+    find_min_steps(&graph)
+}
+
+fn main() -> io::Result<()> {
+    let input = fs::read_to_string("input.txt")?;
+    let result = actual_main(&input);
+    println!("{:?}", result);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("#######
+#a.#Cd#
+##...##
+##.@.##
+##...##
+#cB#Ab#
+#######" => 8; "day 18 part 2 example 1")]
+    #[test_case("###############
+#d.ABC.#.....a#
+######@#@######
+###############
+######@#@######
+#b.....#.....c#
+###############" => 24; "day 18 part 2 example 2")]
+    #[test_case("#############
+#DcBa.#.GhKl#
+#.###@#@#I###
+#e#d#####j#k#
+###C#@#@###J#
+#fEbA.#.FgHi#
+#############" => 32; "day 18 part 2 example 3")]
+    #[test_case("#############
+#g#f.D#..h#l#
+#F###e#E###.#
+#dCba@#@BcIJ#
+#############
+#nK.L@#@G...#
+#M###N#H###.#
+#o#m..#i#jk.#
+#############" => 72; "day 18 part 2 example 4")]
+    fn test(input: &str) -> usize {
+        actual_main(input)
+    }
+}
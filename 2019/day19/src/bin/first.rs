@@ -4,12 +4,7 @@ use std::fs;
 use std::io;
 use std::ops;
 
-#[derive(Debug, PartialEq)]
-enum ParamMode {
-    Position,
-    Immediate,
-    Relative,
-}
+use intcode::{Event, FastMachine};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum PartState {
@@ -51,21 +46,6 @@ impl ops::Neg for Direction {
     }
 }
 
-#[derive(Debug)]
-enum OpCode {
-    Add = 1,
-    Multiply = 2,
-    Read = 3,
-    Write = 4,
-    JumpIfTrue = 5,
-    JumpIfFalse = 6,
-    LessThan = 7,
-    EqualTo = 8,
-    AdjustBase = 9,
-    Exit = 99,
-}
-use OpCode::*;
-
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Default)]
 struct Point {
     x: isize,
@@ -130,17 +110,14 @@ impl fmt::Debug for dyn PipelinePart {
 
 #[derive(Debug, Default, Clone)]
 struct IntCode {
-    input_queue: VecDeque<isize>,
     output_queue: VecDeque<isize>,
     state: PartState,
-    program: Vec<isize>,
-    ip: usize,
-    rel_base: isize,
+    machine: FastMachine,
 }
 
 impl PipelinePart for IntCode {
     fn get_input_queue(&self) -> VecDeque<isize> {
-        self.input_queue.clone()
+        self.machine.input().iter().map(|&x| x as isize).collect()
     }
     fn get_output_queue(&self) -> VecDeque<isize> {
         self.output_queue.clone()
@@ -153,161 +130,16 @@ impl PipelinePart for IntCode {
     }
 }
 
-fn param_mode(digit: isize) -> ParamMode {
-    match digit {
-        0 => ParamMode::Position,
-        1 => ParamMode::Immediate,
-        2 => ParamMode::Relative,
-        _ => panic!("invalid parameter mode: {}", digit),
-    }
-}
-
-fn param_to_position(
-    program: &mut Vec<isize>,
-    param_pos: usize,
-    mode: ParamMode,
-    rel_base: isize,
-) -> usize {
-    let result = match mode {
-        ParamMode::Position => program[param_pos] as usize,
-        ParamMode::Relative => (program[param_pos] + rel_base) as usize,
-        ParamMode::Immediate => panic!("Immediate can not be a position"),
-    };
-    if result >= program.len() {
-        program.resize(result + 1, 0);
-    }
-    result
-}
-
-fn param_to_value(
-    program: &mut Vec<isize>,
-    param_pos: usize,
-    mode: ParamMode,
-    rel_base: isize,
-) -> isize {
-    match mode {
-        ParamMode::Position | ParamMode::Relative => {
-            let abs_pos = param_to_position(program, param_pos, mode, rel_base);
-            program[abs_pos]
-        }
-        ParamMode::Immediate => program[param_pos],
-    }
-}
-
-fn in_in_out(opcode: OpCode, inval1: isize, inval2: isize) -> isize {
-    match opcode {
-        Add => inval1 + inval2,
-        Multiply => inval1 * inval2,
-        LessThan => {
-            if inval1 < inval2 {
-                1
-            } else {
-                0
-            }
-        }
-        EqualTo => {
-            if inval1 == inval2 {
-                1
-            } else {
-                0
-            }
-        }
-        _ => panic!("program error"),
-    }
-}
-
-fn in_in_jump(opcode: OpCode, inval1: isize, inval2: isize, ip: &mut usize) {
-    match opcode {
-        JumpIfTrue => {
-            // jump if true
-            if inval1 != 0 {
-                *ip = inval2 as usize;
-            } else {
-                *ip += 3;
-            }
-        }
-        JumpIfFalse => {
-            // jump if false
-            if inval1 == 0 {
-                *ip = inval2 as usize;
-            } else {
-                *ip += 3;
-            }
-        }
-        _ => panic!("program error"),
-    }
-}
-
 fn run_program(intcode: &mut IntCode) -> PartState {
-    let program = &mut intcode.program;
-    let ip = &mut intcode.ip;
-    let rel_base = &mut intcode.rel_base;
-    loop {
-        let mut digits = Vec::new();
-        let mut remain = program[*ip];
-        for i in (2..5).rev() {
-            digits.push(remain / 10_isize.pow(i));
-            remain %= 10_isize.pow(i);
-        }
-        let opcode = match remain {
-            1 => Add,
-            2 => Multiply,
-            3 => Read,
-            4 => Write,
-            5 => JumpIfTrue,
-            6 => JumpIfFalse,
-            7 => LessThan,
-            8 => EqualTo,
-            9 => AdjustBase,
-            99 => Exit,
-            _ => panic!("invalid opcode: {}", remain),
-        };
-        let param_3_mode = param_mode(digits[0]);
-        let param_2_mode = param_mode(digits[1]);
-        let param_1_mode = param_mode(digits[2]);
-        match opcode {
-            // in in out
-            Add | Multiply | LessThan | EqualTo => {
-                let inval1 = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-                let inval2 = param_to_value(program, *ip + 2, param_2_mode, *rel_base);
-                let outpos = param_to_position(program, *ip + 3, param_3_mode, *rel_base);
-                program[outpos] = in_in_out(opcode, inval1, inval2);
-                *ip += 4;
-            }
-            // out
-            Read => {
-                // read from input
-                let outpos = param_to_position(program, *ip + 1, param_1_mode, *rel_base);
-                if intcode.input_queue.is_empty() {
-                    return PartState::NeedInput;
-                }
-                program[outpos] = intcode.input_queue.pop_front().unwrap();
-                *ip += 2;
-            }
-            // in
-            Write | AdjustBase => {
-                let inval = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-                match opcode {
-                    Write => {
-                        intcode.output_queue.push_back(inval);
-                        *ip += 2;
-                        return PartState::Run;
-                    }
-                    AdjustBase => *rel_base += inval,
-                    _ => panic!("program error"),
-                }
-                *ip += 2;
-            }
-            // in in
-            JumpIfTrue | JumpIfFalse => {
-                let inval1 = param_to_value(program, *ip + 1, param_1_mode, *rel_base);
-                let inval2 = param_to_value(program, *ip + 2, param_2_mode, *rel_base);
-                in_in_jump(opcode, inval1, inval2, ip);
-            }
-            Exit => break,
+    match intcode.machine.run_until_output() {
+        Ok(Event::Output(value)) => {
+            intcode.output_queue.push_back(value);
+            PartState::Run
         }
+        Ok(Event::NeedInput) => PartState::NeedInput,
+        Ok(Event::Halted) => PartState::Exit,
+        Err(error) => panic!("program error: {}", error),
     }
-    PartState::Exit
 }
 
 fn main() -> io::Result<()> {
@@ -315,17 +147,17 @@ fn main() -> io::Result<()> {
         .trim()
         .split(',')
         .map(|x| x.parse().expect("not a number"))
-        .collect::<Vec<isize>>();
+        .collect::<Vec<i64>>();
     let clean_intcode = IntCode {
-        program,
+        machine: FastMachine::new(program),
         ..Default::default()
     };
     let mut count = 0;
     for x in 0..50 {
         for y in 0..50 {
             let mut intcode = clean_intcode.clone();
-            intcode.input_queue.push_back(x);
-            intcode.input_queue.push_back(y);
+            intcode.machine.push_input(x);
+            intcode.machine.push_input(y);
             intcode.run();
             match intcode.output_queue.pop_front() {
                 Some(0) => {
@@ -357,7 +189,7 @@ mod tests {
             109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
         ];
         let mut intcode = IntCode {
-            program: program.to_vec(),
+            machine: FastMachine::new(program.to_vec()),
             ..Default::default()
         };
         while intcode.get_state() == PartState::Run {
@@ -366,8 +198,8 @@ mod tests {
         let output = intcode
             .output_queue
             .iter()
-            .map(|x| *x)
-            .collect::<Vec<isize>>();
+            .map(|x| *x as i64)
+            .collect::<Vec<i64>>();
         assert_eq!(program, output);
     }
 
@@ -378,12 +210,12 @@ mod tests {
     #[test_case( vec![1,1,1,4,99,5,6,0,99] => vec![30,1,1,4,2,5,6,0,99] ; "day 2 example 5")]
     #[test_case( vec![1002,4,3,4,33] => vec![1002,4,3,4,99] ; "day 5 example 1")]
     #[test_case( vec![1101,100,-1,4,0] => vec![1101,100,-1,4,99] ; "day 5 example 2")]
-    fn pre_input_output(program: Vec<isize>) -> Vec<isize> {
+    fn pre_input_output(program: Vec<i64>) -> Vec<i64> {
         let mut intcode = IntCode {
-            program: program.to_vec(),
+            machine: FastMachine::new(program.to_vec()),
             ..Default::default()
         };
         intcode.run();
-        intcode.program.to_vec()
+        intcode.machine.memory().to_vec()
     }
 }
@@ -0,0 +1,113 @@
+use std::fs;
+use std::io;
+
+use intcode::{Event, FastMachine};
+
+/// The drone system, counting how many times it had to be asked.
+struct Beam {
+    machine: FastMachine,
+    runs: usize,
+}
+
+impl Beam {
+    fn new(program: Vec<i64>) -> Beam {
+        Beam {
+            machine: FastMachine::new(program),
+            runs: 0,
+        }
+    }
+
+    fn pulls(&mut self, x: i64, y: i64) -> bool {
+        self.runs += 1;
+        let mut machine = self.machine.clone();
+        machine.extend_input([x, y]);
+        match machine.run_until_output() {
+            Ok(Event::Output(0)) => false,
+            Ok(Event::Output(1)) => true,
+            other => panic!("unexpected result from program: {:?}", other),
+        }
+    }
+}
+
+/// How far right of the y axis, as a multiple of y, the beam is looked for.
+const MAX_SLOPE: i64 = 10;
+
+/// Top left corner of the closest place a `size` by `size` ship fits in the
+/// beam.
+///
+/// Walks down the lower left edge of the beam, one row at a time, starting at
+/// the first row the bottom of the ship could be on. The edge only ever moves
+/// right, so every point is asked about at most once, and for every row the
+/// only other question is whether the top right corner of the ship is still
+/// in the beam. Close to the emitter the beam can miss rows entirely, so a
+/// row with nothing by `MAX_SLOPE` times y in is skipped, leaving the edge
+/// where it was.
+fn fit_ship(size: i64, mut pulls: impl FnMut(i64, i64) -> bool) -> (i64, i64) {
+    let mut x = 0;
+    let mut y = size - 1;
+    loop {
+        let mut scan = x;
+        while scan <= MAX_SLOPE * y && !pulls(scan, y) {
+            scan += 1;
+        }
+        if scan <= MAX_SLOPE * y {
+            x = scan;
+            let top = y - (size - 1);
+            if pulls(x + size - 1, top) {
+                return (x, top);
+            }
+        }
+        y += 1;
+    }
+}
+
+fn main() -> io::Result<()> {
+    let program = fs::read_to_string("input.txt")?
+        .trim()
+        .split(',')
+        .map(|x| x.parse().expect("not a number"))
+        .collect::<Vec<i64>>();
+    let mut beam = Beam::new(program);
+    let (x, y) = fit_ship(100, |x, y| beam.pulls(x, y));
+    println!("{}", x * 10000 + y);
+    println!("{} intcode runs", beam.runs);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    /// The closest place by trying every one.
+    fn brute_force(size: i64, pulls: impl Fn(i64, i64) -> bool) -> (i64, i64) {
+        for top in 0.. {
+            for left in 0..=top * 10 {
+                let fits = (top..top + size).all(|y| (left..left + size).all(|x| pulls(x, y)));
+                if fits {
+                    return (left, top);
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    #[test_case(10, 7, 13, 0; "wedge")]
+    #[test_case(10, 2, 9, 0; "wide")]
+    #[test_case(5, 10, 12, 0; "steep")]
+    #[test_case(2, 7, 13, 4; "empty rows")]
+    fn test_fit_ship(size: i64, low: i64, high: i64, empty: i64) {
+        // In the beam between slopes low/10 and high/10, from row `empty` down
+        let pulls = |x: i64, y: i64| y >= empty && x * low <= y * 10 && y * 10 <= x * high;
+        let mut runs = 0;
+        let (x, y) = fit_ship(size, |x, y| {
+            runs += 1;
+            pulls(x, y)
+        });
+        assert_eq!((x, y), brute_force(size, pulls));
+        // Every empty row is looked at all the way
+        let skipped = (MAX_SLOPE * empty + 1) * empty;
+        assert!(runs <= (2 * (x + y + size) + skipped) as usize);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+
+use aoc_common::automaton::{Neighborhood, VonNeumann};
+use aoc_common::parse::{char_grid, parse_all};
+
+type Point = [isize; 2];
+
+#[derive(Debug, Default)]
+struct Maze {
+    open: HashSet<Point>,
+    /// Where stepping onto a portal tile takes you, and how many levels
+    /// down that goes
+    portals: HashMap<Point, (Point, isize)>,
+    start: Point,
+    end: Point,
+}
+
+fn is_outer(open: &HashSet<Point>, [x, y]: Point) -> bool {
+    let xs = open.iter().map(|point| point[0]);
+    let ys = open.iter().map(|point| point[1]);
+    let (min_x, max_x) = (xs.clone().min(), xs.max());
+    let (min_y, max_y) = (ys.clone().min(), ys.max());
+    Some(x) == min_x || Some(x) == max_x || Some(y) == min_y || Some(y) == max_y
+}
+
+fn string_to_data(string: &str) -> Maze {
+    // Editors like to eat the spaces at the end of lines, but a grid has to
+    // be rectangular
+    let width = string.lines().map(str::len).max().unwrap_or(0);
+    let padded = string
+        .lines()
+        .map(|line| format!("{:1$}", line, width))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let tiles = parse_all(char_grid, &padded).expect("maze isn't a grid");
+    let tile = |[x, y]: Point| {
+        let row = tiles.get(usize::try_from(y).ok()?)?;
+        row.get(usize::try_from(x).ok()?).copied()
+    };
+    let mut maze = Maze::default();
+    let mut labels: HashMap<String, Vec<Point>> = HashMap::new();
+    for (y, row) in tiles.iter().enumerate() {
+        for (x, &c) in row.iter().enumerate() {
+            if c != '.' {
+                continue;
+            }
+            let point = [x as isize, y as isize];
+            maze.open.insert(point);
+            for near in VonNeumann.neighbors(point) {
+                let far = [2 * near[0] - point[0], 2 * near[1] - point[1]];
+                if let (Some(a), Some(b)) = (tile(near), tile(far)) {
+                    if a.is_ascii_uppercase() && b.is_ascii_uppercase() {
+                        // Labels read left to right or top to bottom, so
+                        // above or left of the tile they start furthest away
+                        let label = if far < point {
+                            format!("{}{}", b, a)
+                        } else {
+                            format!("{}{}", a, b)
+                        };
+                        labels.entry(label).or_default().push(point);
+                    }
+                }
+            }
+        }
+    }
+    for (label, points) in labels {
+        match (label.as_str(), points.as_slice()) {
+            ("AA", [start]) => maze.start = *start,
+            ("ZZ", [end]) => maze.end = *end,
+            (_, [a, b]) => {
+                // Outer portals sit on the edge of the maze and go up a level
+                let depth = if is_outer(&maze.open, *a) { -1 } else { 1 };
+                maze.portals.insert(*a, (*b, depth));
+                maze.portals.insert(*b, (*a, -depth));
+            }
+            _ => panic!("portal {} doesn't have two ends: {:?}", label, points),
+        }
+    }
+    maze
+}
+
+/// Breadth first over places and levels. Going deeper than there are portals
+/// can't lead anywhere new, since every way back up has to pass each of them.
+fn find_min_steps(maze: &Maze) -> usize {
+    let max_level = maze.portals.len() as isize;
+    let mut seen = HashSet::new();
+    seen.insert((maze.start, 0));
+    let mut queue = VecDeque::new();
+    queue.push_back((maze.start, 0, 0));
+    while let Some((point, level, steps)) = queue.pop_front() {
+        if point == maze.end && level == 0 {
+            return steps;
+        }
+        let walk = VonNeumann.neighbors(point).map(|next| (next, level));
+        let warp = maze
+            .portals
+            .get(&point)
+            .map(|&(to, depth)| (to, level + depth))
+            .filter(|&(_, level)| (0..=max_level).contains(&level));
+        for (next, level) in walk.chain(warp) {
+            if maze.open.contains(&next) && seen.insert((next, level)) {
+                queue.push_back((next, level, steps + 1));
+            }
+        }
+    }
+    panic!("no way from AA to ZZ");
+}
+
+fn main() -> io::Result<()> {
+    let input = fs::read_to_string("input.txt")?;
+    let maze = string_to_data(&input);
+    println!("{:?}", find_min_steps(&maze));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("         A
+         A
+  #######.#########
+  #######.........#
+  #######.#######.#
+  #######.#######.#
+  #######.#######.#
+  #####  B    ###.#
+BC...##  C    ###.#
+  ##.##       ###.#
+  ##...DE  F  ###.#
+  #####    G  ###.#
+  #########.#####.#
+DE..#######...###.#
+  #.#########.###.#
+FG..#########.....#
+  ###########.#####
+             Z
+             Z       " => 26; "example 1")]
+    #[test_case("             Z L X W       C
+             Z P Q B       K
+  ###########.#.#.#.#######.###############
+  #...#.......#.#.......#.#.......#.#.#...#
+  ###.#.#.#.#.#.#.#.###.#.#.#######.#.#.###
+  #.#...#.#.#...#.#.#...#...#...#.#.......#
+  #.###.#######.###.###.#.###.###.#.#######
+  #...#.......#.#...#...#.............#...#
+  #.#########.#######.#.#######.#######.###
+  #...#.#    F       R I       Z    #.#.#.#
+  #.###.#    D       E C       H    #.#.#.#
+  #.#...#                           #...#.#
+  #.###.#                           #.###.#
+  #.#....OA                       WB..#.#..ZH
+  #.###.#                           #.#.#.#
+CJ......#                           #.....#
+  #######                           #######
+  #.#....CK                         #......IC
+  #.###.#                           #.###.#
+  #.....#                           #...#.#
+  ###.###                           #.#.#.#
+XF....#.#                         RF..#.#.#
+  #####.#                           #######
+  #......CJ                       NM..#...#
+  ###.#.#                           #.###.#
+RE....#.#                           #......RF
+  ###.###        X   X       L      #.#.#.#
+  #.....#        F   Q       P      #.#.#.#
+  ###.###########.###.#######.#########.###
+  #.....#...#.....#.......#...#.....#.#...#
+  #####.#.###.#######.#######.###.###.#.#.#
+  #.......#.......#.#.#.#.#...#...#...#.#.#
+  #####.###.#####.#.#.#.#.###.###.#.###.###
+  #.......#.....#.#...#...............#...#
+  #############.#.#.###.###################
+               A O F   N
+               A A D   M                     " => 396; "example 3")]
+    fn test(input: &str) -> usize {
+        find_min_steps(&string_to_data(input))
+    }
+}
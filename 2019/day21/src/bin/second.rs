@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+
+use intcode::{Ascii, Machine, Status};
+
+/// Jump when there's a hole in the next three tiles and ground to land on,
+/// but only if from there it can either step on or jump again right away.
+/// Otherwise landing on D can leave it stuck in front of a hole.
+const SCRIPT: &[&str] = &[
+    "NOT A J", "NOT B T", "OR T J", "NOT C T", "OR T J", "AND D J", "NOT E T", "NOT T T", "OR H T",
+    "AND T J",
+];
+
+/// Feeds `script` to the droid and lets it go. Returns the hull damage, or the
+/// last thing the droid saw before falling in.
+fn run_droid(program: &[isize], script: &[&str], mode: &str) -> Result<isize, String> {
+    let mut ascii = Ascii::new(Machine::new(program.to_vec()));
+    match ascii.run() {
+        Ok(Status::NeedInput) => (),
+        other => panic!("droid didn't ask for instructions: {:?}", other),
+    }
+    ascii.take_text();
+    for line in script {
+        ascii.send_line(line);
+    }
+    ascii.send_line(mode);
+    match ascii.run() {
+        Ok(Status::Halted) => (),
+        other => panic!("droid didn't finish: {:?}", other),
+    }
+    match ascii.values() {
+        [damage] => Ok(*damage),
+        _ => Err(ascii.take_text()),
+    }
+}
+
+fn main() -> io::Result<()> {
+    let program = fs::read_to_string("input.txt")?
+        .trim()
+        .split(',')
+        .map(|x| x.parse().expect("not a number"))
+        .collect::<Vec<isize>>();
+    match run_droid(&program, SCRIPT, "RUN") {
+        Ok(damage) => println!("{}", damage),
+        Err(frames) => println!("{}", frames),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    /// Runs `script` over `hull` the way the droid would, seeing `sensors`
+    /// tiles ahead. Returns whether it makes it to the end.
+    fn survives(script: &[&str], hull: &str, sensors: usize) -> bool {
+        let hull = hull.as_bytes();
+        let ground = |at: usize| hull.get(at).is_none_or(|&tile| tile == b'#');
+        let mut at = 0;
+        while at < hull.len() {
+            if !ground(at) {
+                return false;
+            }
+            let (mut t, mut j) = (false, false);
+            for line in script {
+                let words = line.split(' ').collect::<Vec<_>>();
+                let x = match words[1] {
+                    "T" => t,
+                    "J" => j,
+                    sensor => {
+                        let distance = (sensor.as_bytes()[0] - b'A') as usize + 1;
+                        assert!(distance <= sensors, "can't see {}", sensor);
+                        ground(at + distance)
+                    }
+                };
+                let y = if words[2] == "T" { &mut t } else { &mut j };
+                match words[0] {
+                    "AND" => *y &= x,
+                    "OR" => *y |= x,
+                    "NOT" => *y = !x,
+                    instruction => panic!("unknown instruction {}", instruction),
+                }
+            }
+            at += if j { 4 } else { 1 };
+        }
+        true
+    }
+
+    #[test_case("#####.####" => true; "one hole")]
+    #[test_case("#####...#########" => true; "wide hole")]
+    #[test_case("#####.#.##.#.####" => true; "jump late")]
+    #[test_case("#####.##.##..####" => true; "wait for it")]
+    #[test_case("#####...########....#" => false; "too wide")]
+    fn test(hull: &str) -> bool {
+        survives(SCRIPT, hull, 9)
+    }
+
+    #[test]
+    fn test_walking_fails() {
+        let walk = &SCRIPT[..6];
+        assert!(!survives(walk, "#####.#.##.#.####", 4));
+    }
+}
@@ -1,10 +1,8 @@
 use std::io;
 use std::fs;
+use aoc_common::parse::{signed, unsigned};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::digit1;
-use nom::combinator::map;
-use nom::sequence::preceded;
 use nom::IResult;
 
 type Deck = Vec<u32>;
@@ -51,14 +49,6 @@ fn make_deck(size: usize) -> Deck {
     deck
 }
 
-fn parse_num(input: &str) -> IResult<&str, isize> {
-  alt((
-    map(digit1, |digit_str: &str| digit_str.parse::<isize>().unwrap()),
-    map(preceded(tag("-"), digit1), |digit_str: &str| 
-      -1 * digit_str.parse::<isize>().unwrap()),
-  ))(input)
-}
-
 fn parse_new(input: &str) -> IResult<&str, Instruction> {
     let (input, _) = tag("deal into new stack")(input)?;
     Ok((input, Instruction::DealIntoNew))
@@ -66,14 +56,14 @@ fn parse_new(input: &str) -> IResult<&str, Instruction> {
 
 fn parse_cut(input: &str) -> IResult<&str, Instruction> {
     let (input, _) = tag("cut ")(input)?;
-    let (input, index) = parse_num(input)?;
+    let (input, index) = signed(input)?;
     Ok((input, Instruction::Cut(index)))
 }
 
 fn parse_deal(input: &str) -> IResult<&str, Instruction> {
     let (input, _) = tag("deal with increment ")(input)?;
-    let (input, incr) = parse_num(input)?;
-    Ok((input, Instruction::DealWithIncr(incr as usize)))
+    let (input, incr) = unsigned(input)?;
+    Ok((input, Instruction::DealWithIncr(incr)))
 }
 
 
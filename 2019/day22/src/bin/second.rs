@@ -1,12 +1,9 @@
 use std::io;
 use std::fs;
+use aoc_common::parse::{signed, unsigned};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::digit1;
-use nom::combinator::map;
-use nom::sequence::preceded;
 use nom::IResult;
-//use std::time::Instant;
 
 #[derive(Debug)]
 enum Instruction {
@@ -15,10 +12,12 @@ enum Instruction {
     DealWithIncr(u64),
 }
 
+#[cfg(test)]
 fn deal_into_new(pos: u64, max: u64) -> u64 {
     max - pos
 }
 
+#[cfg(test)]
 fn cut(pos: u64, size: u64, index: i64) -> u64 {
     let index: u64 = if index < 0 {
         (index + (size as i64)) as u64
@@ -32,16 +31,100 @@ fn cut(pos: u64, size: u64, index: i64) -> u64 {
     }
 }
 
+#[cfg(test)]
 fn deal_with_incr(pos: u64, size: u64, increment: u64) -> u64 {
     (pos * increment) % size
 }
 
-fn parse_num(input: &str) -> IResult<&str, i64> {
-  alt((
-    map(digit1, |digit_str: &str| digit_str.parse::<i64>().unwrap()),
-    map(preceded(tag("-"), digit1), |digit_str: &str| 
-      -1 * digit_str.parse::<i64>().unwrap()),
-  ))(input)
+/// Where a shuffle moves the card at `pos`: `(a * pos + b) % size`. Every
+/// instruction is one of these, and so is doing any of them one after the
+/// other, any number of times.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Affine {
+    a: u128,
+    b: u128,
+    size: u128,
+}
+
+impl Affine {
+    fn identity(size: u64) -> Affine {
+        Affine {
+            a: 1,
+            b: 0,
+            size: size as u128,
+        }
+    }
+
+    fn from_instruction(instr: &Instruction, size: u64) -> Affine {
+        let size = size as u128;
+        let (a, b) = match *instr {
+            Instruction::DealIntoNew => (size - 1, size - 1),
+            Instruction::Cut(index) => (1, (-(index as i128)).rem_euclid(size as i128) as u128),
+            Instruction::DealWithIncr(incr) => (incr as u128 % size, 0),
+        };
+        Affine { a, b, size }
+    }
+
+    fn from_instructions(instructions: &[Instruction], size: u64) -> Affine {
+        instructions
+            .iter()
+            .fold(Affine::identity(size), |shuffle, instr| {
+                shuffle.then(Affine::from_instruction(instr, size))
+            })
+    }
+
+    /// `self` followed by `other`.
+    fn then(self, other: Affine) -> Affine {
+        Affine {
+            a: other.a * self.a % self.size,
+            b: (other.a * self.b + other.b) % self.size,
+            size: self.size,
+        }
+    }
+
+    /// `self` done `times` times, by squaring.
+    fn pow(self, mut times: u64) -> Affine {
+        let mut result = Affine::identity(self.size as u64);
+        let mut square = self;
+        while times > 0 {
+            if times & 1 == 1 {
+                result = result.then(square);
+            }
+            square = square.then(square);
+            times >>= 1;
+        }
+        result
+    }
+
+    /// Where a card has to start to end up at `pos`, which only makes sense
+    /// if no two cards end up in the same place.
+    fn inverse(self) -> Option<Affine> {
+        let a = mod_inverse(self.a, self.size)?;
+        Some(Affine {
+            a,
+            b: (self.size - self.b) * a % self.size,
+            size: self.size,
+        })
+    }
+
+    fn apply(self, pos: u64) -> u64 {
+        ((self.a * pos as u128 + self.b) % self.size) as u64
+    }
+}
+
+/// The `x` for which `a * x % n == 1`, by the extended Euclidean algorithm.
+fn mod_inverse(a: u128, n: u128) -> Option<u128> {
+    let (mut old_r, mut r) = (a as i128, n as i128);
+    let (mut old_s, mut s) = (1_i128, 0_i128);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+    if old_r != 1 {
+        return None;
+    }
+    Some(old_s.rem_euclid(n as i128) as u128)
 }
 
 fn parse_new(input: &str) -> IResult<&str, Instruction> {
@@ -51,14 +134,14 @@ fn parse_new(input: &str) -> IResult<&str, Instruction> {
 
 fn parse_cut(input: &str) -> IResult<&str, Instruction> {
     let (input, _) = tag("cut ")(input)?;
-    let (input, index) = parse_num(input)?;
+    let (input, index) = signed(input)?;
     Ok((input, Instruction::Cut(index)))
 }
 
 fn parse_deal(input: &str) -> IResult<&str, Instruction> {
     let (input, _) = tag("deal with increment ")(input)?;
-    let (input, incr) = parse_num(input)?;
-    Ok((input, Instruction::DealWithIncr(incr as u64)))
+    let (input, incr) = unsigned(input)?;
+    Ok((input, Instruction::DealWithIncr(incr)))
 }
 
 fn parse_instructions(instructions: &str) -> Vec<Instruction> {
@@ -70,6 +153,7 @@ fn parse_instructions(instructions: &str) -> Vec<Instruction> {
     result
 }
 
+#[cfg(test)]
 fn apply_instructions(mut pos: u64, size: u64, instructions: &Vec<Instruction>) -> u64 {
     let max = size - 1;
     for instr in instructions {
@@ -84,21 +168,14 @@ fn apply_instructions(mut pos: u64, size: u64, instructions: &Vec<Instruction>)
 
 fn main() -> io::Result<()> {
     let instructions = fs::read_to_string("input.txt")?;
-    let size = 119315717514047;
+    let size = 119_315_717_514_047;
     let instructions = parse_instructions(&instructions);
-    let mut pos = apply_instructions(2020, size, &instructions);
-    println!("pos: {:?}", pos);
-    let mut reps: u64 = 1;
-//    let start = Instant::now();
-    while pos != 2020 && reps < 101_741_582_076_661 {
-        pos = apply_instructions(pos, size, &instructions);
-        reps += 1;
-//        if reps % 1_000_000 == 0 {
-//            println!("{}: {:?}", reps, start.elapsed());
-//        }
-        println!("pos: {:?}", pos);
-    }
-    println!("pos is {} after {} reps", pos, reps);
+    let shuffle = Affine::from_instructions(&instructions, size).pow(101_741_582_076_661);
+    let card = shuffle
+        .inverse()
+        .expect("shuffle puts two cards in the same place")
+        .apply(2020);
+    println!("{}", card);
     Ok(())
 }
 
@@ -171,6 +248,46 @@ cut -1" => vec![9, 2, 5, 8, 1, 4, 7, 0, 3, 6]; "example 8")]
         result
     }
 
+    #[test_case(10, "deal into new stack
+cut -2
+deal with increment 7
+cut 8
+cut -4
+deal with increment 7
+cut 3
+deal with increment 9
+deal with increment 3
+cut -1"; "example 8")]
+    #[test_case(10007, &fs::read_to_string("input.txt").unwrap(); "input")]
+    fn test_affine(size: u64, instructions: &str) {
+        let instructions = parse_instructions(instructions);
+        let shuffle = Affine::from_instructions(&instructions, size);
+        for card in 0..size {
+            assert_eq!(
+                shuffle.apply(card),
+                apply_instructions(card, size, &instructions)
+            );
+        }
+    }
+
+    #[test]
+    fn test_repeated_inverse() {
+        let instructions = fs::read_to_string("input.txt").expect("can't read file");
+        let instructions = parse_instructions(&instructions);
+        let size = 10007;
+        let shuffle = Affine::from_instructions(&instructions, size);
+        let card = shuffle.pow(5).inverse().unwrap().apply(2020);
+        let mut pos = card;
+        for _ in 0..5 {
+            pos = apply_instructions(pos, size, &instructions);
+        }
+        assert_eq!(pos, 2020);
+        assert_eq!(
+            Affine::from_instruction(&Instruction::DealWithIncr(2), 10).inverse(),
+            None
+        );
+    }
+
     #[test]
     fn part_1() {
         let instructions = fs::read_to_string("input.txt").expect("can't read file");
@@ -0,0 +1,116 @@
+use std::fs;
+use std::io;
+
+use intcode::{Error, Machine, Network, Node, NodeId, NonBlocking, Outbox, Outcome, State};
+
+const COMPUTERS: usize = 50;
+const NAT: usize = 255;
+
+/// Reassembles `(address, x, y)` from every interface and sends `(x, y)` out
+/// on the port for that address.
+#[derive(Default)]
+struct Switch {
+    partial: Vec<Vec<isize>>,
+}
+
+impl Node for Switch {
+    fn receive(&mut self, port: usize, value: isize) {
+        if self.partial.len() <= port {
+            self.partial.resize(port + 1, Vec::new());
+        }
+        self.partial[port].push(value);
+    }
+
+    fn run(&mut self, outbox: &mut Outbox) -> Result<State, Error> {
+        for partial in &mut self.partial {
+            while partial.len() >= 3 {
+                let packet = partial.drain(..3).collect::<Vec<_>>();
+                outbox.send_to(packet[0] as usize, packet[1]);
+                outbox.send_to(packet[0] as usize, packet[2]);
+            }
+        }
+        Ok(State::Blocked)
+    }
+}
+
+/// Stops the network as soon as a whole packet arrives.
+#[derive(Default)]
+struct Monitor {
+    packet: Vec<isize>,
+}
+
+impl Node for Monitor {
+    fn receive(&mut self, _port: usize, value: isize) {
+        self.packet.push(value);
+    }
+
+    fn run(&mut self, outbox: &mut Outbox) -> Result<State, Error> {
+        if self.packet.len() >= 2 {
+            outbox.stop();
+        }
+        Ok(State::Blocked)
+    }
+}
+
+/// Boots every computer with its address and wires it to the switch, with
+/// `nat` listening on address 255. Returns the interfaces.
+fn build_network(network: &mut Network, program: &[isize], nat: NodeId) -> Vec<NodeId> {
+    let switch = network.add(Switch::default());
+    network.connect_ports(switch, NAT, nat, 0);
+    (0..COMPUTERS)
+        .map(|address| {
+            let machine = Machine::with_input(program.to_vec(), [address as isize]);
+            let id = network.add(NonBlocking::new(machine, -1));
+            network.connect_ports(id, 0, switch, address);
+            network.connect_ports(switch, address, id, 0);
+            id
+        })
+        .collect()
+}
+
+fn first_packet(network: &mut Network, monitor: NodeId) -> isize {
+    match network.run() {
+        Ok(Outcome::Stopped(id)) if id == monitor => (),
+        other => panic!("no packet for {}: {:?}", NAT, other),
+    }
+    network.node::<Monitor>(monitor).unwrap().packet[1]
+}
+
+fn main() -> io::Result<()> {
+    let program = fs::read_to_string("input.txt")?
+        .trim()
+        .split(',')
+        .map(|x| x.parse().expect("not a number"))
+        .collect::<Vec<isize>>();
+    let mut network = Network::new();
+    let monitor = network.add(Monitor::default());
+    build_network(&mut network, &program, monitor);
+    println!("{}", first_packet(&mut network, monitor));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    /// Reads its address, then passes every packet `(x, y)` it gets on to the
+    /// next address as `(x, y + 1)`. The last one sends to 255 instead.
+    const RELAY: [isize; 42] = [
+        3, 100, 3, 101, 1008, 101, -1, 103, 1005, 103, 2, 3, 102, 1001, 100, 1, 104, 1007, 104, 50,
+        105, 1005, 105, 28, 1101, 0, 255, 104, 4, 104, 4, 101, 1001, 102, 1, 102, 4, 102, 1105, 1,
+        2, 99,
+    ];
+
+    #[test_case(0, 0 => 50; "from the start")]
+    #[test_case(20, 5 => 35; "halfway")]
+    fn test(to: usize, y: isize) -> isize {
+        let mut network = Network::new();
+        let monitor = network.add(Monitor::default());
+        let interfaces = build_network(&mut network, &RELAY, monitor);
+        network.send(interfaces[to], 0, 7);
+        network.send(interfaces[to], 0, y);
+        first_packet(&mut network, monitor)
+    }
+}
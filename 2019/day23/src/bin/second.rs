@@ -0,0 +1,138 @@
+use std::fs;
+use std::io;
+
+use intcode::{Error, Machine, Network, Node, NodeId, NonBlocking, Outbox, Outcome, State};
+
+const COMPUTERS: usize = 50;
+const NAT: usize = 255;
+
+/// Reassembles `(address, x, y)` from every interface and sends `(x, y)` out
+/// on the port for that address.
+#[derive(Default)]
+struct Switch {
+    partial: Vec<Vec<isize>>,
+}
+
+impl Node for Switch {
+    fn receive(&mut self, port: usize, value: isize) {
+        if self.partial.len() <= port {
+            self.partial.resize(port + 1, Vec::new());
+        }
+        self.partial[port].push(value);
+    }
+
+    fn run(&mut self, outbox: &mut Outbox) -> Result<State, Error> {
+        for partial in &mut self.partial {
+            while partial.len() >= 3 {
+                let packet = partial.drain(..3).collect::<Vec<_>>();
+                outbox.send_to(packet[0] as usize, packet[1]);
+                outbox.send_to(packet[0] as usize, packet[2]);
+            }
+        }
+        Ok(State::Blocked)
+    }
+}
+
+/// Holds on to the last packet for 255, and sends it to address 0 whenever
+/// the network goes idle. Stops the network when it's about to send the same
+/// Y twice in a row.
+#[derive(Default)]
+struct Nat {
+    packet: Vec<isize>,
+    delivered: Option<isize>,
+}
+
+impl Node for Nat {
+    fn receive(&mut self, _port: usize, value: isize) {
+        if self.packet.len() == 2 {
+            self.packet.clear();
+        }
+        self.packet.push(value);
+    }
+
+    fn run(&mut self, _outbox: &mut Outbox) -> Result<State, Error> {
+        Ok(State::Blocked)
+    }
+
+    fn idle(&mut self, outbox: &mut Outbox) {
+        if self.packet.len() < 2 {
+            return;
+        }
+        if self.delivered == Some(self.packet[1]) {
+            outbox.stop();
+            return;
+        }
+        outbox.send(self.packet[0]);
+        outbox.send(self.packet[1]);
+        self.delivered = Some(self.packet[1]);
+    }
+}
+
+/// Boots every computer with its address and wires it to the switch, with
+/// `nat` listening on address 255. Returns the interfaces.
+fn build_network(network: &mut Network, program: &[isize], nat: NodeId) -> Vec<NodeId> {
+    let switch = network.add(Switch::default());
+    network.connect_ports(switch, NAT, nat, 0);
+    (0..COMPUTERS)
+        .map(|address| {
+            let machine = Machine::with_input(program.to_vec(), [address as isize]);
+            let id = network.add(NonBlocking::new(machine, -1));
+            network.connect_ports(id, 0, switch, address);
+            network.connect_ports(switch, address, id, 0);
+            id
+        })
+        .collect()
+}
+
+fn repeated_y(network: &mut Network, nat: NodeId) -> isize {
+    match network.run() {
+        Ok(Outcome::Stopped(id)) if id == nat => (),
+        other => panic!("the NAT never repeated itself: {:?}", other),
+    }
+    network.node::<Nat>(nat).unwrap().delivered.unwrap()
+}
+
+fn main() -> io::Result<()> {
+    let program = fs::read_to_string("input.txt")?
+        .trim()
+        .split(',')
+        .map(|x| x.parse().expect("not a number"))
+        .collect::<Vec<isize>>();
+    let mut network = Network::new();
+    let nat = network.add(Nat::default());
+    let interfaces = build_network(&mut network, &program, nat);
+    network.connect(nat, interfaces[0]);
+    println!("{}", repeated_y(&mut network, nat));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    /// Reads its address, then passes every packet `(x, y)` it gets on to the
+    /// next address as `(x, y + 1)`, where the 1 is at index 34. The last one
+    /// sends to 255 instead.
+    const RELAY: [isize; 42] = [
+        3, 100, 3, 101, 1008, 101, -1, 103, 1005, 103, 2, 3, 102, 1001, 100, 1, 104, 1007, 104, 50,
+        105, 1005, 105, 28, 1101, 0, 255, 104, 4, 104, 4, 101, 1001, 102, 1, 102, 4, 102, 1105, 1,
+        2, 99,
+    ];
+
+    #[test_case(0, 3 => 3; "from the start")]
+    #[test_case(20, 5 => 5; "halfway")]
+    fn test(to: usize, y: isize) -> isize {
+        // Pass packets on as they are, so the NAT keeps getting the same one
+        let mut relay = RELAY;
+        relay[34] = 0;
+        let mut network = Network::new();
+        let nat = network.add(Nat::default());
+        let interfaces = build_network(&mut network, &relay, nat);
+        network.connect(nat, interfaces[0]);
+        network.send(interfaces[to], 0, 7);
+        network.send(interfaces[to], 0, y);
+        repeated_y(&mut network, nat)
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+use aoc_common::automaton::{Automaton, Dense, Rule, VonNeumann};
+
+const SIZE: usize = 5;
+
+/// A bit per tile, row by row from the top left, so the grid is its own
+/// biodiversity rating.
+type Grid = u32;
+
+fn string_to_data(string: &str) -> Grid {
+    string
+        .lines()
+        .flat_map(|line| line.trim().chars())
+        .enumerate()
+        .filter(|&(_, c)| c == '#')
+        .fold(0, |grid, (tile, _)| grid | 1 << tile)
+}
+
+/// A bug dies unless there's exactly one bug next to it, and an empty tile
+/// gets infested if one or two bugs are next to it.
+fn rule() -> Rule {
+    Rule::new(&[1, 2], &[1])
+}
+
+/// Tiles are numbered the same way as the bits of the grid.
+fn automaton(grid: Grid) -> Dense {
+    let cells = (0..SIZE * SIZE)
+        .map(|tile| {
+            let cell = [(tile % SIZE) as isize, (tile / SIZE) as isize];
+            (cell, grid & 1 << tile != 0)
+        })
+        .collect::<Vec<_>>();
+    Dense::new(&cells, &VonNeumann, rule())
+}
+
+fn rating(automaton: &Dense) -> Grid {
+    automaton
+        .alive()
+        .iter()
+        .enumerate()
+        .filter(|&(_, &bug)| bug)
+        .fold(0, |grid, (tile, _)| grid | 1 << tile)
+}
+
+fn first_repeat(grid: Grid) -> Grid {
+    let mut automaton = automaton(grid);
+    let mut seen = HashSet::new();
+    while seen.insert(rating(&automaton)) {
+        automaton.step();
+    }
+    rating(&automaton)
+}
+
+fn main() -> io::Result<()> {
+    let input = fs::read_to_string("input.txt")?;
+    println!("{}", first_repeat(string_to_data(&input)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("....#
+#..#.
+#..##
+..#..
+#...." => 2129920; "example")]
+    fn test(input: &str) -> Grid {
+        first_repeat(string_to_data(input))
+    }
+
+    #[test_case(".....
+.....
+.....
+#....
+.#...", 2129920; "example")]
+    #[test_case("#....
+.....
+.....
+.....
+.....", 1; "top left")]
+    fn test_biodiversity(input: &str, rating: Grid) {
+        assert_eq!(string_to_data(input), rating);
+    }
+
+    #[test_case("....#
+#..#.
+#..##
+..#..
+#....", "#..#.
+####.
+###.#
+##.##
+.##..", 1; "after 1 minute")]
+    #[test_case("....#
+#..#.
+#..##
+..#..
+#....", "####.
+....#
+##..#
+.....
+##...", 4; "after 4 minutes")]
+    fn test_step(input: &str, expected: &str, minutes: usize) {
+        let mut automaton = automaton(string_to_data(input));
+        automaton.run(minutes);
+        assert_eq!(rating(&automaton), string_to_data(expected));
+    }
+}
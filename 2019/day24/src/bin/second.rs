@@ -0,0 +1,96 @@
+use std::fs;
+use std::io;
+
+use aoc_common::automaton::{Automaton, Neighborhood, Rule, Sparse};
+
+const SIZE: usize = 5;
+
+/// A bit per tile, row by row from the top left, so the grid is its own
+/// biodiversity rating.
+type Grid = u32;
+
+fn string_to_data(string: &str) -> Grid {
+    string
+        .lines()
+        .flat_map(|line| line.trim().chars())
+        .enumerate()
+        .filter(|&(_, c)| c == '#')
+        .fold(0, |grid, (tile, _)| grid | 1 << tile)
+}
+
+fn is_bug(grid: Grid, x: isize, y: isize) -> bool {
+    let inside = (0..SIZE as isize).contains(&x) && (0..SIZE as isize).contains(&y);
+    inside && grid & 1 << (y as usize * SIZE + x as usize) != 0
+}
+
+const MIDDLE: isize = SIZE as isize / 2;
+
+/// A tile on a level, levels counting up going inward.
+type Tile = (isize, isize, isize);
+
+/// Grids nest in the middle tile of the one around them. Walking off the edge
+/// ends up next to the middle of the level above, and walking into the middle
+/// ends up next to a whole edge of the level below.
+struct Recursive;
+
+impl Neighborhood<Tile> for Recursive {
+    fn neighbors(&self, (level, x, y): Tile) -> impl Iterator<Item = Tile> {
+        let mut neighbours = Vec::with_capacity(8);
+        for &(dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)].iter() {
+            let (nx, ny) = (x + dx, y + dy);
+            if !(0..SIZE as isize).contains(&nx) || !(0..SIZE as isize).contains(&ny) {
+                neighbours.push((level - 1, MIDDLE + dx, MIDDLE + dy));
+            } else if (nx, ny) == (MIDDLE, MIDDLE) {
+                let edge = move |i| match (dx, dy) {
+                    (1, _) => (level + 1, 0, i),
+                    (-1, _) => (level + 1, SIZE as isize - 1, i),
+                    (_, 1) => (level + 1, i, 0),
+                    _ => (level + 1, i, SIZE as isize - 1),
+                };
+                neighbours.extend((0..SIZE as isize).map(edge));
+            } else {
+                neighbours.push((level, nx, ny));
+            }
+        }
+        neighbours.into_iter()
+    }
+}
+
+/// A bug dies unless there's exactly one bug next to it, and an empty tile
+/// gets infested if one or two bugs are next to it. The middle tile holds the
+/// next level, so a bug there doesn't count.
+fn count_bugs(grid: Grid, minutes: usize) -> usize {
+    let bugs = (0..SIZE as isize)
+        .flat_map(|y| (0..SIZE as isize).map(move |x| (0, x, y)))
+        .filter(|&(_, x, y)| (x, y) != (MIDDLE, MIDDLE) && is_bug(grid, x, y));
+    let mut eris = Sparse::new(bugs, Recursive, Rule::new(&[1, 2], &[1]));
+    eris.run(minutes);
+    eris.population()
+}
+
+fn main() -> io::Result<()> {
+    let input = fs::read_to_string("input.txt")?;
+    println!("{}", count_bugs(string_to_data(&input), 200));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("....#
+#..#.
+#..##
+..#..
+#....", 10 => 99; "example")]
+    #[test_case(".....
+.....
+..#..
+.....
+.....", 10 => 0; "bug in the middle")]
+    fn test(input: &str, minutes: usize) -> usize {
+        count_bugs(string_to_data(input), minutes)
+    }
+}
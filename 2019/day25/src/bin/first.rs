@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+use intcode::{Ascii, Machine, Status};
+
+/// Picking these up ends the game or gets the droid stuck.
+const DANGEROUS: &[&str] = &[
+    "infinite loop",
+    "giant electromagnet",
+    "molten lava",
+    "escape pod",
+    "photons",
+];
+
+const CHECKPOINT: &str = "Security Checkpoint";
+
+#[derive(Debug, Default, PartialEq)]
+struct Room {
+    name: String,
+    doors: Vec<String>,
+    items: Vec<String>,
+}
+
+/// The last room described in `text`. Getting thrown out of somewhere
+/// describes two rooms, and the second one is where the droid ends up.
+fn parse_room(text: &str) -> Option<Room> {
+    let start = text.rfind("== ")?;
+    let mut lines = text[start..].lines();
+    let name = lines.next()?.trim_matches(|c| c == '=' || c == ' ');
+    let mut room = Room {
+        name: name.to_string(),
+        ..Room::default()
+    };
+    let mut list = None;
+    for line in lines {
+        match line {
+            "Doors here lead:" => list = Some(&mut room.doors),
+            "Items here:" => list = Some(&mut room.items),
+            _ => match (line.strip_prefix("- "), list.as_mut()) {
+                (Some(entry), Some(list)) => list.push(entry.to_string()),
+                _ => list = None,
+            },
+        }
+    }
+    Some(room)
+}
+
+fn opposite(direction: &str) -> &'static str {
+    match direction {
+        "north" => "south",
+        "south" => "north",
+        "east" => "west",
+        "west" => "east",
+        _ => panic!("not a direction: {}", direction),
+    }
+}
+
+/// The number the airlock wants, from the message that lets the droid in.
+fn password(text: &str) -> Option<&str> {
+    let start = text.find("typing ")? + "typing ".len();
+    let digits = text[start..].find(|c: char| !c.is_ascii_digit())?;
+    Some(&text[start..start + digits]).filter(|password| !password.is_empty())
+}
+
+/// Which item to drop or pick up to get from one subset of `count` items to
+/// the next, so that every subset gets tried once, one change at a time.
+fn gray_code(count: usize) -> impl Iterator<Item = usize> {
+    (1..1_usize << count).map(|i| i.trailing_zeros() as usize)
+}
+
+struct Droid {
+    ascii: Ascii,
+    items: Vec<String>,
+    /// How to get from the hull breach to the checkpoint, and which way the
+    /// pressure-sensitive floor is from there
+    checkpoint: Option<(Vec<String>, String)>,
+}
+
+impl Droid {
+    fn new(program: &[isize]) -> Droid {
+        Droid {
+            ascii: Ascii::new(Machine::new(program.to_vec())),
+            items: Vec::new(),
+            checkpoint: None,
+        }
+    }
+
+    /// Runs until the game wants the next command and returns what it said.
+    fn read(&mut self) -> String {
+        match self.ascii.run() {
+            Ok(Status::NeedInput) | Ok(Status::Halted) => self.ascii.take_text(),
+            other => panic!("game crashed: {:?}", other),
+        }
+    }
+
+    fn command(&mut self, command: &str) -> String {
+        self.ascii.send_line(command);
+        self.read()
+    }
+
+    /// Walks every room once, depth first, picking up everything that's
+    /// safe, and comes back to where it started. Stops at the checkpoint,
+    /// since going past it without the right items throws the droid out.
+    fn explore(&mut self, room: Room, path: &mut Vec<String>, seen: &mut HashSet<String>) {
+        seen.insert(room.name.clone());
+        for item in room.items {
+            if !DANGEROUS.contains(&item.as_str()) {
+                self.command(&format!("take {}", item));
+                self.items.push(item);
+            }
+        }
+        let back = path.last().map(|door| opposite(door));
+        if room.name == CHECKPOINT {
+            let floor = room
+                .doors
+                .into_iter()
+                .find(|door| Some(door.as_str()) != back);
+            self.checkpoint = Some((path.clone(), floor.expect("no way past the checkpoint")));
+            return;
+        }
+        for door in room.doors {
+            if Some(door.as_str()) == back {
+                continue;
+            }
+            let next = parse_room(&self.command(&door)).expect("door doesn't lead anywhere");
+            if !seen.contains(&next.name) {
+                path.push(door.clone());
+                self.explore(next, path, seen);
+                path.pop();
+            }
+            self.command(opposite(&door));
+        }
+    }
+
+    /// Tries every combination of items on the floor until one gets the
+    /// droid through.
+    fn crack(&mut self) -> String {
+        let (path, floor) = self.checkpoint.clone().expect("never found the checkpoint");
+        for door in &path {
+            self.command(door);
+        }
+        let mut held = vec![true; self.items.len()];
+        let mut toggles = gray_code(self.items.len());
+        loop {
+            let text = self.command(&floor);
+            if let Some(password) = password(&text) {
+                return password.to_string();
+            }
+            let item = toggles.next().expect("no combination of items works");
+            let verb = if held[item] { "drop" } else { "take" };
+            self.command(&format!("{} {}", verb, self.items[item]));
+            held[item] = !held[item];
+        }
+    }
+}
+
+fn main() -> io::Result<()> {
+    let program = fs::read_to_string("input.txt")?
+        .trim()
+        .split(',')
+        .map(|x| x.parse().expect("not a number"))
+        .collect::<Vec<isize>>();
+    let mut droid = Droid::new(&program);
+    let start = parse_room(&droid.read()).expect("no starting room");
+    droid.explore(start, &mut Vec::new(), &mut HashSet::new());
+    println!("{}", droid.crack());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    const HULL_BREACH: &str = "
+
+
+== Hull Breach ==
+You got in through a hole in the floor here. To keep your ship from also freezing, the hole has been sealed.
+
+Doors here lead:
+- north
+- east
+- west
+
+Items here:
+- mug
+- escape pod
+
+Command?
+";
+
+    const EJECTED: &str = "
+
+
+== Pressure-Sensitive Floor ==
+Analyzing...
+
+Doors here lead:
+- west
+
+A loud, robotic voice says \"Alert! Droids on this ship are heavier than the detected value!\" and you are ejected back to the checkpoint.
+
+
+
+== Security Checkpoint ==
+In the next room, a pressure-sensitive floor will verify your identity.
+
+Doors here lead:
+- east
+- south
+
+Command?
+";
+
+    #[test]
+    fn test_parse_room() {
+        let room = parse_room(HULL_BREACH).unwrap();
+        assert_eq!(room.name, "Hull Breach");
+        assert_eq!(room.doors, ["north", "east", "west"]);
+        assert_eq!(room.items, ["mug", "escape pod"]);
+
+        let room = parse_room(EJECTED).unwrap();
+        assert_eq!(room.name, CHECKPOINT);
+        assert_eq!(room.doors, ["east", "south"]);
+        assert!(room.items.is_empty());
+
+        assert_eq!(parse_room("You can't go that way.\n\nCommand?\n"), None);
+    }
+
+    #[test_case("\"Oh, hello! You should be able to get in by typing 2424 on the keypad at the main airlock.\"" => Some("2424"); "found")]
+    #[test_case(EJECTED => None; "ejected")]
+    fn test_password(text: &str) -> Option<&str> {
+        password(text)
+    }
+
+    #[test_case(1)]
+    #[test_case(3)]
+    #[test_case(8)]
+    fn test_gray_code(count: usize) {
+        let mut subset = 0_usize;
+        let mut seen = HashSet::new();
+        seen.insert(subset);
+        for item in gray_code(count) {
+            assert!(item < count);
+            subset ^= 1 << item;
+            assert!(seen.insert(subset), "tried {:b} twice", subset);
+        }
+        assert_eq!(seen.len(), 1 << count);
+    }
+}
@@ -0,0 +1,18 @@
+use std::fs;
+use std::io;
+
+use intcode::{Ascii, Machine};
+
+/// There's no puzzle for the last star, so this is for playing the game by
+/// hand: commands come from stdin, one per line.
+fn main() -> io::Result<()> {
+    let program = fs::read_to_string("input.txt")?
+        .trim()
+        .split(',')
+        .map(|x| x.parse().expect("not a number"))
+        .collect::<Vec<isize>>();
+    let mut ascii = Ascii::new(Machine::new(program));
+    let status = ascii.repl(io::stdin().lock(), &mut io::stdout())?;
+    println!("{:?}", status);
+    Ok(())
+}
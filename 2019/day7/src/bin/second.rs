@@ -1,203 +1,26 @@
 use std::io;
 use std::fs;
-use std::collections::VecDeque;
 
-#[derive(Debug, PartialEq)]
-enum ParamMode {
-  Position,
-  Immediate,
-}
-
-fn param_mode(digit: isize) -> ParamMode {
-  match digit {
-      0 => ParamMode::Position,
-      1 => ParamMode::Immediate,
-      _ => panic!("invalid parameter mode: {}", digit),
-  }
-}
-
-fn param_to_value(program: &Vec<isize>, pos: usize, mode: ParamMode) -> isize {
-  match mode {
-    ParamMode::Position => {let inpos = program[pos] as usize; program[inpos]},
-    ParamMode::Immediate => program[pos],
-  }
-}
-
-#[derive(Debug)]
-enum ProgramState {
-  Run,
-  Exit,
-  NeedInput,
-}
-
-fn run_program(part: &mut PipelinePart) -> ProgramState {
-  let program = &mut part.program;
-  let ip = &mut part.ip;
-  loop {
-    let mut digits = Vec::new();
-    let mut remain = program[*ip];
-    for i in (2..5).rev() {
-      digits.push(remain / 10_isize.pow(i));
-      remain = remain % 10_isize.pow(i);
-    }
-    let opcode = remain;
-    let param_3_mode = param_mode(digits[0]);
-    let param_2_mode = param_mode(digits[1]);
-    let param_1_mode = param_mode(digits[2]);
-    match opcode {
-      // in in out
-      1 | 2 | 7 | 8 => {
-        let inval1 = param_to_value(&program, *ip + 1, param_1_mode);
-        let inval2 = param_to_value(&program, *ip + 2, param_2_mode);
-        assert_eq!(param_3_mode, ParamMode::Position);
-        let outpos = program[*ip + 3] as usize;
-        match opcode {
-          // add
-          1 => program[outpos] = inval1 + inval2,
-          // multiply
-          2 => program[outpos] = inval1 * inval2,
-          // less than
-          7 => program[outpos] = match inval1 < inval2 {
-            true => 1,
-            false => 0,
-          },
-          // equals
-          8 => program[outpos] = match inval1 == inval2 {
-            true => 1,
-            false => 0,
-          },
-          _ => panic!("program error"),
-        }
-        *ip += 4;
-      },
-      3 => {
-        // in
-        // read from input
-        if part.input_queue.len() <= 0 {
-          return ProgramState::NeedInput;
-        }
-        assert_eq!(param_1_mode, ParamMode::Position);
-        let outpos = program[*ip + 1] as usize;
-        program[outpos] = part.input_queue.pop_front().unwrap();
-        *ip += 2;
-      }
-      4 => {
-        // out
-        // write to output
-        let inval = param_to_value(&program, *ip + 1, param_1_mode);
-        part.output_queue.push_back(inval);
-        *ip += 2;
-      }
-      5 | 6 => {
-        // in in
-        let inval1 = param_to_value(&program, *ip + 1, param_1_mode);
-        let inval2 = param_to_value(&program, *ip + 2, param_2_mode);
-        match opcode {
-          5 => {
-            // jump if true
-            if inval1 != 0 {
-              *ip = inval2 as usize;
-            } else {
-              *ip += 3;
-            }
-          },
-          6 => {
-            // jump if false
-            if inval1 == 0 {
-              *ip = inval2 as usize;
-            } else {
-              *ip += 3;
-            }
-          },
-          _ => panic!("program error"),
-        }
-      },
-      99 => break,
-      _ => panic!("invalid opcode: {}", opcode),
-    }
-  }
-  ProgramState::Exit
-}
-
-#[derive(Debug)]
-struct PipelinePart {
-  program: Vec<isize>,
-  state: ProgramState,
-  input_queue: VecDeque<isize>,
-  output_queue: VecDeque<isize>,
-  ip: usize,
-  previous: usize,
-}
+use intcode::{Machine, Network, Outcome, Sink};
 
 fn run_pipe(program: &Vec<isize>, a: isize, b: isize, c: isize, d: isize, e: isize) -> isize {
-  let mut pipeline: [PipelinePart; 5] = [
-    PipelinePart{
-      program: program.to_vec(),
-      state: ProgramState::Run,
-      input_queue: VecDeque::from(vec![a, 0]),
-      output_queue: VecDeque::new(),
-      ip: 0,
-      previous: 4},
-    PipelinePart{
-      program: program.to_vec(),
-      state: ProgramState::Run,
-      input_queue: VecDeque::from(vec![b]),
-      output_queue: VecDeque::new(),
-      ip: 0,
-      previous: 0},
-    PipelinePart{
-      program: program.to_vec(),
-      state: ProgramState::Run,
-      input_queue: VecDeque::from(vec![c]),
-      output_queue: VecDeque::new(),
-      ip: 0,
-      previous: 1},
-    PipelinePart{
-      program: program.to_vec(),
-      state: ProgramState::Run,
-      input_queue: VecDeque::from(vec![d]),
-      output_queue: VecDeque::new(),
-      ip: 0,
-      previous: 2},
-    PipelinePart{
-      program: program.to_vec(),
-      state: ProgramState::Run,
-      input_queue: VecDeque::from(vec![e]),
-      output_queue: VecDeque::new(),
-      ip: 0,
-      previous: 3},
-  ];
-  let mut exited = 0;
-  while exited < 5 {
-    exited = 0;
-    for index in 0..5 {
-      loop {
-        match pipeline[index].state {
-          ProgramState::Exit => {
-            exited += 1;
-            break; // go to the next part
-          },
-          ProgramState::Run => {
-            pipeline[index].state = run_program(&mut pipeline[index]);
-          },
-          ProgramState::NeedInput => {
-            let previdx = pipeline[index].previous;
-            if pipeline[previdx].output_queue.len() > 0 {
-              let temp = pipeline[previdx].output_queue.pop_front().unwrap();
-              pipeline[index].input_queue.push_back(temp);
-              pipeline[index].state = ProgramState::Run;
-            } else {
-              match pipeline[previdx].state {
-                ProgramState::Exit => panic!("pipeline fucked: {:?}", pipeline),
-                _ => break, // go to the next part
-              }
-            }
-          }
-        }
-      }
-    }
+  let mut network = Network::new();
+  let amps = [a, b, c, d, e].iter()
+                            .map(|&setting| network.add(Machine::with_input(program.to_vec(), vec![setting])))
+                            .collect::<Vec<_>>();
+  for index in 0..5 {
+    network.connect(amps[index], amps[(index + 1) % 5]);
+  }
+  let thrusters = network.add(Sink::default());
+  network.connect(amps[4], thrusters);
+  network.send(amps[0], 0, 0);
+  match network.run() {
+    Ok(Outcome::Halted) => (),
+    Ok(Outcome::Deadlock(waiting)) => panic!("amplifiers deadlocked waiting for input: {:?}", waiting),
+    Ok(Outcome::Stopped(node)) => panic!("amplifier {:?} stopped the feedback loop", node),
+    Err(error) => panic!("program error: {}", error),
   }
-  pipeline[4].output_queue.pop_front().unwrap()
+  *network.node::<Sink>(thrusters).unwrap().values.last().unwrap()
 }
 
 fn main() -> io::Result<()> {
@@ -1,3 +1,4 @@
+use aoc_common::ocr;
 use std::fs;
 use std::io;
 
@@ -44,5 +45,9 @@ fn main() -> io::Result<()> {
     }
     println!();
   }
+  let pixels = (0..HEIGHT)
+    .map(|y| (0..WIDTH).map(|x| output[x][y] == 'X').collect())
+    .collect::<Vec<Vec<bool>>>();
+  println!("{}", ocr::recognize(&pixels).expect("unreadable letters"));
   Ok(())
 }
@@ -0,0 +1,199 @@
+//! Talking to programs that speak ASCII.
+//!
+//! Output values that are ASCII characters are collected as text, which can
+//! be read back a line or a frame at a time. A frame is a block of lines
+//! ended by an empty line, which is how camera images and the like are
+//! printed. Anything else a program writes, usually the answer, is kept
+//! apart in [`Ascii::values`].
+
+use crate::machine::{Error, Machine, Status};
+use std::io::{self, BufRead, Write};
+
+#[derive(Clone, Debug)]
+pub struct Ascii {
+    machine: Machine,
+    text: String,
+    values: Vec<isize>,
+}
+
+impl Ascii {
+    #[must_use]
+    pub fn new(machine: Machine) -> Self {
+        Self {
+            machine,
+            text: String::new(),
+            values: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    /// Queues `text` as input, one value per character.
+    pub fn send(&mut self, text: &str) {
+        self.machine.extend_input(text.chars().map(|c| c as isize));
+    }
+
+    /// Queues `line` and a newline as input.
+    pub fn send_line(&mut self, line: &str) {
+        self.send(line);
+        self.send("\n");
+    }
+
+    /// Runs until the program needs input or halts, and sorts what it wrote
+    /// into text and values.
+    ///
+    /// # Errors
+    /// If the program does something wrong.
+    pub fn run(&mut self) -> Result<Status, Error> {
+        let status = self.machine.run_until_input()?;
+        for value in self.machine.take_output() {
+            match u8::try_from(value) {
+                Ok(byte) if byte.is_ascii() => self.text.push(char::from(byte)),
+                _ => self.values.push(value),
+            }
+        }
+        Ok(status)
+    }
+
+    /// Text that hasn't been read yet.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn take_text(&mut self) -> String {
+        std::mem::take(&mut self.text)
+    }
+
+    /// The next complete line, without its newline.
+    pub fn read_line(&mut self) -> Option<String> {
+        let end = self.text.find('\n')?;
+        let mut line = self.text.drain(..=end).collect::<String>();
+        line.pop();
+        Some(line)
+    }
+
+    /// The next complete frame, every line ending in a newline. The empty
+    /// line after it is dropped, as are empty lines before it.
+    pub fn read_frame(&mut self) -> Option<String> {
+        let start = self.text.len() - self.text.trim_start_matches('\n').len();
+        let end = self.text[start..].find("\n\n")? + start;
+        let frame = self.text[start..=end].to_string();
+        self.text.drain(..end + 2);
+        Some(frame)
+    }
+
+    /// Output values that aren't ASCII characters.
+    #[must_use]
+    pub fn values(&self) -> &[isize] {
+        &self.values
+    }
+
+    pub fn take_values(&mut self) -> Vec<isize> {
+        std::mem::take(&mut self.values)
+    }
+
+    /// Plays the program interactively: prints what it writes, values on a
+    /// line of their own after the text, and sends it every line read from
+    /// `input`. Returns once the program halts or `input` runs out.
+    ///
+    /// # Errors
+    /// If reading or writing fails, or the program does something wrong.
+    pub fn repl(&mut self, mut input: impl BufRead, output: &mut impl Write) -> io::Result<Status> {
+        loop {
+            let status = self.run().map_err(io::Error::other)?;
+            output.write_all(self.take_text().as_bytes())?;
+            for value in self.take_values() {
+                writeln!(output, "{value}")?;
+            }
+            output.flush()?;
+            if status == Status::Halted {
+                return Ok(status);
+            }
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(status);
+            }
+            self.send_line(line.trim_end_matches(['\n', '\r']));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    /// A program that writes `text` and then `answer`.
+    fn printer(text: &str, answer: isize) -> Machine {
+        let mut source = text
+            .bytes()
+            .map(|byte| format!("out #{byte}\n"))
+            .collect::<String>();
+        source.push_str(&format!("out #{answer}\nhlt"));
+        Machine::new(assemble(&source).unwrap())
+    }
+
+    const ECHO: &str = "
+        loop:   in   [c]
+                out  [c]
+                jnz  #1, #loop
+        c:      data 0";
+
+    #[test]
+    fn test_lines() {
+        let mut ascii = Ascii::new(printer("first\nsecond\nrest", 1_000_000));
+        assert_eq!(ascii.run(), Ok(Status::Halted));
+        assert_eq!(ascii.read_line().as_deref(), Some("first"));
+        assert_eq!(ascii.read_line().as_deref(), Some("second"));
+        assert_eq!(ascii.read_line(), None);
+        assert_eq!(ascii.take_text(), "rest");
+        assert_eq!(ascii.values(), [1_000_000]);
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut ascii = Ascii::new(printer("\n#.\n.#\n\n##\n##\n\n..", -1));
+        assert_eq!(ascii.run(), Ok(Status::Halted));
+        assert_eq!(ascii.read_frame().as_deref(), Some("#.\n.#\n"));
+        assert_eq!(ascii.read_frame().as_deref(), Some("##\n##\n"));
+        assert_eq!(ascii.read_frame(), None);
+        assert_eq!(ascii.text(), "..");
+        assert_eq!(ascii.take_values(), [-1]);
+        assert!(ascii.values().is_empty());
+    }
+
+    #[test]
+    fn test_send() {
+        let mut ascii = Ascii::new(Machine::new(assemble(ECHO).unwrap()));
+        ascii.send_line("NOT A J");
+        ascii.send("é");
+        assert_eq!(ascii.run(), Ok(Status::NeedInput));
+        assert_eq!(ascii.read_line().as_deref(), Some("NOT A J"));
+        assert_eq!(ascii.values(), ['é' as isize]);
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut ascii = Ascii::new(Machine::new(assemble(ECHO).unwrap()));
+        let mut output = Vec::new();
+        let status = ascii.repl("north\r\ntake mug\n".as_bytes(), &mut output);
+        assert_eq!(status.unwrap(), Status::NeedInput);
+        assert_eq!(String::from_utf8(output).unwrap(), "north\ntake mug\n");
+
+        let mut ascii = Ascii::new(printer("You win!\n", 2_424));
+        let mut output = Vec::new();
+        assert_eq!(
+            ascii.repl(io::empty(), &mut output).unwrap(),
+            Status::Halted
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "You win!\n2424\n");
+    }
+}
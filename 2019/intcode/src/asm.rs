@@ -0,0 +1,276 @@
+//! Turning readable source into a program, the inverse of [`crate::disasm`].
+//!
+//! Every line holds at most one instruction or `data` directive, using the
+//! same syntax as the listings:
+//!
+//! ```text
+//! start:  in   [x]              ; position mode
+//!         add  [x], #-1, [rb+2] ; immediate and relative mode
+//!         jnz  [x], #start
+//!         hlt
+//! x:      data 0, start, -5
+//! ```
+//!
+//! Anything after a `;` is a comment. A label is a name followed by a colon
+//! and stands for the address of whatever comes after it. Labels can be used
+//! wherever a number can, with an optional offset like `x+1`. A number at the
+//! start of a line is an address column, which is ignored so a listing can be
+//! assembled again.
+
+use crate::instruction::{Instruction, OpCode, ParamMode};
+use std::{collections::HashMap, fmt};
+
+/// What went wrong, and on which line, counting from 1.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Error {
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    UnknownMnemonic(String),
+    /// The instruction takes `expected` operands
+    OperandCount {
+        expected: usize,
+        found: usize,
+    },
+    /// Not one of `#value`, `[value]` or `[rb+value]`
+    BadOperand(String),
+    /// Not a number or a label with an optional offset
+    BadValue(String),
+    DuplicateLabel(String),
+    UnknownLabel(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ErrorKind::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic {mnemonic:?}"),
+            ErrorKind::OperandCount { expected, found } => {
+                write!(f, "expected {expected} operands, found {found}")
+            }
+            ErrorKind::BadOperand(operand) => write!(f, "bad operand {operand:?}"),
+            ErrorKind::BadValue(value) => write!(f, "bad value {value:?}"),
+            ErrorKind::DuplicateLabel(label) => write!(f, "label {label:?} defined twice"),
+            ErrorKind::UnknownLabel(label) => write!(f, "unknown label {label:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A memory cell whose value may not be known until every label is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Value {
+    Number(isize),
+    Label { name: String, offset: isize },
+}
+
+/// Assembles `source` into a program.
+///
+/// # Errors
+/// On the first line that can't be assembled, or the first use of a label
+/// that's never defined.
+pub fn assemble(source: &str) -> Result<Vec<isize>, Error> {
+    let mut labels = HashMap::new();
+    // Every cell with the line it came from
+    let mut cells = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |kind| Error {
+            line: line_number,
+            kind,
+        };
+        let mut line = line.split(';').next().unwrap_or_default().trim();
+        if let Some((address, rest)) = line.split_once(char::is_whitespace) {
+            if address.parse::<usize>().is_ok() {
+                line = rest.trim_start();
+            }
+        }
+        while let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_name(label) {
+                break;
+            }
+            if labels.insert(label.to_string(), cells.len()).is_some() {
+                return Err(error(ErrorKind::DuplicateLabel(label.to_string())));
+            }
+            line = rest.trim_start();
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let operands = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect::<Vec<_>>();
+        if mnemonic == "data" {
+            for operand in operands {
+                let value = parse_value(operand).map_err(error)?;
+                cells.push((line_number, value));
+            }
+            continue;
+        }
+        let opcode = OpCode::from_mnemonic(mnemonic)
+            .ok_or_else(|| error(ErrorKind::UnknownMnemonic(mnemonic.to_string())))?;
+        if operands.len() != opcode.params() {
+            return Err(error(ErrorKind::OperandCount {
+                expected: opcode.params(),
+                found: operands.len(),
+            }));
+        }
+        let mut instruction = Instruction {
+            opcode,
+            modes: [ParamMode::Position; 3],
+        };
+        let mut params = Vec::new();
+        for (mode, operand) in instruction.modes.iter_mut().zip(operands) {
+            let (operand_mode, value) = parse_operand(operand).map_err(error)?;
+            *mode = operand_mode;
+            params.push((line_number, value));
+        }
+        cells.push((line_number, Value::Number(instruction.encode())));
+        cells.extend(params);
+    }
+
+    cells
+        .into_iter()
+        .map(|(line, value)| match value {
+            Value::Number(number) => Ok(number),
+            Value::Label { name, offset } => match labels.get(&name) {
+                Some(&address) => Ok(address as isize + offset),
+                None => Err(Error {
+                    line,
+                    kind: ErrorKind::UnknownLabel(name),
+                }),
+            },
+        })
+        .collect()
+}
+
+/// Writes `program` in the comma separated format [`crate::parse_program`]
+/// reads.
+#[must_use]
+pub fn format_program(program: &[isize]) -> String {
+    program
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_operand(operand: &str) -> Result<(ParamMode, Value), ErrorKind> {
+    if let Some(value) = operand.strip_prefix('#') {
+        return Ok((ParamMode::Immediate, parse_value(value)?));
+    }
+    let inner = operand
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .ok_or_else(|| ErrorKind::BadOperand(operand.to_string()))?
+        .trim();
+    match inner.strip_prefix("rb").map(str::trim_start) {
+        Some("") => Ok((ParamMode::Relative, Value::Number(0))),
+        Some(offset) if offset.starts_with('+') => {
+            Ok((ParamMode::Relative, parse_value(&offset[1..])?))
+        }
+        Some(offset) if offset.starts_with('-') => match parse_value(&offset[1..])? {
+            Value::Number(number) => Ok((ParamMode::Relative, Value::Number(-number))),
+            Value::Label { .. } => Err(ErrorKind::BadOperand(operand.to_string())),
+        },
+        _ => Ok((ParamMode::Position, parse_value(inner)?)),
+    }
+}
+
+fn parse_value(value: &str) -> Result<Value, ErrorKind> {
+    let value = value.trim();
+    if let Ok(number) = value.parse() {
+        return Ok(Value::Number(number));
+    }
+    let bad_value = || ErrorKind::BadValue(value.to_string());
+    let (name, offset) = match value.find(['+', '-']) {
+        Some(sign) => {
+            let offset = value[sign..]
+                .replace(' ', "")
+                .parse()
+                .map_err(|_| bad_value())?;
+            (value[..sign].trim_end(), offset)
+        }
+        None => (value, 0),
+    };
+    if !is_name(name) {
+        return Err(bad_value());
+    }
+    Ok(Value::Label {
+        name: name.to_string(),
+        offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+    use test_case::test_case;
+
+    #[test_case("add [9], [10], [3]
+        mul [3], [11], [0]
+        hlt
+        data 30, 40, 50" => vec![1,9,10,3,2,3,11,0,99,30,40,50]; "day 2 example")]
+    #[test_case("
+        start:  in   [x]              ; position mode
+                add  [x], #-1, [rb+2] ; immediate and relative mode
+                jnz  [x], #start
+                hlt
+        x:      data 0, start, -5" => vec![3,10,21001,10,-1,2,1005,10,0,99,0,0,-5]; "module example")]
+    #[test_case("a: b: out #a
+        c: out #b+1 ; comment: with colon
+        hlt" => vec![104,0,104,1,99]; "labels")]
+    #[test_case("arb #-3
+        in [rb]
+        out [rb-2]
+        out [rb - 1]
+        out [rb + 1]
+        out [rb+x]
+        x: data x-1, x + 2" => vec![109,-3,203,0,204,-2,204,-1,204,1,204,12,11,14]; "relative")]
+    fn test_assemble(source: &str) -> Vec<isize> {
+        assemble(source).unwrap()
+    }
+
+    #[test_case("jmp #1" => (1, ErrorKind::UnknownMnemonic("jmp".to_string())); "unknown mnemonic")]
+    #[test_case("hlt\nadd #1, #2" => (2, ErrorKind::OperandCount { expected: 3, found: 2 }); "operand count")]
+    #[test_case("hlt\nhlt\nout 5" => (3, ErrorKind::BadOperand("5".to_string())); "bad operand")]
+    #[test_case("out [x+y]" => (1, ErrorKind::BadValue("x+y".to_string())); "bad value")]
+    #[test_case("out [rb-x]" => (1, ErrorKind::BadOperand("[rb-x]".to_string())); "negative label")]
+    #[test_case("x: hlt\nx: hlt" => (2, ErrorKind::DuplicateLabel("x".to_string())); "duplicate label")]
+    #[test_case("out #x\nhlt" => (1, ErrorKind::UnknownLabel("x".to_string())); "unknown label")]
+    fn test_errors(source: &str) -> (usize, ErrorKind) {
+        let error = assemble(source).unwrap_err();
+        (error.line, error.kind)
+    }
+
+    #[test_case(&[3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9]; "day 5 jump example")]
+    #[test_case(&[21101,7,0,1,1105,1,10,104,5,99,2106,0,1,42]; "function call")]
+    #[test_case(&[1,9,10,3,2,3,11,0,99,30,40,50]; "day 2 example")]
+    #[test_case(&[1105,1,7,1,2,3,4,2,12,12,13,99,5,0]; "data between code")]
+    fn test_round_trip(program: &[isize]) {
+        let listing = disassemble(program).to_string();
+        assert_eq!(assemble(&listing).unwrap(), program);
+    }
+
+    #[test]
+    fn test_format_program() {
+        assert_eq!(format_program(&[1, -2, 99]), "1,-2,99");
+    }
+}
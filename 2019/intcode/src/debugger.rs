@@ -0,0 +1,564 @@
+//! Stepping through a program to see what it does.
+//!
+//! A [`Debugger`] wraps a [`Machine`] and is driven by text commands, one per
+//! line, so it works the same typed in at a prompt or read from a script:
+//!
+//! ```text
+//! step [n]              executes n instructions (1), showing each one
+//! continue              runs until a breakpoint or watch, input, exit or error
+//! break <addr|opcode>   stops before the instruction at addr, or any with that
+//!                       mnemonic
+//! delete <addr|opcode>  removes a breakpoint
+//! watch <addr>          stops after the value at addr changes
+//! unwatch <addr>        removes a watch
+//! input <value>...      queues input
+//! print <addr> [n]      shows n values (1, at most [`MAX_PRINT`]) starting at
+//!                       addr
+//! info                  shows ip, rel_base, the queues and the next instruction
+//! rewind [n]            undoes the last n instructions (1)
+//! save <file>           writes the machine to file
+//! load <file>           carries on with a machine from file, forgetting what
+//!                       happened before
+//! quit
+//! ```
+//!
+//! Rewinding restores the last snapshot from before the step it goes back to
+//! and replays from there. Snapshots are taken every [`SNAPSHOT_INTERVAL`]
+//! steps, and whenever input is queued so that replaying never needs input
+//! that wasn't there yet.
+
+use crate::{
+    disasm::decode_item,
+    instruction::OpCode,
+    machine::{Error, Machine, Status},
+};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{self, BufRead, Write},
+};
+
+pub const SNAPSHOT_INTERVAL: usize = 1000;
+
+/// Most values `print` shows at once.
+pub const MAX_PRINT: usize = 1000;
+
+/// What the prompt looks like, also used to echo commands from a script.
+pub const PROMPT: &str = "(intcode) ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    Address(usize),
+    OpCode(OpCode),
+}
+
+impl Breakpoint {
+    fn parse(text: &str) -> Option<Self> {
+        match text.parse() {
+            Ok(address) => Some(Breakpoint::Address(address)),
+            Err(_) => OpCode::from_mnemonic(text).map(Breakpoint::OpCode),
+        }
+    }
+}
+
+/// Why running stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// Took as many steps as asked for
+    Done,
+    Breakpoint(usize),
+    Watch {
+        address: usize,
+        old: isize,
+        new: isize,
+    },
+    NeedInput,
+    Halted,
+    Error(Error),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Done => Ok(()),
+            Stop::Breakpoint(ip) => write!(f, "breakpoint at {ip}"),
+            Stop::Watch { address, old, new } => {
+                write!(f, "[{address}] changed from {old} to {new}")
+            }
+            Stop::NeedInput => write!(f, "waiting for input"),
+            Stop::Halted => write!(f, "halted"),
+            Stop::Error(error) => write!(f, "error: {error}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+    machine: Machine,
+    steps: usize,
+    breakpoints: Vec<Breakpoint>,
+    /// Watched addresses with the value they had last time we looked
+    watches: BTreeMap<usize, isize>,
+    /// Step count and machine, oldest first
+    snapshots: Vec<(usize, Machine)>,
+}
+
+impl Debugger {
+    #[must_use]
+    pub fn new(machine: Machine) -> Self {
+        Self {
+            snapshots: vec![(0, machine.clone())],
+            machine,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Carries on with `machine` from scratch, keeping breakpoints and
+    /// watches.
+    pub fn reset(&mut self, machine: Machine) {
+        for (&address, value) in &mut self.watches {
+            *value = machine.read(address);
+        }
+        self.steps = 0;
+        self.snapshots = vec![(0, machine.clone())];
+        self.machine = machine;
+    }
+
+    /// Number of instructions executed so far.
+    #[must_use]
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Executes up to `count` instructions, writing each one to `out` before
+    /// it runs. Stops early for watches, but not for breakpoints.
+    ///
+    /// # Errors
+    /// If writing to `out` fails.
+    pub fn step(&mut self, count: usize, out: &mut impl Write) -> io::Result<Stop> {
+        for _ in 0..count {
+            self.show_next(out)?;
+            if let Some(stop) = self.step_once() {
+                return Ok(stop);
+            }
+        }
+        Ok(Stop::Done)
+    }
+
+    /// Runs until something stops it. A breakpoint on the instruction it
+    /// starts from doesn't count, so continuing from a breakpoint works.
+    pub fn cont(&mut self) -> Stop {
+        if let Some(stop) = self.step_once() {
+            return stop;
+        }
+        loop {
+            if self.at_breakpoint() {
+                return Stop::Breakpoint(self.machine.ip());
+            }
+            if let Some(stop) = self.step_once() {
+                return stop;
+            }
+        }
+    }
+
+    /// Goes back `count` instructions, or to the start if there weren't that
+    /// many.
+    pub fn rewind(&mut self, count: usize) {
+        let target = self.steps.saturating_sub(count);
+        while self.snapshots.len() > 1 && self.snapshots[self.snapshots.len() - 1].0 > target {
+            self.snapshots.pop();
+        }
+        let (steps, machine) = &self.snapshots[self.snapshots.len() - 1];
+        self.steps = *steps;
+        self.machine = machine.clone();
+        while self.steps < target && self.machine.step() == Ok(Status::Running) {
+            self.steps += 1;
+        }
+        for (&address, value) in &mut self.watches {
+            *value = self.machine.read(address);
+        }
+    }
+
+    pub fn push_input(&mut self, values: impl IntoIterator<Item = isize>) {
+        self.machine.extend_input(values);
+        if self.snapshots[self.snapshots.len() - 1].0 == self.steps {
+            self.snapshots.pop();
+        }
+        self.snapshots.push((self.steps, self.machine.clone()));
+    }
+
+    /// Runs one command. Returns `false` when it's time to quit.
+    ///
+    /// # Errors
+    /// If writing to `out` fails.
+    pub fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let args = words.collect::<Vec<_>>();
+        let number = |index: usize, default: usize| {
+            args.get(index)
+                .map_or(Some(default), |arg| arg.parse().ok())
+        };
+        match (command, args.len()) {
+            ("step" | "s", 0 | 1) => {
+                let Some(count) = number(0, 1) else {
+                    return bad_arguments(line, out);
+                };
+                let stop = self.step(count, out)?;
+                if stop != Stop::Done {
+                    writeln!(out, "{stop}")?;
+                }
+            }
+            ("continue" | "c", 0) => {
+                let stop = self.cont();
+                writeln!(out, "{stop}")?;
+                self.show_next(out)?;
+            }
+            ("break" | "b", 1) => {
+                let Some(breakpoint) = Breakpoint::parse(args[0]) else {
+                    return bad_arguments(line, out);
+                };
+                if !self.breakpoints.contains(&breakpoint) {
+                    self.breakpoints.push(breakpoint);
+                }
+            }
+            ("delete" | "d", 1) => {
+                let Some(breakpoint) = Breakpoint::parse(args[0]) else {
+                    return bad_arguments(line, out);
+                };
+                self.breakpoints.retain(|&other| other != breakpoint);
+            }
+            ("watch" | "w", 1) => {
+                let Ok(address) = args[0].parse() else {
+                    return bad_arguments(line, out);
+                };
+                self.watches.insert(address, self.machine.read(address));
+            }
+            ("unwatch", 1) => {
+                let Ok(address) = args[0].parse() else {
+                    return bad_arguments(line, out);
+                };
+                self.watches.remove(&address);
+            }
+            ("input" | "i", 1..) => {
+                let Ok(values) = args
+                    .iter()
+                    .map(|arg| arg.parse())
+                    .collect::<Result<Vec<_>, _>>()
+                else {
+                    return bad_arguments(line, out);
+                };
+                self.push_input(values);
+            }
+            ("print" | "p", 1 | 2) => {
+                let (Some(address), Some(count)) = (number(0, 0), number(1, 1)) else {
+                    return bad_arguments(line, out);
+                };
+                let Some(end) = address.checked_add(count).filter(|_| count <= MAX_PRINT) else {
+                    return bad_arguments(line, out);
+                };
+                let values = (address..end)
+                    .map(|address| self.machine.read(address).to_string())
+                    .collect::<Vec<_>>();
+                writeln!(out, "[{address}] {}", values.join(", "))?;
+            }
+            ("info", 0) => {
+                writeln!(
+                    out,
+                    "ip {}, rel_base {}, {} steps",
+                    self.machine.ip(),
+                    self.machine.rel_base(),
+                    self.steps
+                )?;
+                writeln!(out, "input {:?}", self.machine.input())?;
+                writeln!(out, "output {:?}", self.machine.output())?;
+                self.show_next(out)?;
+            }
+            ("rewind" | "r", 0 | 1) => {
+                let Some(count) = number(0, 1) else {
+                    return bad_arguments(line, out);
+                };
+                self.rewind(count);
+                self.show_next(out)?;
+            }
+            ("save", 1) => {
+                if let Err(error) = fs::write(args[0], self.machine.save()) {
+                    writeln!(out, "can't save to {}: {error}", args[0])?;
+                }
+            }
+            ("load", 1) => match read_machine(args[0]) {
+                Ok(machine) => {
+                    self.reset(machine);
+                    self.show_next(out)?;
+                }
+                Err(error) => writeln!(out, "can't load {}: {error}", args[0])?,
+            },
+            ("quit" | "q", 0) => return Ok(false),
+            _ => return bad_arguments(line, out),
+        }
+        Ok(true)
+    }
+
+    /// Runs every command in `script`, echoing each one after the prompt.
+    ///
+    /// # Errors
+    /// If reading `script` or writing to `out` fails.
+    pub fn run_script(&mut self, script: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        for line in script.lines() {
+            let line = line?;
+            writeln!(out, "{PROMPT}{line}")?;
+            if !self.command(&line, out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes one instruction, checking the watches afterwards.
+    fn step_once(&mut self) -> Option<Stop> {
+        match self.machine.step() {
+            Ok(Status::Running) => (),
+            Ok(Status::NeedInput) => return Some(Stop::NeedInput),
+            Ok(Status::Halted) => return Some(Stop::Halted),
+            Err(error) => return Some(Stop::Error(error)),
+        }
+        self.steps += 1;
+        if self.steps.is_multiple_of(SNAPSHOT_INTERVAL) {
+            self.snapshots.push((self.steps, self.machine.clone()));
+        }
+        let mut stop = None;
+        for (&address, value) in &mut self.watches {
+            let new = self.machine.read(address);
+            if new != *value && stop.is_none() {
+                stop = Some(Stop::Watch {
+                    address,
+                    old: *value,
+                    new,
+                });
+            }
+            *value = new;
+        }
+        stop
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        let ip = self.machine.ip();
+        let opcode = self
+            .machine
+            .decode()
+            .ok()
+            .map(|instruction| instruction.opcode);
+        self.breakpoints.iter().any(|&breakpoint| match breakpoint {
+            Breakpoint::Address(address) => address == ip,
+            Breakpoint::OpCode(other) => opcode == Some(other),
+        })
+    }
+
+    /// Writes the instruction at the instruction pointer.
+    fn show_next(&self, out: &mut impl Write) -> io::Result<()> {
+        let ip = self.machine.ip();
+        match decode_item(self.machine.memory(), ip) {
+            Some(item) => writeln!(out, "{item}"),
+            None => writeln!(out, "{ip:>6}  ??? {}", self.machine.read(ip)),
+        }
+    }
+}
+
+fn read_machine(path: &str) -> Result<Machine, Box<dyn std::error::Error>> {
+    Ok(Machine::restore(&fs::read_to_string(path)?)?)
+}
+
+fn bad_arguments(line: &str, out: &mut impl Write) -> io::Result<bool> {
+    writeln!(out, "don't know how to {line:?}")?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(program: Vec<isize>, script: &str) -> String {
+        let mut debugger = Debugger::new(Machine::new(program));
+        let mut out = Vec::new();
+        debugger.run_script(script.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_breakpoints_and_watches() {
+        // Day 5: outputs whether the input is equal to 8
+        let program = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        let script = "\
+break out
+watch 9
+continue
+input 8
+continue
+unwatch 9
+continue
+info
+continue";
+        assert_eq!(
+            transcript(program, script),
+            "\
+(intcode) break out
+(intcode) watch 9
+(intcode) continue
+waiting for input
+     0  in   [9]
+(intcode) input 8
+(intcode) continue
+[9] changed from -1 to 8
+     2  eq   [9], [10], [9]
+(intcode) unwatch 9
+(intcode) continue
+breakpoint at 6
+     6  out  [9]
+(intcode) info
+ip 6, rel_base 0, 2 steps
+input []
+output []
+     6  out  [9]
+(intcode) continue
+halted
+     8  hlt
+"
+        );
+    }
+
+    #[test]
+    fn test_step_and_rewind() {
+        // Counts down from 3, printing every number
+        let program = vec![4, 10, 1001, 10, -1, 10, 1005, 10, 0, 99, 3];
+        let script = "\
+step 4
+print 10
+rewind 3
+print 10
+info
+rewind 100
+info
+bogus
+step x
+quit
+step";
+        assert_eq!(
+            transcript(program, script),
+            "\
+(intcode) step 4
+     0  out  [10]
+     2  add  [10], #-1, [10]
+     6  jnz  [10], #0
+     0  out  [10]
+(intcode) print 10
+[10] 2
+(intcode) rewind 3
+     2  add  [10], #-1, [10]
+(intcode) print 10
+[10] 3
+(intcode) info
+ip 2, rel_base 0, 1 steps
+input []
+output [3]
+     2  add  [10], #-1, [10]
+(intcode) rewind 100
+     0  out  [10]
+(intcode) info
+ip 0, rel_base 0, 0 steps
+input []
+output []
+     0  out  [10]
+(intcode) bogus
+don't know how to \"bogus\"
+(intcode) step x
+don't know how to \"step x\"
+(intcode) quit
+"
+        );
+    }
+
+    #[test]
+    fn test_print_limits() {
+        let script = format!(
+            "print 1 3\nprint 1 {}\nprint 2 {}",
+            MAX_PRINT + 1,
+            usize::MAX
+        );
+        assert_eq!(
+            transcript(vec![99, 7, 8], &script),
+            format!(
+                "\
+(intcode) print 1 3
+[1] 7, 8, 0
+(intcode) print 1 {}
+don't know how to \"print 1 {}\"
+(intcode) print 2 {}
+don't know how to \"print 2 {}\"
+",
+                MAX_PRINT + 1,
+                MAX_PRINT + 1,
+                usize::MAX,
+                usize::MAX
+            )
+        );
+    }
+
+    #[test]
+    fn test_rewind_across_snapshots() {
+        // Counts down from 2500 without output
+        let program = vec![1001, 8, -1, 8, 1005, 8, 0, 99, 2500];
+        let mut debugger = Debugger::new(Machine::new(program));
+        assert_eq!(debugger.cont(), Stop::Halted);
+        assert_eq!(debugger.steps(), 5000);
+        debugger.push_input([1]);
+        debugger.rewind(2001);
+        assert_eq!(debugger.steps(), 2999);
+        assert_eq!(debugger.machine().read(8), 1000);
+        assert!(debugger.machine().input().is_empty());
+        debugger.rewind(0);
+        assert_eq!(debugger.steps(), 2999);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("intcode-debugger-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        // Counts down from 3, printing every number
+        let program = vec![4, 10, 1001, 10, -1, 10, 1005, 10, 0, 99, 3];
+        let script = format!("step 2\nsave {path}\nstep 4\nload {path}\ninfo\nload {path}.missing");
+        let transcript = transcript(program, &script);
+        fs::remove_file(path).unwrap();
+        let (transcript, missing) = transcript.trim_end().rsplit_once('\n').unwrap();
+        assert_eq!(
+            transcript,
+            format!(
+                "\
+(intcode) step 2
+     0  out  [10]
+     2  add  [10], #-1, [10]
+(intcode) save {path}
+(intcode) step 4
+     6  jnz  [10], #0
+     0  out  [10]
+     2  add  [10], #-1, [10]
+     6  jnz  [10], #0
+(intcode) load {path}
+     6  jnz  [10], #0
+(intcode) info
+ip 6, rel_base 0, 0 steps
+input []
+output [3]
+     6  jnz  [10], #0
+(intcode) load {path}.missing"
+            )
+        );
+        assert!(missing.starts_with(&format!("can't load {path}.missing: ")));
+    }
+}
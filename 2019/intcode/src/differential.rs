@@ -0,0 +1,184 @@
+//! Running random programs on every engine and checking they all agree with
+//! [`Machine`], the reference.
+//!
+//! Programs are mostly valid instructions with parameters that point into or
+//! just past the program, mixed with the odd value that isn't an instruction.
+//! Nothing stops them from jumping backwards or writing over their own code,
+//! so every run is cut off after [`STEPS`] instructions. What is compared is
+//! the status of every step, where the machine ends up, the input it left,
+//! the output it wrote and its memory.
+//!
+//! A new engine only needs an [`Engine`] implementation and a line in the
+//! `proptest!` block.
+
+use crate::{
+    fast::{FastMachine, DENSE_LIMIT},
+    instruction::{Instruction, OpCode, ParamMode},
+    machine::{Error, Machine, Status},
+};
+use proptest::{collection::vec, prelude::*, sample::select};
+
+/// Programs are at most about this long, and most addresses they use are
+/// below it.
+const SIZE: isize = 64;
+
+/// Instructions run before giving up on a program that doesn't stop.
+const STEPS: usize = 500;
+
+/// An address just past [`DENSE_LIMIT`], so reads and writes there go through
+/// the sparse memory of both engines.
+const FAR: isize = DENSE_LIMIT as isize + 3;
+
+trait Engine {
+    fn new(program: Vec<isize>, input: Vec<isize>) -> Self;
+    fn step(&mut self) -> Result<Status, Error>;
+    fn ip(&self) -> usize;
+    fn rel_base(&self) -> i64;
+    fn read(&self, address: usize) -> i64;
+    fn input(&self) -> Vec<i64>;
+    fn output(&self) -> Vec<i64>;
+}
+
+impl Engine for Machine {
+    fn new(program: Vec<isize>, input: Vec<isize>) -> Self {
+        Machine::with_input(program, input)
+    }
+
+    fn step(&mut self) -> Result<Status, Error> {
+        Machine::step(self)
+    }
+
+    fn ip(&self) -> usize {
+        Machine::ip(self)
+    }
+
+    fn rel_base(&self) -> i64 {
+        Machine::rel_base(self) as i64
+    }
+
+    fn read(&self, address: usize) -> i64 {
+        Machine::read(self, address) as i64
+    }
+
+    fn input(&self) -> Vec<i64> {
+        Machine::input(self)
+            .iter()
+            .map(|&value| value as i64)
+            .collect()
+    }
+
+    fn output(&self) -> Vec<i64> {
+        Machine::output(self)
+            .iter()
+            .map(|&value| value as i64)
+            .collect()
+    }
+}
+
+impl Engine for FastMachine {
+    fn new(program: Vec<isize>, input: Vec<isize>) -> Self {
+        FastMachine::from(&Machine::with_input(program, input))
+    }
+
+    fn step(&mut self) -> Result<Status, Error> {
+        FastMachine::step(self)
+    }
+
+    fn ip(&self) -> usize {
+        FastMachine::ip(self)
+    }
+
+    fn rel_base(&self) -> i64 {
+        FastMachine::rel_base(self)
+    }
+
+    fn read(&self, address: usize) -> i64 {
+        FastMachine::read(self, address)
+    }
+
+    fn input(&self) -> Vec<i64> {
+        FastMachine::input(self).iter().copied().collect()
+    }
+
+    fn output(&self) -> Vec<i64> {
+        FastMachine::output(self).iter().copied().collect()
+    }
+}
+
+/// Everything about a run that engines have to agree on.
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    statuses: Vec<Result<Status, Error>>,
+    ip: usize,
+    rel_base: i64,
+    /// Every address a program can reach without computing it
+    memory: Vec<i64>,
+    input: Vec<i64>,
+    output: Vec<i64>,
+}
+
+fn run<E: Engine>(program: &[isize], input: &[isize]) -> Outcome {
+    let mut engine = E::new(program.to_vec(), input.to_vec());
+    let mut statuses = Vec::new();
+    while statuses.len() < STEPS {
+        let status = engine.step();
+        statuses.push(status);
+        if status != Ok(Status::Running) {
+            break;
+        }
+    }
+    let addresses = (0..3 * SIZE as usize).chain(FAR as usize..FAR as usize + 2 * SIZE as usize);
+    Outcome {
+        statuses,
+        ip: engine.ip(),
+        rel_base: engine.rel_base(),
+        memory: addresses.map(|address| engine.read(address)).collect(),
+        input: engine.input(),
+        output: engine.output(),
+    }
+}
+
+fn param() -> impl Strategy<Value = (ParamMode, isize)> {
+    prop_oneof![
+        4 => (0..SIZE + 8).prop_map(|address| (ParamMode::Position, address)),
+        3 => (-4..SIZE).prop_map(|offset| (ParamMode::Relative, offset)),
+        4 => (-4..SIZE + 4).prop_map(|value| (ParamMode::Immediate, value)),
+        1 => Just((ParamMode::Position, FAR)),
+        1 => (-SIZE..0).prop_map(|address| (ParamMode::Position, address)),
+        1 => any::<isize>().prop_map(|value| (ParamMode::Immediate, value)),
+    ]
+}
+
+/// An instruction with its parameters, or now and then a lone value.
+fn cells() -> impl Strategy<Value = Vec<isize>> {
+    let instruction =
+        (select(OpCode::ALL.to_vec()), [param(), param(), param()]).prop_map(|(opcode, params)| {
+            let instruction = Instruction {
+                opcode,
+                modes: params.map(|(mode, _)| mode),
+            };
+            let params = params[..opcode.params()].iter().map(|&(_, value)| value);
+            std::iter::once(instruction.encode())
+                .chain(params)
+                .collect()
+        });
+    prop_oneof![
+        9 => instruction,
+        1 => (-2..30_000_isize).prop_map(|value| vec![value]),
+    ]
+}
+
+fn program() -> impl Strategy<Value = Vec<isize>> {
+    vec(cells(), 1..SIZE as usize / 3).prop_map(|cells| cells.concat())
+}
+
+fn input() -> impl Strategy<Value = Vec<isize>> {
+    vec(-3..SIZE, 0..4)
+}
+
+proptest! {
+    #[test]
+    fn test_fast_machine(program in program(), input in input()) {
+        prop_assert_eq!(run::<FastMachine>(&program, &input), run::<Machine>(&program, &input));
+    }
+}
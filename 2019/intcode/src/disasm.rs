@@ -0,0 +1,388 @@
+//! Turning a program back into something readable.
+//!
+//! [`disassemble`] follows the program from address 0 the way the machine
+//! would, so only cells that can actually run are shown as instructions and
+//! everything else is data. Jumps to a fixed address are followed and get a
+//! label. Jumps to a computed address can't be followed, with one exception:
+//! the usual way to call a function is to push the return address with
+//! `add #ret, #0, [rb+n]` and then jump, so an address pushed like that is
+//! followed when the instruction just before it is a jump that's always
+//! taken.
+//!
+//! Instructions that write into code are flagged, as is the code they write
+//! into. The listing only shows what the program looked like before it ran.
+
+use crate::instruction::{Instruction, OpCode, ParamMode};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// A parameter with its mode, shown as `[addr]`, `#imm` or `[rb+off]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Param {
+    pub mode: ParamMode,
+    pub value: isize,
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            ParamMode::Position => write!(f, "[{}]", self.value),
+            ParamMode::Immediate => write!(f, "#{}", self.value),
+            ParamMode::Relative => write!(f, "[rb{:+}]", self.value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+    Code {
+        address: usize,
+        opcode: OpCode,
+        params: Vec<Param>,
+        /// Index of the parameter that is the address of a label
+        label: Option<usize>,
+    },
+    Data {
+        address: usize,
+        values: Vec<isize>,
+    },
+}
+
+impl Item {
+    #[must_use]
+    pub fn address(&self) -> usize {
+        match self {
+            Item::Code { address, .. } | Item::Data { address, .. } => *address,
+        }
+    }
+
+    /// Number of memory cells the item covers.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        match self {
+            Item::Code { params, .. } => 1 + params.len(),
+            Item::Data { values, .. } => values.len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Listing {
+    pub items: Vec<Item>,
+    /// Addresses of code that gets jumped to
+    pub labels: BTreeSet<usize>,
+    /// Instructions that write into code, with the address they write to
+    pub self_modifying: BTreeMap<usize, usize>,
+}
+
+/// How labels are named in listings.
+#[must_use]
+pub fn label(address: usize) -> String {
+    format!("L{address}")
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Item::Code {
+                address,
+                opcode,
+                params,
+                label: label_param,
+            } => {
+                let operands = params
+                    .iter()
+                    .enumerate()
+                    .map(|(index, param)| match label_param {
+                        Some(label_param) if *label_param == index => {
+                            format!("#{}", label(param.value as usize))
+                        }
+                        _ => param.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let line = format!("{address:>6}  {:<4} {operands}", opcode.mnemonic());
+                f.pad(line.trim_end())
+            }
+            Item::Data { address, values } => {
+                let values = values
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                f.pad(&format!("{address:>6}  data {values}"))
+            }
+        }
+    }
+}
+
+impl Listing {
+    /// The listing with `note(item)` right-aligned in a margin `width` wide in
+    /// front of every item.
+    #[must_use]
+    pub fn annotated(&self, width: usize, note: impl Fn(&Item) -> String) -> String {
+        let mut text = String::new();
+        self.write(&mut text, width, note)
+            .expect("writing to a String doesn't fail");
+        text
+    }
+
+    fn write(
+        &self,
+        f: &mut impl fmt::Write,
+        width: usize,
+        note: impl Fn(&Item) -> String,
+    ) -> fmt::Result {
+        for item in &self.items {
+            let address = item.address();
+            if self.labels.contains(&address) {
+                writeln!(f, "{:width$}{}:", "", label(address))?;
+            }
+            write!(f, "{:>width$}", note(item))?;
+            let mut comments = Vec::new();
+            if let Some(target) = self.self_modifying.get(&address) {
+                comments.push(format!("writes code at {target}"));
+            }
+            let writers = self
+                .self_modifying
+                .iter()
+                .filter(|(_, target)| (address..address + item.size()).contains(target))
+                .map(|(writer, _)| writer.to_string())
+                .collect::<Vec<_>>();
+            if !writers.is_empty() {
+                comments.push(format!("modified by {}", writers.join(", ")));
+            }
+            if comments.is_empty() {
+                writeln!(f, "{item}")?;
+            } else {
+                writeln!(f, "{item:<40} ; {}", comments.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0, |_| String::new())
+    }
+}
+
+/// The instruction at `address` on its own, without following anything.
+#[must_use]
+pub fn decode_item(memory: &[isize], address: usize) -> Option<Item> {
+    let (instruction, params) = decode_at(memory, address)?;
+    Some(Item::Code {
+        address,
+        opcode: instruction.opcode,
+        params,
+        label: None,
+    })
+}
+
+/// Most values on a single line of data.
+const DATA_PER_LINE: usize = 8;
+
+/// Splits `program` into code and data.
+#[must_use]
+pub fn disassemble(program: &[isize]) -> Listing {
+    let mut instructions = BTreeMap::new();
+    // Which instruction every cell belongs to
+    let mut owner = vec![None; program.len()];
+    let mut labels = BTreeSet::new();
+    let mut todo = vec![0];
+    while let Some(address) = todo.pop() {
+        if instructions.contains_key(&address) {
+            continue;
+        }
+        let Some((instruction, params)) = decode_at(program, address) else {
+            continue;
+        };
+        let cells = address..address + instruction.size();
+        if cells.clone().any(|cell| owner[cell].is_some()) {
+            continue;
+        }
+        for cell in cells {
+            owner[cell] = Some(address);
+        }
+        let next = address + instruction.size();
+        let mut target = None;
+        match instruction.opcode {
+            OpCode::Exit => (),
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let jumps = always_jumps(instruction.opcode, params[0]);
+                if jumps != Some(false) && params[1].mode == ParamMode::Immediate {
+                    target = Some(params[1].value);
+                }
+                if jumps != Some(true) {
+                    todo.push(next);
+                }
+            }
+            _ => {
+                target = pushed_return(program, instruction.opcode, &params);
+                todo.push(next);
+            }
+        }
+        if let Some(Ok(target)) = target.map(usize::try_from) {
+            labels.insert(target);
+            todo.push(target);
+        }
+        instructions.insert(address, (instruction, params));
+    }
+    labels.retain(|address| instructions.contains_key(address));
+
+    let self_modifying = instructions
+        .iter()
+        .filter_map(|(&address, (instruction, params))| {
+            let param = params[instruction.opcode.written_param()?];
+            if param.mode != ParamMode::Position {
+                return None;
+            }
+            let target = usize::try_from(param.value).ok()?;
+            owner.get(target)?.map(|_| (address, target))
+        })
+        .collect();
+
+    let mut items = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        if let Some((instruction, params)) = instructions.remove(&address) {
+            let label = match instruction.opcode {
+                OpCode::JumpIfTrue | OpCode::JumpIfFalse
+                    if params[1].mode == ParamMode::Immediate
+                        && labels.contains(&(params[1].value as usize)) =>
+                {
+                    Some(1)
+                }
+                OpCode::Add | OpCode::Multiply
+                    if pushed_return(program, instruction.opcode, &params)
+                        .is_some_and(|target| labels.contains(&(target as usize))) =>
+                {
+                    Some(usize::from(params[0].value == identity(instruction.opcode)))
+                }
+                _ => None,
+            };
+            address += instruction.size();
+            items.push(Item::Code {
+                address: address - instruction.size(),
+                opcode: instruction.opcode,
+                params,
+                label,
+            });
+        } else {
+            let end = (address..program.len())
+                .find(|&cell| owner[cell].is_some())
+                .unwrap_or(program.len())
+                .min(address + DATA_PER_LINE);
+            items.push(Item::Data {
+                address,
+                values: program[address..end].to_vec(),
+            });
+            address = end;
+        }
+    }
+
+    Listing {
+        items,
+        labels,
+        self_modifying,
+    }
+}
+
+/// The instruction at `address` and its parameters, if it is one and fits.
+fn decode_at(program: &[isize], address: usize) -> Option<(Instruction, Vec<Param>)> {
+    let instruction = Instruction::decode(*program.get(address)?)?;
+    let values = program.get(address + 1..address + instruction.size())?;
+    let params = values
+        .iter()
+        .zip(instruction.modes)
+        .map(|(&value, mode)| Param { mode, value })
+        .collect();
+    Some((instruction, params))
+}
+
+/// Whether a conditional jump always or never jumps, if that's known up front.
+fn always_jumps(opcode: OpCode, condition: Param) -> Option<bool> {
+    (condition.mode == ParamMode::Immediate)
+        .then(|| (condition.value != 0) == (opcode == OpCode::JumpIfTrue))
+}
+
+/// The value that leaves the other operand unchanged.
+fn identity(opcode: OpCode) -> isize {
+    isize::from(opcode == OpCode::Multiply)
+}
+
+/// The return address if the instruction pushes one for a function call.
+fn pushed_return(program: &[isize], opcode: OpCode, params: &[Param]) -> Option<isize> {
+    if !matches!(opcode, OpCode::Add | OpCode::Multiply)
+        || params[2].mode != ParamMode::Relative
+        || params[..2]
+            .iter()
+            .any(|param| param.mode != ParamMode::Immediate)
+    {
+        return None;
+    }
+    let target = match (params[0].value, params[1].value) {
+        (target, other) | (other, target) if other == identity(opcode) => target,
+        _ => return None,
+    };
+    // The call itself has to be right before the address it returns to
+    let call = usize::try_from(target).ok()?.checked_sub(3)?;
+    match decode_at(program, call)? {
+        (instruction, params)
+            if instruction.size() == 3
+                && always_jumps(instruction.opcode, params[0]) == Some(true) =>
+        {
+            Some(target)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&[3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9] => "     0  in   [12]
+     2  jz   [12], [15]
+     5  add  [13], [14], [13]
+     9  out  [13]
+    11  hlt
+    12  data -1, 0, 1, 9
+"; "day 5 jump example")]
+    #[test_case(&[21101,7,0,1,1105,1,10,104,5,99,2106,0,1,42] => "     0  add  #L7, #0, [rb+1]
+     4  jnz  #1, #L10
+L7:
+     7  out  #5
+     9  hlt
+L10:
+    10  jz   #0, [rb+1]
+    13  data 42
+"; "function call")]
+    #[test_case(&[1,9,10,3,2,3,11,0,99,30,40,50] => "     0  add  [9], [10], [3]              ; writes code at 3, modified by 0, 4
+     4  mul  [3], [11], [0]              ; writes code at 0
+     8  hlt
+     9  data 30, 40, 50
+"; "day 2 example")]
+    #[test_case(&[1105,1,7,1,2,3,4,2,12,12,13,99,5,0] => "     0  jnz  #1, #L7
+     3  data 1, 2, 3, 4
+L7:
+     7  mul  [12], [12], [13]
+    11  hlt
+    12  data 5, 0
+"; "data between code")]
+    fn test_disassemble(program: &[isize]) -> String {
+        disassemble(program).to_string()
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // Reads over the condition of the jump after it
+        let listing = disassemble(&[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1]);
+        assert_eq!(listing.self_modifying, BTreeMap::from([(0, 3)]));
+        assert_eq!(listing.labels, BTreeSet::from([9]));
+    }
+}
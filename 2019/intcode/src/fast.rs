@@ -0,0 +1,655 @@
+//! A faster engine for programs that run long or get run a lot.
+//!
+//! [`FastMachine`] behaves exactly like [`Machine`], errors included, but
+//! doesn't split instructions into opcode and parameter modes every time they
+//! run. Every cell of the program is decoded up front into a handler made for
+//! that opcode and those modes, and the decodings are shared by all clones of
+//! the machine, so starting a fresh copy of a program thousands of times costs
+//! nothing extra. A decoding only counts while its cell still holds the value
+//! it was decoded from, so code the program writes over is decoded again, the
+//! slow way. So is anything unusual, like memory that isn't there yet or an
+//! instruction that fails.
+//!
+//! Memory is `i64`. Addresses below [`DENSE_LIMIT`], or inside the program,
+//! are stored in a plain vector. Anything above that goes in a hash map, so a
+//! program writing far away doesn't allocate everything in between.
+
+use crate::{
+    instruction::{Instruction, OpCode, ParamMode},
+    machine::{Error, Event, Machine, Status},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+/// Executes the instruction at the instruction pointer if it only touches
+/// dense memory, returning `None`, having changed nothing, for anything else,
+/// including every error. There's one for every opcode and combination of
+/// parameter modes, so none of them look at the modes while running.
+type Handler = fn(&mut FastMachine) -> Option<Status>;
+
+#[derive(Clone, Copy, Debug)]
+struct Decoded {
+    /// The value the instruction was decoded from
+    value: i64,
+    instruction: Instruction,
+    handler: Handler,
+}
+
+pub use crate::machine::DENSE_LIMIT;
+
+#[derive(Clone, Debug, Default)]
+pub struct FastMachine {
+    memory: Vec<i64>,
+    /// Every instruction in the program as loaded, with the value it was
+    /// decoded from
+    decoded: Arc<[Option<Decoded>]>,
+    /// Memory from [`DENSE_LIMIT`] up that has been written to
+    sparse: HashMap<usize, i64>,
+    ip: usize,
+    rel_base: i64,
+    input: VecDeque<i64>,
+    output: VecDeque<i64>,
+}
+
+impl FastMachine {
+    #[must_use]
+    pub fn new(program: Vec<i64>) -> Self {
+        Self {
+            decoded: program
+                .iter()
+                .map(|&value| {
+                    let instruction = decode(value)?;
+                    Some(Decoded {
+                        value,
+                        instruction,
+                        handler: handler(instruction),
+                    })
+                })
+                .collect(),
+            memory: program,
+            ..Default::default()
+        }
+    }
+
+    /// A new machine with `input` already queued.
+    #[must_use]
+    pub fn with_input(program: Vec<i64>, input: impl IntoIterator<Item = i64>) -> Self {
+        let mut machine = Self::new(program);
+        machine.input.extend(input);
+        machine
+    }
+
+    /// The densely stored part of memory, which starts with the program.
+    #[must_use]
+    pub fn memory(&self) -> &[i64] {
+        &self.memory
+    }
+
+    #[must_use]
+    pub fn read(&self, address: usize) -> i64 {
+        match self.memory.get(address) {
+            Some(&value) => value,
+            None => self.read_outside(address),
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: i64) {
+        match self.memory.get_mut(address) {
+            Some(cell) => *cell = value,
+            None => self.write_outside(address, value),
+        }
+    }
+
+    /// Address of the next instruction.
+    #[must_use]
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    #[must_use]
+    pub fn rel_base(&self) -> i64 {
+        self.rel_base
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    pub fn extend_input(&mut self, values: impl IntoIterator<Item = i64>) {
+        self.input.extend(values);
+    }
+
+    /// Values queued for reads, oldest first.
+    #[must_use]
+    pub fn input(&self) -> &VecDeque<i64> {
+        &self.input
+    }
+
+    /// Values written but not yet taken, oldest first.
+    #[must_use]
+    pub fn output(&self) -> &VecDeque<i64> {
+        &self.output
+    }
+
+    pub fn pop_output(&mut self) -> Option<i64> {
+        self.output.pop_front()
+    }
+
+    /// Takes everything from the output queue.
+    pub fn take_output(&mut self) -> Vec<i64> {
+        self.output.drain(..).collect()
+    }
+
+    /// Decodes the instruction at the instruction pointer, unless it was
+    /// decoded up front and hasn't changed since.
+    ///
+    /// # Errors
+    /// If the value there isn't a valid instruction.
+    pub fn decode(&self) -> Result<Instruction, Error> {
+        let ip = self.ip;
+        let value = self.read(ip);
+        match self.decoded.get(ip) {
+            Some(Some(decoded)) if decoded.value == value => Ok(decoded.instruction),
+            _ => decode(value).ok_or(Error::InvalidInstruction {
+                ip,
+                value: value as isize,
+            }),
+        }
+    }
+
+    /// Executes one instruction, see [`Machine::step`].
+    ///
+    /// # Errors
+    /// If the instruction is invalid, or does something invalid. The machine
+    /// is left as it was before the instruction.
+    #[inline(always)]
+    pub fn step(&mut self) -> Result<Status, Error> {
+        match self.step_dense() {
+            Some(status) => Ok(status),
+            None => self.step_slow(),
+        }
+    }
+
+    /// Executes one instruction the way [`Machine`] does, for whatever the
+    /// handlers leave alone.
+    fn step_slow(&mut self) -> Result<Status, Error> {
+        let instruction = self.decode()?;
+        let ip = self.ip;
+        match instruction.opcode {
+            OpCode::Add => {
+                self.calculate(instruction, i64::checked_add)?;
+            }
+            OpCode::Multiply => {
+                self.calculate(instruction, i64::checked_mul)?;
+            }
+            OpCode::LessThan => {
+                self.calculate(instruction, |a, b| Some(i64::from(a < b)))?;
+            }
+            OpCode::EqualTo => {
+                self.calculate(instruction, |a, b| Some(i64::from(a == b)))?;
+            }
+            OpCode::Read => {
+                let address = self.address(instruction, 0)?;
+                let Some(value) = self.input.pop_front() else {
+                    return Ok(Status::NeedInput);
+                };
+                self.write(address, value);
+            }
+            OpCode::Write => {
+                let value = self.load(instruction, 0)?;
+                self.output.push_back(value);
+            }
+            OpCode::AdjustBase => {
+                let value = self.load(instruction, 0)?;
+                self.rel_base = self
+                    .rel_base
+                    .checked_add(value)
+                    .filter(|&base| i64_to_isize(base).is_some())
+                    .ok_or(Error::Overflow { ip })?;
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let condition = self.load(instruction, 0)?;
+                let target = self.load(instruction, 1)?;
+                if (condition != 0) == (instruction.opcode == OpCode::JumpIfTrue) {
+                    self.ip = usize::try_from(target).map_err(|_| Error::NegativeAddress {
+                        ip,
+                        address: target as isize,
+                    })?;
+                    return Ok(Status::Running);
+                }
+            }
+            OpCode::Exit => return Ok(Status::Halted),
+        }
+        self.ip += instruction.size();
+        Ok(Status::Running)
+    }
+
+    /// Runs until the program wants input that isn't there, or exits, see
+    /// [`Machine::run_until_input`].
+    ///
+    /// # Errors
+    /// If the program hits an invalid instruction, see [`FastMachine::step`].
+    pub fn run_until_input(&mut self) -> Result<Status, Error> {
+        loop {
+            match self.step()? {
+                Status::Running => {}
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Runs until the program writes a value, see
+    /// [`Machine::run_until_output`]. The value is passed on as an `isize` to
+    /// share [`Event`] with [`Machine`].
+    ///
+    /// # Errors
+    /// If the program hits an invalid instruction, see [`FastMachine::step`].
+    pub fn run_until_output(&mut self) -> Result<Event, Error> {
+        loop {
+            if let Some(value) = self.output.pop_front() {
+                return Ok(Event::Output(value as isize));
+            }
+            match self.step()? {
+                Status::Running => {}
+                Status::NeedInput => return Ok(Event::NeedInput),
+                Status::Halted => return Ok(Event::Halted),
+            }
+        }
+    }
+
+    /// Executes the instruction at the instruction pointer with its handler
+    /// if it was decoded up front, see [`Handler`].
+    #[inline(always)]
+    fn step_dense(&mut self) -> Option<Status> {
+        let decoded = (*self.decoded.get(self.ip)?)?;
+        if *self.memory.get(self.ip)? != decoded.value {
+            return None;
+        }
+        (decoded.handler)(self)
+    }
+
+    /// The address parameter `index` points to, if it's in dense memory.
+    #[inline(always)]
+    fn dense_address<const MODES: usize>(&self, index: usize) -> Option<usize> {
+        let param = *self.memory.get(self.ip + 1 + index)?;
+        let address = match mode(MODES, index) {
+            ParamMode::Position => param,
+            ParamMode::Relative => param.checked_add(self.rel_base)?,
+            ParamMode::Immediate => return None,
+        };
+        usize::try_from(address)
+            .ok()
+            .filter(|&address| address < self.memory.len())
+    }
+
+    /// The value of parameter `index`, if it's in dense memory.
+    #[inline(always)]
+    fn dense_load<const MODES: usize>(&self, index: usize) -> Option<i64> {
+        match mode(MODES, index) {
+            ParamMode::Immediate => self.memory.get(self.ip + 1 + index).copied(),
+            ParamMode::Position | ParamMode::Relative => {
+                Some(self.memory[self.dense_address::<MODES>(index)?])
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn dense_calculate<const MODES: usize>(
+        &mut self,
+        operation: impl Fn(i64, i64) -> Option<i64>,
+    ) -> Option<Status> {
+        let result = operation(self.dense_load::<MODES>(0)?, self.dense_load::<MODES>(1)?)?;
+        let address = self.dense_address::<MODES>(2)?;
+        self.memory[address] = result;
+        self.ip += 4;
+        Some(Status::Running)
+    }
+
+    #[inline(always)]
+    fn dense_jump<const MODES: usize>(&mut self, if_true: bool) -> Option<Status> {
+        let condition = self.dense_load::<MODES>(0)?;
+        let target = self.dense_load::<MODES>(1)?;
+        self.ip = if (condition != 0) == if_true {
+            usize::try_from(target).ok()?
+        } else {
+            self.ip + 3
+        };
+        Some(Status::Running)
+    }
+
+    /// Reads past the end of the dense memory, kept out of the way of the
+    /// common case.
+    #[cold]
+    fn read_outside(&self, address: usize) -> i64 {
+        if address < DENSE_LIMIT {
+            0
+        } else {
+            self.sparse.get(&address).copied().unwrap_or(0)
+        }
+    }
+
+    #[cold]
+    fn write_outside(&mut self, address: usize, value: i64) {
+        if address < DENSE_LIMIT {
+            // Programs tend to keep going where they started writing
+            let size = (address + 1).next_power_of_two().min(DENSE_LIMIT);
+            self.memory.resize(size, 0);
+            self.memory[address] = value;
+        } else {
+            self.sparse.insert(address, value);
+        }
+    }
+
+    fn calculate(
+        &mut self,
+        instruction: Instruction,
+        operation: impl Fn(i64, i64) -> Option<i64>,
+    ) -> Result<(), Error> {
+        let a = self.load(instruction, 0)?;
+        let b = self.load(instruction, 1)?;
+        let address = self.address(instruction, 2)?;
+        let result = operation(a, b).ok_or(Error::Overflow { ip: self.ip })?;
+        self.write(address, result);
+        Ok(())
+    }
+
+    fn load(&self, instruction: Instruction, index: usize) -> Result<i64, Error> {
+        match instruction.modes[index] {
+            ParamMode::Immediate => Ok(self.read(self.ip + 1 + index)),
+            ParamMode::Position | ParamMode::Relative => {
+                Ok(self.read(self.address(instruction, index)?))
+            }
+        }
+    }
+
+    fn address(&self, instruction: Instruction, index: usize) -> Result<usize, Error> {
+        let ip = self.ip;
+        let param = self.read(ip + 1 + index);
+        let address = match instruction.modes[index] {
+            ParamMode::Position => param,
+            ParamMode::Relative => param
+                .checked_add(self.rel_base)
+                .ok_or(Error::Overflow { ip })?,
+            ParamMode::Immediate => return Err(Error::WriteToImmediate { ip }),
+        };
+        usize::try_from(address).map_err(|_| Error::NegativeAddress {
+            ip,
+            address: address as isize,
+        })
+    }
+}
+
+/// Where `isize` is smaller than `i64`, values that don't fit can't be
+/// instructions or relative bases, just like in [`Machine`].
+fn i64_to_isize(value: i64) -> Option<isize> {
+    isize::try_from(value).ok()
+}
+
+fn decode(value: i64) -> Option<Instruction> {
+    i64_to_isize(value).and_then(Instruction::decode)
+}
+
+/// The mode of parameter `index` in `modes`, which holds one base 3 digit
+/// per parameter, the first one lowest.
+const fn mode(modes: usize, index: usize) -> ParamMode {
+    match modes / 3_usize.pow(index as u32) % 3 {
+        0 => ParamMode::Position,
+        1 => ParamMode::Immediate,
+        _ => ParamMode::Relative,
+    }
+}
+
+fn add<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    machine.dense_calculate::<MODES>(i64::checked_add)
+}
+
+fn multiply<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    machine.dense_calculate::<MODES>(i64::checked_mul)
+}
+
+fn less_than<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    machine.dense_calculate::<MODES>(|a, b| Some(i64::from(a < b)))
+}
+
+fn equal_to<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    machine.dense_calculate::<MODES>(|a, b| Some(i64::from(a == b)))
+}
+
+fn read<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    let address = machine.dense_address::<MODES>(0)?;
+    let Some(value) = machine.input.pop_front() else {
+        return Some(Status::NeedInput);
+    };
+    machine.memory[address] = value;
+    machine.ip += 2;
+    Some(Status::Running)
+}
+
+fn write<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    let value = machine.dense_load::<MODES>(0)?;
+    machine.output.push_back(value);
+    machine.ip += 2;
+    Some(Status::Running)
+}
+
+fn adjust_base<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    machine.rel_base = machine
+        .rel_base
+        .checked_add(machine.dense_load::<MODES>(0)?)
+        .filter(|&base| i64_to_isize(base).is_some())?;
+    machine.ip += 2;
+    Some(Status::Running)
+}
+
+fn jump_if_true<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    machine.dense_jump::<MODES>(true)
+}
+
+fn jump_if_false<const MODES: usize>(machine: &mut FastMachine) -> Option<Status> {
+    machine.dense_jump::<MODES>(false)
+}
+
+fn exit<const MODES: usize>(_: &mut FastMachine) -> Option<Status> {
+    Some(Status::Halted)
+}
+
+/// The handlers for every combination of parameter modes, in the order of
+/// [`mode`].
+macro_rules! for_modes {
+    ($handler:ident) => {
+        [
+            $handler::<0>,
+            $handler::<1>,
+            $handler::<2>,
+            $handler::<3>,
+            $handler::<4>,
+            $handler::<5>,
+            $handler::<6>,
+            $handler::<7>,
+            $handler::<8>,
+            $handler::<9>,
+            $handler::<10>,
+            $handler::<11>,
+            $handler::<12>,
+            $handler::<13>,
+            $handler::<14>,
+            $handler::<15>,
+            $handler::<16>,
+            $handler::<17>,
+            $handler::<18>,
+            $handler::<19>,
+            $handler::<20>,
+            $handler::<21>,
+            $handler::<22>,
+            $handler::<23>,
+            $handler::<24>,
+            $handler::<25>,
+            $handler::<26>,
+        ]
+    };
+}
+
+fn handler(instruction: Instruction) -> Handler {
+    let handlers: [Handler; 27] = match instruction.opcode {
+        OpCode::Add => for_modes!(add),
+        OpCode::Multiply => for_modes!(multiply),
+        OpCode::Read => for_modes!(read),
+        OpCode::Write => for_modes!(write),
+        OpCode::JumpIfTrue => for_modes!(jump_if_true),
+        OpCode::JumpIfFalse => for_modes!(jump_if_false),
+        OpCode::LessThan => for_modes!(less_than),
+        OpCode::EqualTo => for_modes!(equal_to),
+        OpCode::AdjustBase => for_modes!(adjust_base),
+        OpCode::Exit => for_modes!(exit),
+    };
+    let modes = instruction
+        .modes
+        .iter()
+        .rev()
+        .fold(0, |modes, &mode| modes * 3 + mode as usize);
+    handlers[modes]
+}
+
+impl From<&Machine> for FastMachine {
+    /// A fast machine in the same state as `machine`.
+    fn from(machine: &Machine) -> Self {
+        let to_i64 = |values: &VecDeque<isize>| values.iter().map(|&value| value as i64).collect();
+        let mut fast = Self::new(machine.memory().iter().map(|&value| value as i64).collect());
+        fast.ip = machine.ip();
+        fast.rel_base = machine.rel_base() as i64;
+        fast.input = to_i64(machine.input());
+        fast.output = to_i64(machine.output());
+        for (&address, &value) in machine.sparse_memory() {
+            fast.write(address, value as i64);
+        }
+        fast
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test as std_test;
+    use super::*;
+    use crate::{asm::assemble, parse_program};
+    use std::fs;
+    use std_test::{black_box, Bencher};
+    use test_case::test_case;
+
+    fn fast_machine(program: &[isize]) -> FastMachine {
+        FastMachine::from(&Machine::new(program.to_vec()))
+    }
+
+    #[test_case(&[109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99], &[]; "day 9 quine")]
+    #[test_case(&[1102,34915192,34915192,7,4,7,99,0], &[]; "day 9 large multiply")]
+    #[test_case(&[3,9,8,9,10,9,4,9,99,-1,8], &[8]; "day 5 equal")]
+    #[test_case(&[3,3,1105,-1,9,1101,0,0,12,4,12,99,1], &[0]; "day 5 self-modifying jump")]
+    #[test_case(&[1,1,1,4,99,5,6,0,99], &[]; "day 2 overwrites exit")]
+    #[test_case(&[1105,1,4,99,301], &[]; "invalid mode")]
+    #[test_case(&[109,-5,204,1,99], &[]; "negative relative address")]
+    #[test_case(&[1102,isize::MAX,2,0,99], &[]; "overflow")]
+    #[test_case(&[109,isize::MAX,109,1,99], &[]; "overflowing relative base")]
+    #[test_case(&[1101,0,0,1000000000000000000,99], &[]; "far away write")]
+    fn test_same_as_machine(program: &[isize], input: &[isize]) {
+        let mut machine = Machine::with_input(program.to_vec(), input.iter().copied());
+        let mut fast = FastMachine::from(&machine);
+        let status = machine.run_until_input();
+        assert_eq!(fast.run_until_input(), status);
+        assert_eq!(fast.ip(), machine.ip());
+        assert_eq!(fast.rel_base(), machine.rel_base() as i64);
+        // Fast memory grows in bigger steps
+        let size = machine.memory().len().max(fast.memory().len());
+        assert!((0..size).all(|address| machine.read(address) as i64 == fast.read(address)));
+        let mut sparse = machine.sparse_memory().iter();
+        assert!(sparse.all(|(&address, &value)| fast.read(address) == value as i64));
+        let output = machine.output().iter().map(|&value| value as i64);
+        assert!(output.eq(fast.output().iter().copied()));
+    }
+
+    #[test]
+    fn test_rewritten_instruction() {
+        // Runs the instruction at `patch` twice, turning it from an `out` into
+        // an `in` in between
+        const PATCHED: &str = "
+            patch:  out  [x]
+                    jz   [again], #end
+                    add  [patch], #-1, [patch]
+                    mul  [again], #0, [again]
+                    jz   #0, #patch
+            end:    hlt
+            again:  data 1
+            x:      data 7";
+        let mut machine = fast_machine(&assemble(PATCHED).unwrap());
+        assert_eq!(machine.run_until_input(), Ok(Status::NeedInput));
+        assert_eq!(machine.take_output(), [7]);
+        machine.push_input(42);
+        assert_eq!(machine.run_until_input(), Ok(Status::Halted));
+        assert_eq!(machine.read(18), 42);
+    }
+
+    #[test]
+    fn test_sparse_memory() {
+        let far = 1 << 40;
+        let mut machine = fast_machine(&[1101, 3, 4, far, 4, far, 99]);
+        assert_eq!(machine.run_until_output(), Ok(Event::Output(7)));
+        assert_eq!(machine.memory().len(), 7);
+        assert_eq!(machine.read(far as usize), 7);
+        machine.write(DENSE_LIMIT - 1, 1);
+        assert_eq!(machine.memory().len(), DENSE_LIMIT);
+    }
+
+    fn read_input(day: usize) -> Vec<isize> {
+        parse_program(&fs::read_to_string(format!("../day{day}/input.txt")).unwrap()).unwrap()
+    }
+
+    /// Day 9 part 2 runs a few hundred thousand instructions.
+    fn boost(program: &[isize]) -> (Machine, FastMachine) {
+        let machine = Machine::with_input(program.to_vec(), [2]);
+        let fast = FastMachine::from(&machine);
+        (machine, fast)
+    }
+
+    #[bench]
+    fn bench_boost_machine(b: &mut Bencher) {
+        let (machine, _) = boost(&read_input(9));
+        b.iter(|| black_box(machine.clone()).run_until_input());
+    }
+
+    #[bench]
+    fn bench_boost_fast(b: &mut Bencher) {
+        let (_, fast) = boost(&read_input(9));
+        b.iter(|| black_box(fast.clone()).run_until_input());
+    }
+
+    /// Day 19 part 1 starts a fresh machine for each of 2500 points.
+    #[bench]
+    fn bench_beam_machine(b: &mut Bencher) {
+        let machine = Machine::new(read_input(19));
+        b.iter(|| {
+            (0..50)
+                .flat_map(|x| (0..50).map(move |y| [x, y]))
+                .filter(|&point| {
+                    let mut machine = black_box(&machine).clone();
+                    machine.extend_input(point);
+                    machine.run_until_output() == Ok(Event::Output(1))
+                })
+                .count()
+        });
+    }
+
+    #[bench]
+    fn bench_beam_fast(b: &mut Bencher) {
+        let fast = fast_machine(&read_input(19));
+        b.iter(|| {
+            (0..50)
+                .flat_map(|x| (0..50).map(move |y| [x, y]))
+                .filter(|&point| {
+                    let mut fast = black_box(&fast).clone();
+                    fast.extend_input(point);
+                    fast.run_until_output() == Ok(Event::Output(1))
+                })
+                .count()
+        });
+    }
+}
@@ -0,0 +1,176 @@
+//! Decoding instructions. The last two digits of an instruction are the
+//! opcode, every digit above that is the mode of one parameter, starting with
+//! the first parameter in the hundreds.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    Add = 1,
+    Multiply = 2,
+    Read = 3,
+    Write = 4,
+    JumpIfTrue = 5,
+    JumpIfFalse = 6,
+    LessThan = 7,
+    EqualTo = 8,
+    AdjustBase = 9,
+    Exit = 99,
+}
+
+impl OpCode {
+    pub const ALL: [OpCode; 10] = [
+        OpCode::Add,
+        OpCode::Multiply,
+        OpCode::Read,
+        OpCode::Write,
+        OpCode::JumpIfTrue,
+        OpCode::JumpIfFalse,
+        OpCode::LessThan,
+        OpCode::EqualTo,
+        OpCode::AdjustBase,
+        OpCode::Exit,
+    ];
+
+    #[must_use]
+    pub fn from_value(value: isize) -> Option<Self> {
+        OpCode::ALL
+            .into_iter()
+            .find(|&opcode| opcode as isize == value)
+    }
+
+    /// How many parameters follow the opcode.
+    #[must_use]
+    pub const fn params(self) -> usize {
+        match self {
+            OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::EqualTo => 3,
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => 2,
+            OpCode::Read | OpCode::Write | OpCode::AdjustBase => 1,
+            OpCode::Exit => 0,
+        }
+    }
+
+    /// Index of the parameter the opcode writes to, if any.
+    #[must_use]
+    pub const fn written_param(self) -> Option<usize> {
+        match self {
+            OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::EqualTo => Some(2),
+            OpCode::Read => Some(0),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        OpCode::ALL
+            .into_iter()
+            .find(|opcode| opcode.mnemonic() == mnemonic)
+    }
+
+    /// The name used in listings.
+    #[must_use]
+    pub const fn mnemonic(self) -> &'static str {
+        match self {
+            OpCode::Add => "add",
+            OpCode::Multiply => "mul",
+            OpCode::Read => "in",
+            OpCode::Write => "out",
+            OpCode::JumpIfTrue => "jnz",
+            OpCode::JumpIfFalse => "jz",
+            OpCode::LessThan => "lt",
+            OpCode::EqualTo => "eq",
+            OpCode::AdjustBase => "arb",
+            OpCode::Exit => "hlt",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ParamMode {
+    /// The parameter is the address of the value
+    #[default]
+    Position = 0,
+    /// The parameter is the value
+    Immediate = 1,
+    /// The parameter plus the relative base is the address of the value
+    Relative = 2,
+}
+
+impl ParamMode {
+    #[must_use]
+    pub fn from_digit(digit: isize) -> Option<Self> {
+        match digit {
+            0 => Some(ParamMode::Position),
+            1 => Some(ParamMode::Immediate),
+            2 => Some(ParamMode::Relative),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    pub opcode: OpCode,
+    pub modes: [ParamMode; 3],
+}
+
+impl Instruction {
+    /// Splits `value` into opcode and parameter modes.
+    ///
+    /// Returns `None` if the opcode or any of the three modes is unknown.
+    /// Modes are checked even for parameters the opcode doesn't have.
+    #[must_use]
+    pub fn decode(value: isize) -> Option<Self> {
+        if !(0..100_000).contains(&value) {
+            return None;
+        }
+        let opcode = OpCode::from_value(value % 100)?;
+        let mut modes = [ParamMode::Position; 3];
+        let mut digits = value / 100;
+        for mode in &mut modes {
+            *mode = ParamMode::from_digit(digits % 10)?;
+            digits /= 10;
+        }
+        Some(Self { opcode, modes })
+    }
+
+    /// The inverse of [`Instruction::decode`].
+    #[must_use]
+    pub fn encode(self) -> isize {
+        self.modes
+            .iter()
+            .rev()
+            .fold(0, |value, &mode| value * 10 + mode as isize)
+            * 100
+            + self.opcode as isize
+    }
+
+    /// Number of memory cells taken by the instruction and its parameters.
+    #[must_use]
+    pub const fn size(self) -> usize {
+        1 + self.opcode.params()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(1002 => Some(Instruction { opcode: OpCode::Multiply, modes: [ParamMode::Position, ParamMode::Immediate, ParamMode::Position] }); "day 5 example")]
+    #[test_case(21101 => Some(Instruction { opcode: OpCode::Add, modes: [ParamMode::Immediate, ParamMode::Immediate, ParamMode::Relative] }); "all modes")]
+    #[test_case(99 => Some(Instruction { opcode: OpCode::Exit, modes: [ParamMode::Position; 3] }); "exit")]
+    #[test_case(0 => None; "zero")]
+    #[test_case(42 => None; "unknown opcode")]
+    #[test_case(301 => None; "unknown mode")]
+    #[test_case(100_001 => None; "too many digits")]
+    #[test_case(-1 => None; "negative")]
+    fn test_decode(value: isize) -> Option<Instruction> {
+        Instruction::decode(value)
+    }
+
+    #[test]
+    fn test_encode() {
+        for value in [1, 1002, 1108, 21101, 203, 99, 22202] {
+            assert_eq!(Instruction::decode(value).unwrap().encode(), value);
+        }
+    }
+}
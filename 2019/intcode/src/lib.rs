@@ -0,0 +1,35 @@
+//! The Intcode computer from 2019, shared by every day that runs an Intcode
+//! program.
+
+#![cfg_attr(test, feature(test))]
+
+pub mod ascii;
+pub mod asm;
+pub mod debugger;
+#[cfg(test)]
+mod differential;
+pub mod disasm;
+pub mod fast;
+pub mod instruction;
+pub mod machine;
+pub mod network;
+pub mod trace;
+
+use std::num::ParseIntError;
+
+pub use ascii::Ascii;
+pub use fast::FastMachine;
+pub use instruction::{Instruction, OpCode, ParamMode};
+pub use machine::{Error, Event, Machine, RestoreError, Status};
+pub use network::{Network, Node, NodeId, NonBlocking, Outbox, Outcome, Sink, State};
+
+/// Reads a program in the usual comma separated format.
+///
+/// # Errors
+/// If anything between the commas isn't a number.
+pub fn parse_program(text: &str) -> Result<Vec<isize>, ParseIntError> {
+    text.trim()
+        .split(',')
+        .map(|value| value.trim().parse())
+        .collect()
+}
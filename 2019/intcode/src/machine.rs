@@ -0,0 +1,731 @@
+//! The Intcode computer itself.
+//!
+//! A [`Machine`] owns its memory and its input and output queues. It can be
+//! driven one instruction at a time with [`Machine::step`], or run until it
+//! needs input ([`Machine::run_until_input`]) or has written a value
+//! ([`Machine::run_until_output`]). Nothing a program does makes it panic,
+//! bad programs get an [`Error`] instead.
+
+use crate::instruction::{Instruction, OpCode, ParamMode};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    num::ParseIntError,
+};
+
+/// What the machine did after running for a while.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// An instruction was executed, the next one can run right away
+    Running,
+    /// Waiting on a read with an empty input queue
+    NeedInput,
+    /// Stopped on an exit instruction
+    Halted,
+}
+
+/// Why [`Machine::run_until_output`] returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Event {
+    Output(isize),
+    NeedInput,
+    Halted,
+}
+
+/// Things a program can do wrong. `ip` is the address of the instruction that
+/// failed, which is left unexecuted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// Unknown opcode or parameter mode
+    InvalidInstruction { ip: usize, value: isize },
+    /// A parameter that gets written to is in immediate mode
+    WriteToImmediate { ip: usize },
+    /// A parameter points below address 0, or a jump goes there
+    NegativeAddress { ip: usize, address: isize },
+    /// A calculation or the relative base doesn't fit in an `isize`
+    Overflow { ip: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidInstruction { ip, value } => {
+                write!(f, "invalid instruction {value} at {ip}")
+            }
+            Error::WriteToImmediate { ip } => {
+                write!(f, "write to immediate parameter at {ip}")
+            }
+            Error::NegativeAddress { ip, address } => {
+                write!(f, "negative address {address} at {ip}")
+            }
+            Error::Overflow { ip } => write!(f, "overflow at {ip}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Why [`Machine::restore`] couldn't make sense of its text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestoreError {
+    /// A field that doesn't exist or is there twice
+    BadField(String),
+    MissingField(&'static str),
+    BadValue(ParseIntError),
+}
+
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestoreError::BadField(field) => write!(f, "bad field {field:?}"),
+            RestoreError::MissingField(field) => write!(f, "missing field {field:?}"),
+            RestoreError::BadValue(error) => write!(f, "bad value: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RestoreError {}
+
+impl From<ParseIntError> for RestoreError {
+    fn from(error: ParseIntError) -> Self {
+        RestoreError::BadValue(error)
+    }
+}
+
+/// Addresses from here up are stored sparsely, unless the program is longer.
+pub const DENSE_LIMIT: usize = 1 << 16;
+
+/// An Intcode computer with its program loaded.
+///
+/// Memory grows as the program writes past the end, up to [`DENSE_LIMIT`].
+/// Writes above that are kept apart, so a program writing far away doesn't
+/// allocate everything in between. Reading past the end gives 0 without
+/// growing anything.
+///
+/// A machine is all its own state, so cloning one at any point forks it: both
+/// carry on from there without affecting each other, which makes searching
+/// over what a program does as simple as cloning before every choice.
+/// [`Machine::save`] and [`Machine::restore`] do the same through text.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Machine {
+    memory: Vec<isize>,
+    /// Memory from [`DENSE_LIMIT`] up that has been written to
+    sparse: BTreeMap<usize, isize>,
+    ip: usize,
+    rel_base: isize,
+    input: VecDeque<isize>,
+    output: VecDeque<isize>,
+}
+
+impl Machine {
+    #[must_use]
+    pub fn new(program: Vec<isize>) -> Self {
+        Self {
+            memory: program,
+            ..Default::default()
+        }
+    }
+
+    /// A new machine with `input` already queued.
+    #[must_use]
+    pub fn with_input(program: Vec<isize>, input: impl IntoIterator<Item = isize>) -> Self {
+        let mut machine = Self::new(program);
+        machine.input.extend(input);
+        machine
+    }
+
+    /// The densely stored part of memory, which starts with the program.
+    #[must_use]
+    pub fn memory(&self) -> &[isize] {
+        &self.memory
+    }
+
+    /// Memory past the dense part that has been written to, by address.
+    #[must_use]
+    pub fn sparse_memory(&self) -> &BTreeMap<usize, isize> {
+        &self.sparse
+    }
+
+    #[must_use]
+    pub fn read(&self, address: usize) -> isize {
+        match self.memory.get(address) {
+            Some(&value) => value,
+            None => self.sparse.get(&address).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: isize) {
+        if address < self.memory.len() {
+            self.memory[address] = value;
+        } else if address < DENSE_LIMIT {
+            self.memory.resize(address + 1, 0);
+            self.memory[address] = value;
+        } else {
+            self.sparse.insert(address, value);
+        }
+    }
+
+    /// Address of the next instruction.
+    #[must_use]
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    #[must_use]
+    pub fn rel_base(&self) -> isize {
+        self.rel_base
+    }
+
+    pub fn push_input(&mut self, value: isize) {
+        self.input.push_back(value);
+    }
+
+    pub fn extend_input(&mut self, values: impl IntoIterator<Item = isize>) {
+        self.input.extend(values);
+    }
+
+    /// Values queued for reads, oldest first.
+    #[must_use]
+    pub fn input(&self) -> &VecDeque<isize> {
+        &self.input
+    }
+
+    /// Values written but not yet taken, oldest first.
+    #[must_use]
+    pub fn output(&self) -> &VecDeque<isize> {
+        &self.output
+    }
+
+    pub fn pop_output(&mut self) -> Option<isize> {
+        self.output.pop_front()
+    }
+
+    /// Takes everything from the output queue.
+    pub fn take_output(&mut self) -> Vec<isize> {
+        self.output.drain(..).collect()
+    }
+
+    /// Writes everything about the machine as text, one field per line. Sparse
+    /// memory, if there is any, goes last as `address:value` pairs.
+    #[must_use]
+    pub fn save(&self) -> String {
+        fn list<'a>(values: impl IntoIterator<Item = &'a isize>) -> String {
+            values
+                .into_iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+        let mut text = format!(
+            "ip {}\nrel_base {}\ninput {}\noutput {}\nmemory {}\n",
+            self.ip,
+            self.rel_base,
+            list(&self.input),
+            list(&self.output),
+            list(&self.memory)
+        );
+        if !self.sparse.is_empty() {
+            let pairs = self
+                .sparse
+                .iter()
+                .map(|(address, value)| format!("{address}:{value}"))
+                .collect::<Vec<_>>();
+            text.push_str(&format!("sparse {}\n", pairs.join(",")));
+        }
+        text
+    }
+
+    /// Reads a machine written by [`Machine::save`]. Only `sparse` may be left
+    /// out.
+    ///
+    /// # Errors
+    /// If a field is missing, unknown or given twice, or a value isn't a
+    /// number.
+    pub fn restore(text: &str) -> Result<Self, RestoreError> {
+        fn list<T: FromIterator<isize>>(values: &str) -> Result<T, ParseIntError> {
+            values
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::parse)
+                .collect()
+        }
+        fn pairs(values: &str) -> Result<BTreeMap<usize, isize>, RestoreError> {
+            values
+                .split(',')
+                .map(str::trim)
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (address, value) = pair
+                        .split_once(':')
+                        .ok_or_else(|| RestoreError::BadField(format!("sparse {pair}")))?;
+                    Ok((address.parse()?, value.parse()?))
+                })
+                .collect()
+        }
+        let (mut ip, mut rel_base, mut input, mut output, mut memory, mut sparse) =
+            (None, None, None, None, None, None);
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (field, values) = line.split_once(' ').unwrap_or((line, ""));
+            let duplicate = match field {
+                "ip" => ip.replace(values.trim().parse()?).is_some(),
+                "rel_base" => rel_base.replace(values.trim().parse()?).is_some(),
+                "input" => input.replace(list(values)?).is_some(),
+                "output" => output.replace(list(values)?).is_some(),
+                "memory" => memory.replace(list(values)?).is_some(),
+                "sparse" => sparse.replace(pairs(values)?).is_some(),
+                _ => true,
+            };
+            if duplicate {
+                return Err(RestoreError::BadField(field.to_string()));
+            }
+        }
+        Ok(Self {
+            memory: memory.ok_or(RestoreError::MissingField("memory"))?,
+            sparse: sparse.unwrap_or_default(),
+            ip: ip.ok_or(RestoreError::MissingField("ip"))?,
+            rel_base: rel_base.ok_or(RestoreError::MissingField("rel_base"))?,
+            input: input.ok_or(RestoreError::MissingField("input"))?,
+            output: output.ok_or(RestoreError::MissingField("output"))?,
+        })
+    }
+
+    /// Decodes the instruction at the instruction pointer.
+    ///
+    /// # Errors
+    /// If the value there isn't a valid instruction.
+    pub fn decode(&self) -> Result<Instruction, Error> {
+        let value = self.read(self.ip);
+        Instruction::decode(value).ok_or(Error::InvalidInstruction { ip: self.ip, value })
+    }
+
+    /// Executes one instruction. A read with no input waiting and an exit
+    /// leave the machine where it is, so stepping again repeats them.
+    ///
+    /// # Errors
+    /// If the instruction is invalid, or does something invalid. The machine
+    /// is left as it was before the instruction.
+    pub fn step(&mut self) -> Result<Status, Error> {
+        let instruction = self.decode()?;
+        let ip = self.ip;
+        match instruction.opcode {
+            OpCode::Add => {
+                self.calculate(instruction, |a, b| a.checked_add(b))?;
+            }
+            OpCode::Multiply => {
+                self.calculate(instruction, |a, b| a.checked_mul(b))?;
+            }
+            OpCode::LessThan => {
+                self.calculate(instruction, |a, b| Some(isize::from(a < b)))?;
+            }
+            OpCode::EqualTo => {
+                self.calculate(instruction, |a, b| Some(isize::from(a == b)))?;
+            }
+            OpCode::Read => {
+                let address = self.address(instruction, 0)?;
+                let Some(value) = self.input.pop_front() else {
+                    return Ok(Status::NeedInput);
+                };
+                self.write(address, value);
+            }
+            OpCode::Write => {
+                let value = self.load(instruction, 0)?;
+                self.output.push_back(value);
+            }
+            OpCode::AdjustBase => {
+                let value = self.load(instruction, 0)?;
+                self.rel_base = self
+                    .rel_base
+                    .checked_add(value)
+                    .ok_or(Error::Overflow { ip })?;
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let condition = self.load(instruction, 0)?;
+                let target = self.load(instruction, 1)?;
+                if (condition != 0) == (instruction.opcode == OpCode::JumpIfTrue) {
+                    self.ip = usize::try_from(target).map_err(|_| Error::NegativeAddress {
+                        ip,
+                        address: target,
+                    })?;
+                    return Ok(Status::Running);
+                }
+            }
+            OpCode::Exit => return Ok(Status::Halted),
+        }
+        self.ip += instruction.size();
+        Ok(Status::Running)
+    }
+
+    /// Runs until the program wants input that isn't there, or exits. Output
+    /// piles up in the output queue.
+    ///
+    /// # Errors
+    /// If the program hits an invalid instruction, see [`Machine::step`].
+    pub fn run_until_input(&mut self) -> Result<Status, Error> {
+        loop {
+            match self.step()? {
+                Status::Running => {}
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Runs until the program writes a value, and takes it from the output
+    /// queue. If there's already output waiting, the oldest value is returned
+    /// without running anything.
+    ///
+    /// # Errors
+    /// If the program hits an invalid instruction, see [`Machine::step`].
+    pub fn run_until_output(&mut self) -> Result<Event, Error> {
+        loop {
+            if let Some(value) = self.output.pop_front() {
+                return Ok(Event::Output(value));
+            }
+            match self.step()? {
+                Status::Running => {}
+                Status::NeedInput => return Ok(Event::NeedInput),
+                Status::Halted => return Ok(Event::Halted),
+            }
+        }
+    }
+
+    /// Runs `operation` on the first two parameters and stores the result in
+    /// the third. `None` from `operation` means it overflowed.
+    fn calculate(
+        &mut self,
+        instruction: Instruction,
+        operation: impl Fn(isize, isize) -> Option<isize>,
+    ) -> Result<(), Error> {
+        let a = self.load(instruction, 0)?;
+        let b = self.load(instruction, 1)?;
+        let address = self.address(instruction, 2)?;
+        let result = operation(a, b).ok_or(Error::Overflow { ip: self.ip })?;
+        self.write(address, result);
+        Ok(())
+    }
+
+    /// The value of parameter `index` (0-based).
+    fn load(&self, instruction: Instruction, index: usize) -> Result<isize, Error> {
+        match instruction.modes[index] {
+            ParamMode::Immediate => Ok(self.read(self.ip + 1 + index)),
+            ParamMode::Position | ParamMode::Relative => {
+                Ok(self.read(self.address(instruction, index)?))
+            }
+        }
+    }
+
+    /// The address parameter `index` (0-based) of `instruction` points to,
+    /// taking its parameters from the instruction pointer on.
+    ///
+    /// # Errors
+    /// If the parameter is in immediate mode, or the address overflows or is
+    /// below 0.
+    pub fn address(&self, instruction: Instruction, index: usize) -> Result<usize, Error> {
+        let ip = self.ip;
+        let param = self.read(ip + 1 + index);
+        let address = match instruction.modes[index] {
+            ParamMode::Position => param,
+            ParamMode::Relative => param
+                .checked_add(self.rel_base)
+                .ok_or(Error::Overflow { ip })?,
+            ParamMode::Immediate => return Err(Error::WriteToImmediate { ip }),
+        };
+        usize::try_from(address).map_err(|_| Error::NegativeAddress { ip, address })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use test_case::test_case;
+
+    fn run(program: Vec<isize>, input: impl IntoIterator<Item = isize>) -> Machine {
+        let mut machine = Machine::with_input(program, input);
+        assert_eq!(machine.run_until_input(), Ok(Status::Halted));
+        machine
+    }
+
+    #[test]
+    fn day9_test_1() {
+        let program = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(run(program.clone(), []).take_output(), program);
+    }
+
+    #[test]
+    fn day9_test_2() {
+        let program = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        let output = run(program, []).pop_output().unwrap();
+        assert_eq!(output.to_string().len(), 16);
+    }
+
+    #[test]
+    fn day9_test_3() {
+        let program = vec![104, 1125899906842624, 99];
+        assert_eq!(run(program, []).pop_output(), Some(1125899906842624));
+    }
+
+    #[test_case("
+                add  [9], [10], [3]
+                mul  [3], [11], [0]
+                hlt
+                data 30, 40, 50" => vec![3500,9,10,70,2,3,11,0,99,30,40,50]; "day 2 example 1")]
+    #[test_case("
+                add  [0], [0], [0]
+                hlt" => vec![2,0,0,0,99]; "day 2 example 2")]
+    #[test_case("
+                mul  [3], [0], [3]
+                hlt" => vec![2,3,0,6,99]; "day 2 example 3")]
+    #[test_case("
+                mul  [end], [end], [square]
+        end:    hlt
+        square: data 0" => vec![2,4,4,5,99,9801]; "day 2 example 4")]
+    #[test_case("
+                add  [1], [1], [patch]
+        patch:  hlt
+                data 5, 6, 0
+                hlt" => vec![30,1,1,4,2,5,6,0,99]; "day 2 example 5")]
+    #[test_case("
+                mul  [end], #3, [end]
+        end:    data 33" => vec![1002,4,3,4,99]; "day 5 example 1")]
+    #[test_case("
+                add  #100, #-1, [end]
+        end:    data 0" => vec![1101,100,-1,4,99]; "day 5 example 2")]
+    fn pre_input_output(source: &str) -> Vec<isize> {
+        run(assemble(source).unwrap(), []).memory().to_vec()
+    }
+
+    const EQUAL_POSITION: &str = "
+                in   [x]
+                eq   [x], [eight], [x]
+                out  [x]
+                hlt
+        x:      data -1
+        eight:  data 8";
+
+    const LESS_POSITION: &str = "
+                in   [x]
+                lt   [x], [eight], [x]
+                out  [x]
+                hlt
+        x:      data -1
+        eight:  data 8";
+
+    const EQUAL_IMMEDIATE: &str = "
+                in   [3]
+                eq   #-1, #8, [3]
+                out  [3]
+                hlt";
+
+    const LESS_IMMEDIATE: &str = "
+                in   [3]
+                lt   #-1, #8, [3]
+                out  [3]
+                hlt";
+
+    const JUMP_POSITION: &str = "
+                in   [input]
+                jz   [input], [target]
+                add  [output], [one], [output]
+        skip:   out  [output]
+                hlt
+        input:  data -1
+        output: data 0
+        one:    data 1
+        target: data skip";
+
+    const JUMP_IMMEDIATE: &str = "
+                in   [3]
+                jnz  #-1, #skip
+                add  #0, #0, [output]
+        skip:   out  [output]
+                hlt
+        output: data 1";
+
+    const LONG_EXAMPLE: &str = "
+                in   [input]
+                eq   [input], #8, [result]
+                jnz  [result], #equal
+                lt   #8, [input], [result]
+                jz   [result], #below
+                jz   #0, #above
+                data 98
+        result: data 0
+        input:  data 0
+        equal:  mul  [input], #125, [result]
+                out  [result]
+                jnz  #1, #end
+        below:  out  #999
+                jnz  #1, #end
+        above:  add  #1000, #1, [result]
+                out  [result]
+                jnz  #1, #end
+                data 98
+        end:    hlt";
+
+    #[test_case(EQUAL_POSITION, 8 => 1; "day 5 example 3a - equal to position mode")]
+    #[test_case(EQUAL_POSITION, 234 => 0; "day 5 example 3b - equal to position mode")]
+    #[test_case(LESS_POSITION, 7 => 1; "day 5 example 4a - less than position mode")]
+    #[test_case(LESS_POSITION, 8 => 0; "day 5 example 4b - less than position mode")]
+    #[test_case(LESS_POSITION, 9 => 0; "day 5 example 4c - less than position mode")]
+    #[test_case(LESS_POSITION, -9 => 1; "day 5 example 4d - less than position mode")]
+    #[test_case(EQUAL_IMMEDIATE, 8 => 1; "day 5 example 5a - equal to immediate mode")]
+    #[test_case(EQUAL_IMMEDIATE, 234 => 0; "day 5 example 5b - equal to immediate mode")]
+    #[test_case(LESS_IMMEDIATE, 7 => 1; "day 5 example 6a - less than immediate mode")]
+    #[test_case(LESS_IMMEDIATE, 8 => 0; "day 5 example 6b - less than immediate mode")]
+    #[test_case(LESS_IMMEDIATE, 9 => 0; "day 5 example 6c - less than immediate mode")]
+    #[test_case(LESS_IMMEDIATE, -9 => 1; "day 5 example 6d - less than immediate mode")]
+    #[test_case(JUMP_POSITION, 0 => 0; "day 5 example 7a - jump position mode")]
+    #[test_case(JUMP_POSITION, 1 => 1; "day 5 example 7b - jump position mode")]
+    #[test_case(JUMP_POSITION, -99 => 1; "day 5 example 7c - jump position mode")]
+    #[test_case(JUMP_IMMEDIATE, 0 => 0; "day 5 example 8a - jump immediate mode")]
+    #[test_case(JUMP_IMMEDIATE, 1 => 1; "day 5 example 8b - jump immediate mode")]
+    #[test_case(JUMP_IMMEDIATE, -99 => 1; "day 5 example 8c - jump immediate mode")]
+    #[test_case(LONG_EXAMPLE, -99 => 999; "day 5 example 9a - long example")]
+    #[test_case(LONG_EXAMPLE, 8 => 1000; "day 5 example 9b - long example")]
+    #[test_case(LONG_EXAMPLE, 1337 => 1001; "day 5 example 9c - long example")]
+    fn simple_input_output(source: &str, input: isize) -> isize {
+        run(assemble(source).unwrap(), [input])
+            .pop_output()
+            .unwrap()
+    }
+
+    #[test_case(vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0], vec![4,3,2,1,0], 0 => 43210; "day 7 example 1")]
+    #[test_case(vec![3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0], vec![0,1,2,3,4],
+        0 => 54321; "day 7 example 2")]
+    #[test_case(vec![3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0],
+        vec![1,0,4,3,2], 0 => 65210; "day 7 example 3")]
+    fn amp_chain(program: Vec<isize>, settings: Vec<isize>, input: isize) -> isize {
+        settings.into_iter().fold(input, |signal, setting| {
+            run(program.clone(), [setting, signal])
+                .pop_output()
+                .unwrap()
+        })
+    }
+
+    #[test_case(vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5],
+        vec![9,8,7,6,5], 0 => 139629729; "day 7 example 4")]
+    #[test_case(vec![3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,
+        0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10], vec![9,7,8,5,6], 0 => 18216; "day 7 example 5")]
+    fn amp_chain_feedback(program: Vec<isize>, settings: Vec<isize>, input: isize) -> isize {
+        let mut amps = settings
+            .into_iter()
+            .map(|setting| Machine::with_input(program.clone(), [setting]))
+            .collect::<Vec<_>>();
+        let mut signal = input;
+        loop {
+            for amp in &mut amps {
+                amp.push_input(signal);
+                match amp.run_until_output() {
+                    Ok(Event::Output(value)) => signal = value,
+                    Ok(Event::Halted) => return signal,
+                    other => panic!("amplifier stuck: {other:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut machine = Machine::new(vec![3, 9, 1001, 9, 5, 9, 4, 9, 99, 0]);
+        assert_eq!(machine.step(), Ok(Status::NeedInput));
+        assert_eq!(machine.ip(), 0);
+        machine.push_input(37);
+        assert_eq!(machine.step(), Ok(Status::Running));
+        assert_eq!(machine.ip(), 2);
+        assert_eq!(machine.step(), Ok(Status::Running));
+        assert_eq!(machine.read(9), 42);
+        assert_eq!(machine.run_until_output(), Ok(Event::Output(42)));
+        assert_eq!(machine.run_until_output(), Ok(Event::Halted));
+        assert_eq!(machine.step(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn test_memory_grows() {
+        let mut machine = Machine::new(vec![109, 1000, 21101, 3, 4, 7, 204, 7, 99]);
+        assert_eq!(machine.read(1007), 0);
+        assert_eq!(machine.run_until_output(), Ok(Event::Output(7)));
+        assert_eq!(machine.rel_base(), 1000);
+        assert_eq!(machine.memory().len(), 1008);
+    }
+
+    #[test_case(vec![1101, 0, 0, 1_000_000_000_000_000_000, 99], 1_000_000_000_000_000_000 => 0; "far away")]
+    #[test_case(vec![109, isize::MAX - 10, 21101, 3, 4, 10, 99], isize::MAX as usize => 7; "at the very end")]
+    #[test_case(vec![1101, 3, 4, DENSE_LIMIT as isize, 99], DENSE_LIMIT => 7; "just past dense")]
+    fn test_sparse_memory(program: Vec<isize>, address: usize) -> isize {
+        let mut machine = Machine::new(program);
+        assert_eq!(machine.run_until_input(), Ok(Status::Halted));
+        assert!(machine.memory().len() < DENSE_LIMIT);
+        assert_eq!(machine.sparse_memory().len(), 1);
+        machine.read(address)
+    }
+
+    #[test_case(vec![42] => Error::InvalidInstruction { ip: 0, value: 42 }; "invalid opcode")]
+    #[test_case(vec![1105, 1, 4, 99, 301] => Error::InvalidInstruction { ip: 4, value: 301 }; "invalid mode")]
+    #[test_case(vec![11101, 1, 1, 0, 99] => Error::WriteToImmediate { ip: 0 }; "write to immediate")]
+    #[test_case(vec![4, -3, 99] => Error::NegativeAddress { ip: 0, address: -3 }; "negative address")]
+    #[test_case(vec![109, -5, 204, 1, 99] => Error::NegativeAddress { ip: 2, address: -4 }; "negative relative address")]
+    #[test_case(vec![1106, 0, -1] => Error::NegativeAddress { ip: 0, address: -1 }; "negative jump")]
+    #[test_case(vec![1102, isize::MAX, 2, 0, 99] => Error::Overflow { ip: 0 }; "overflow")]
+    #[test_case(vec![1, 0, 0, 0] => Error::InvalidInstruction { ip: 4, value: 0 }; "runs off the end")]
+    fn test_errors(program: Vec<isize>) -> Error {
+        let mut machine = Machine::new(program);
+        let error = machine.run_until_input().unwrap_err();
+        assert_eq!(machine.step(), Err(error));
+        error
+    }
+
+    #[test]
+    fn test_fork() {
+        const RUNNING_TOTAL: &str = "
+            loop:   in   [x]
+                    add  [x], [sum], [sum]
+                    out  [sum]
+                    jnz  #1, #loop
+            x:      data 0
+            sum:    data 0";
+        let mut machine = Machine::new(assemble(RUNNING_TOTAL).unwrap());
+        machine.push_input(5);
+        assert_eq!(machine.run_until_input(), Ok(Status::NeedInput));
+        let mut fork = machine.clone();
+        machine.push_input(1);
+        fork.push_input(100);
+        assert_eq!(machine.run_until_input(), Ok(Status::NeedInput));
+        assert_eq!(fork.run_until_input(), Ok(Status::NeedInput));
+        assert_eq!(machine.take_output(), [5, 6]);
+        assert_eq!(fork.take_output(), [5, 105]);
+    }
+
+    #[test]
+    fn test_save_and_restore() {
+        let mut machine = Machine::with_input(vec![109, 7, 3, 0, 204, -7, 99], [4, 5]);
+        assert_eq!(machine.step(), Ok(Status::Running));
+        assert_eq!(machine.step(), Ok(Status::Running));
+        let saved = machine.save();
+        assert_eq!(
+            saved,
+            "ip 4\nrel_base 7\ninput 5\noutput \nmemory 4,7,3,0,204,-7,99\n"
+        );
+        let mut restored = Machine::restore(&saved).unwrap();
+        assert_eq!(restored, machine);
+        assert_eq!(restored.run_until_output(), Ok(Event::Output(4)));
+        assert_eq!(Machine::restore(&restored.save()), Ok(restored));
+
+        let mut far = Machine::new(vec![1101, 3, 4, 1 << 40, 99]);
+        assert_eq!(far.run_until_input(), Ok(Status::Halted));
+        let saved = far.save();
+        assert!(saved.ends_with("\nsparse 1099511627776:7\n"));
+        assert_eq!(Machine::restore(&saved), Ok(far));
+    }
+
+    #[test_case("ip 0\nrel_base 0\ninput\noutput\nmemory 99\nmemory 99" => RestoreError::BadField("memory".to_string()); "twice")]
+    #[test_case("ip 0\nrel_base 0\ninput\noutput\nmemory 99\nsteps 7" => RestoreError::BadField("steps".to_string()); "unknown field")]
+    #[test_case("ip 0\nrel_base 0\ninput\nmemory 99" => RestoreError::MissingField("output"); "missing field")]
+    #[test_case("ip -1" => matches RestoreError::BadValue(_); "bad value")]
+    #[test_case("ip 0\nrel_base 0\ninput\noutput\nmemory 99\nsparse 70000" => RestoreError::BadField("sparse 70000".to_string()); "bad sparse pair")]
+    fn test_restore_errors(text: &str) -> RestoreError {
+        Machine::restore(text).unwrap_err()
+    }
+}
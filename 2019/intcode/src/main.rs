@@ -0,0 +1,97 @@
+//! Tools for writing, reading and debugging Intcode programs.
+//!
+//! ```text
+//! intcode ascii <program>
+//! intcode asm <source>
+//! intcode disasm <program>
+//! intcode debug <program> [script]
+//! intcode trace <program> <log> [input]...
+//! intcode profile <log>
+//! ```
+//!
+//! `ascii` plays a program that talks in text from stdin. Without a script
+//! the debugger reads commands from stdin, see [`intcode::debugger`] for what
+//! they are. `trace` runs a program with the input given until it needs more
+//! or exits, printing its output and logging what it did, and `profile`
+//! reports on such a log, see [`intcode::trace`].
+
+use intcode::{
+    asm::{assemble, format_program},
+    debugger::{Debugger, PROMPT},
+    disasm::disassemble,
+    parse_program,
+    trace::{Profile, Tracer},
+    Ascii, Machine, Status,
+};
+use std::{
+    env::args,
+    fs,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+pub type Error = Box<dyn std::error::Error>;
+
+const USAGE: &str = "usage: intcode ascii <program>
+       intcode asm <source>
+       intcode disasm <program>
+       intcode debug <program> [script]
+       intcode trace <program> <log> [input]...
+       intcode profile <log>";
+
+fn main() -> Result<()> {
+    let mut args = args().skip(1);
+    let command = args.next();
+    let path = args.next().ok_or(USAGE)?;
+    if command.as_deref() == Some("profile") {
+        print!("{}", Profile::read(BufReader::new(fs::File::open(path)?))?);
+        return Ok(());
+    }
+    let text = fs::read_to_string(path)?;
+    match command.as_deref() {
+        Some("ascii") => {
+            let mut ascii = Ascii::new(Machine::new(parse_program(&text)?));
+            ascii.repl(io::stdin().lock(), &mut io::stdout().lock())?;
+        }
+        Some("asm") => println!("{}", format_program(&assemble(&text)?)),
+        Some("disasm") => print!("{}", disassemble(&parse_program(&text)?)),
+        Some("debug") => {
+            let mut debugger = Debugger::new(Machine::new(parse_program(&text)?));
+            let mut stdout = io::stdout().lock();
+            if let Some(script) = args.next() {
+                let script = BufReader::new(fs::File::open(script)?);
+                debugger.run_script(script, &mut stdout)?;
+            } else {
+                let mut lines = io::stdin().lock().lines();
+                loop {
+                    write!(stdout, "{PROMPT}")?;
+                    stdout.flush()?;
+                    let Some(line) = lines.next() else {
+                        break;
+                    };
+                    if !debugger.command(&line?, &mut stdout)? {
+                        break;
+                    }
+                }
+            }
+        }
+        Some("trace") => {
+            let log = BufWriter::new(fs::File::create(args.next().ok_or(USAGE)?)?);
+            let input = args
+                .map(|value| value.parse())
+                .collect::<core::result::Result<Vec<_>, _>>()?;
+            let machine = Machine::with_input(parse_program(&text)?, input);
+            let mut tracer = Tracer::new(machine, log)?;
+            let status = tracer.run_until_input()?;
+            for value in tracer.machine_mut().take_output() {
+                println!("{value}");
+            }
+            tracer.into_log().flush()?;
+            if status == Status::NeedInput {
+                eprintln!("stopped waiting for input");
+            }
+        }
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
+}
@@ -0,0 +1,552 @@
+//! Running Intcode machines and native peripherals together.
+//!
+//! A [`Network`] is a directed graph of [`Node`]s. Nodes send values on
+//! numbered output ports, and every edge copies what's sent on one output port
+//! to an input port of another node. Feedback loops are fine, and so is
+//! connecting several edges to the same port, in either direction.
+//!
+//! The network runs its nodes round-robin until nothing can move any more.
+//! Then either every node has halted, a node asked to stop, or the nodes that
+//! are left are waiting on each other. That last one is reported as a
+//! deadlock rather than spinning forever.
+
+use crate::machine::{Error, Machine, Status};
+use std::{any::Any, fmt};
+
+pub type NodeId = usize;
+
+/// What a node is up to after it ran.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum State {
+    /// Has more work to do without new input
+    Ready,
+    /// Waiting for input
+    Blocked,
+    /// Looked for input, didn't find any and carried on. Like a blocked node
+    /// it only runs again when input arrives.
+    Idle,
+    /// Done for good
+    Halted,
+}
+
+/// Collects what a node sends while it runs.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    sent: Vec<(usize, isize)>,
+    stop: bool,
+}
+
+impl Outbox {
+    /// Sends `value` on output port 0.
+    pub fn send(&mut self, value: isize) {
+        self.send_to(0, value);
+    }
+
+    /// Sends `value` on output `port`. Values sent on a port without edges
+    /// are dropped.
+    pub fn send_to(&mut self, port: usize, value: isize) {
+        self.sent.push((port, value));
+    }
+
+    /// Stops the whole network once the node returns.
+    pub fn stop(&mut self) {
+        self.stop = true;
+    }
+}
+
+/// Anything that can be wired into a [`Network`].
+pub trait Node: Any {
+    /// Takes a value that arrived on input `port`.
+    fn receive(&mut self, port: usize, value: isize);
+
+    /// Does as much as it can with the input received so far.
+    ///
+    /// # Errors
+    /// If a machine runs into an invalid instruction.
+    fn run(&mut self, outbox: &mut Outbox) -> Result<State, Error>;
+
+    /// Called once, when the node is blocked and every node that sends to it
+    /// has halted, so no input will ever come. The default stays blocked.
+    fn inputs_closed(&mut self, outbox: &mut Outbox) -> State {
+        let _ = outbox;
+        State::Blocked
+    }
+
+    /// Called on every node that hasn't halted when the network goes quiet:
+    /// no node is ready and nothing is waiting to be read. Sending something
+    /// gets things going again.
+    fn idle(&mut self, outbox: &mut Outbox) {
+        let _ = outbox;
+    }
+}
+
+/// Takes input on any port and sends output on port 0.
+impl Node for Machine {
+    fn receive(&mut self, _port: usize, value: isize) {
+        self.push_input(value);
+    }
+
+    fn run(&mut self, outbox: &mut Outbox) -> Result<State, Error> {
+        let status = self.run_until_input();
+        for value in self.take_output() {
+            outbox.send(value);
+        }
+        Ok(match status? {
+            Status::Halted => State::Halted,
+            Status::Running | Status::NeedInput => State::Blocked,
+        })
+    }
+}
+
+/// A machine that reads `empty` instead of waiting when there's no input, like
+/// the network interfaces in 2019 day 23. It reads `empty` once per run and
+/// then reports itself [`State::Idle`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NonBlocking {
+    pub machine: Machine,
+    pub empty: isize,
+}
+
+impl NonBlocking {
+    #[must_use]
+    pub fn new(machine: Machine, empty: isize) -> Self {
+        Self { machine, empty }
+    }
+}
+
+impl Node for NonBlocking {
+    fn receive(&mut self, _port: usize, value: isize) {
+        self.machine.push_input(value);
+    }
+
+    fn run(&mut self, outbox: &mut Outbox) -> Result<State, Error> {
+        let mut polled = false;
+        loop {
+            let status = self.machine.run_until_input();
+            for value in self.machine.take_output() {
+                outbox.send(value);
+            }
+            match status? {
+                Status::Halted => return Ok(State::Halted),
+                _ if polled => return Ok(State::Idle),
+                _ => {
+                    self.machine.push_input(self.empty);
+                    polled = true;
+                }
+            }
+        }
+    }
+}
+
+/// Keeps everything it receives, for reading the results of a network.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sink {
+    pub values: Vec<isize>,
+}
+
+impl Node for Sink {
+    fn receive(&mut self, _port: usize, value: isize) {
+        self.values.push(value);
+    }
+
+    fn run(&mut self, _outbox: &mut Outbox) -> Result<State, Error> {
+        Ok(State::Blocked)
+    }
+
+    fn inputs_closed(&mut self, _outbox: &mut Outbox) -> State {
+        State::Halted
+    }
+}
+
+/// How a [`Network::run`] ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every node halted
+    Halted,
+    /// A node called [`Outbox::stop`]
+    Stopped(NodeId),
+    /// Nothing can happen any more, but these nodes haven't halted
+    Deadlock(Vec<NodeId>),
+}
+
+/// A machine in the network failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeError {
+    pub node: NodeId,
+    pub error: Error,
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {}: {}", self.node, self.error)
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+struct Slot {
+    node: Box<dyn Node>,
+    state: State,
+    has_input: bool,
+    closed: bool,
+}
+
+struct Edge {
+    from: NodeId,
+    from_port: usize,
+    to: NodeId,
+    to_port: usize,
+}
+
+#[derive(Default)]
+pub struct Network {
+    slots: Vec<Slot>,
+    edges: Vec<Edge>,
+}
+
+impl Network {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node, which starts out [`State::Ready`].
+    pub fn add(&mut self, node: impl Node) -> NodeId {
+        self.slots.push(Slot {
+            node: Box::new(node),
+            state: State::Ready,
+            has_input: false,
+            closed: false,
+        });
+        self.slots.len() - 1
+    }
+
+    /// Connects output port 0 of `from` to input port 0 of `to`.
+    pub fn connect(&mut self, from: NodeId, to: NodeId) {
+        self.connect_ports(from, 0, to, 0);
+    }
+
+    pub fn connect_ports(&mut self, from: NodeId, from_port: usize, to: NodeId, to_port: usize) {
+        self.edges.push(Edge {
+            from,
+            from_port,
+            to,
+            to_port,
+        });
+    }
+
+    /// Hands `value` to input `port` of `to`, as if a node sent it.
+    pub fn send(&mut self, to: NodeId, port: usize, value: isize) {
+        let slot = &mut self.slots[to];
+        slot.node.receive(port, value);
+        slot.has_input = true;
+    }
+
+    /// The node `id`, if it is a `T`.
+    #[must_use]
+    pub fn node<T: Node>(&self, id: NodeId) -> Option<&T> {
+        let node: &dyn Any = self.slots.get(id)?.node.as_ref();
+        node.downcast_ref()
+    }
+
+    /// The node `id`, if it is a `T`.
+    pub fn node_mut<T: Node>(&mut self, id: NodeId) -> Option<&mut T> {
+        let node: &mut dyn Any = self.slots.get_mut(id)?.node.as_mut();
+        node.downcast_mut()
+    }
+
+    #[must_use]
+    pub fn state(&self, id: NodeId) -> State {
+        self.slots[id].state
+    }
+
+    /// Runs until every node halted, a node stops the network, or nothing can
+    /// happen any more. Running again after a stop carries on from there.
+    ///
+    /// # Errors
+    /// If a machine runs into an invalid instruction.
+    pub fn run(&mut self) -> Result<Outcome, NodeError> {
+        loop {
+            let mut ran = false;
+            for id in 0..self.slots.len() {
+                if !self.runnable(id) {
+                    continue;
+                }
+                ran = true;
+                let slot = &mut self.slots[id];
+                slot.has_input = false;
+                let mut outbox = Outbox::default();
+                slot.state = slot
+                    .node
+                    .run(&mut outbox)
+                    .map_err(|error| NodeError { node: id, error })?;
+                if self.deliver(id, outbox) {
+                    return Ok(Outcome::Stopped(id));
+                }
+            }
+            if ran {
+                continue;
+            }
+            if self.all_halted() {
+                return Ok(Outcome::Halted);
+            }
+            if let Some(id) = self.wake() {
+                return Ok(Outcome::Stopped(id));
+            }
+            if !self.all_halted() && !self.any_runnable() {
+                let waiting = (0..self.slots.len())
+                    .filter(|&id| self.slots[id].state != State::Halted)
+                    .collect();
+                return Ok(Outcome::Deadlock(waiting));
+            }
+        }
+    }
+
+    fn all_halted(&self) -> bool {
+        self.slots.iter().all(|slot| slot.state == State::Halted)
+    }
+
+    fn any_runnable(&self) -> bool {
+        (0..self.slots.len()).any(|id| self.runnable(id))
+    }
+
+    fn runnable(&self, id: NodeId) -> bool {
+        let slot = &self.slots[id];
+        match slot.state {
+            State::Ready => true,
+            State::Blocked | State::Idle => slot.has_input,
+            State::Halted => false,
+        }
+    }
+
+    /// Gives a quiet network a nudge: first lets nodes that will never get
+    /// input again wrap up, and if that doesn't do anything, calls
+    /// [`Node::idle`] everywhere. Returns the node that stopped the network,
+    /// if any.
+    fn wake(&mut self) -> Option<NodeId> {
+        for id in 0..self.slots.len() {
+            let slot = &self.slots[id];
+            let senders_halted = self
+                .edges
+                .iter()
+                .filter(|edge| edge.to == id)
+                .all(|edge| self.slots[edge.from].state == State::Halted);
+            if slot.state != State::Blocked || slot.closed || !senders_halted {
+                continue;
+            }
+            let slot = &mut self.slots[id];
+            slot.closed = true;
+            let mut outbox = Outbox::default();
+            slot.state = slot.node.inputs_closed(&mut outbox);
+            if self.deliver(id, outbox) {
+                return Some(id);
+            }
+        }
+        if self.all_halted() || self.any_runnable() {
+            return None;
+        }
+        for id in 0..self.slots.len() {
+            let slot = &mut self.slots[id];
+            if slot.state == State::Halted {
+                continue;
+            }
+            let mut outbox = Outbox::default();
+            slot.node.idle(&mut outbox);
+            if self.deliver(id, outbox) {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    /// Passes what `from` sent along its edges. Returns whether it asked to
+    /// stop.
+    fn deliver(&mut self, from: NodeId, outbox: Outbox) -> bool {
+        for (port, value) in outbox.sent {
+            for edge in &self.edges {
+                if edge.from == from && edge.from_port == port {
+                    let slot = &mut self.slots[edge.to];
+                    slot.node.receive(edge.to_port, value);
+                    slot.has_input = true;
+                }
+            }
+        }
+        outbox.stop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5],
+        vec![9,8,7,6,5] => 139629729; "day 7 example 4")]
+    #[test_case(vec![3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,
+        0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10], vec![9,7,8,5,6] => 18216; "day 7 example 5")]
+    fn amp_chain_feedback(program: Vec<isize>, settings: Vec<isize>) -> isize {
+        let mut network = Network::new();
+        let amps = settings
+            .into_iter()
+            .map(|setting| network.add(Machine::with_input(program.clone(), [setting])))
+            .collect::<Vec<_>>();
+        for (&from, &to) in amps.iter().zip(amps.iter().cycle().skip(1)) {
+            network.connect(from, to);
+        }
+        let thrusters = network.add(Sink::default());
+        network.connect(amps[amps.len() - 1], thrusters);
+        network.send(amps[0], 0, 0);
+
+        assert_eq!(network.run(), Ok(Outcome::Halted));
+        *network
+            .node::<Sink>(thrusters)
+            .unwrap()
+            .values
+            .last()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_deadlock() {
+        // Both wait for the other to say something first
+        let echo = vec![3, 5, 4, 5, 99, 0];
+        let mut network = Network::new();
+        let a = network.add(Machine::new(echo.clone()));
+        let b = network.add(Machine::new(echo));
+        network.connect(a, b);
+        network.connect(b, a);
+        assert_eq!(network.run(), Ok(Outcome::Deadlock(vec![a, b])));
+
+        // Until one of them hears from outside
+        network.send(a, 0, 42);
+        assert_eq!(network.run(), Ok(Outcome::Halted));
+        assert_eq!(network.node::<Machine>(a).unwrap().input(), &[42]);
+    }
+
+    #[test]
+    fn test_inputs_closed() {
+        let mut network = Network::new();
+        let source = network.add(Machine::new(vec![104, 1, 104, 2, 99]));
+        let starved = network.add(Machine::new(vec![3, 0, 3, 0, 3, 0, 99]));
+        let sink = network.add(Sink::default());
+        network.connect(source, starved);
+        network.connect(source, sink);
+        assert_eq!(network.run(), Ok(Outcome::Deadlock(vec![starved])));
+        assert_eq!(network.node::<Sink>(sink).unwrap().values, vec![1, 2]);
+        assert_eq!(network.state(sink), State::Halted);
+        assert!(network.node::<Sink>(starved).is_none());
+    }
+
+    #[test]
+    fn test_error() {
+        let mut network = Network::new();
+        let source = network.add(Machine::new(vec![104, 42, 99]));
+        let broken = network.add(Machine::new(vec![3, 5, 1105, 1, 5, 0]));
+        network.connect(source, broken);
+        assert_eq!(
+            network.run(),
+            Err(NodeError {
+                node: broken,
+                error: Error::InvalidInstruction { ip: 5, value: 42 }
+            })
+        );
+    }
+
+    /// Reads its address, then passes every packet `(x, y)` it gets on to the
+    /// next address as `(x, y + 1)`. The last one sends to 255 instead.
+    const RELAY: [isize; 42] = [
+        3, 100, 3, 101, 1008, 101, -1, 103, 1005, 103, 2, 3, 102, 1001, 100, 1, 104, 1007, 104, 50,
+        105, 1005, 105, 28, 1101, 0, 255, 104, 4, 104, 4, 101, 1001, 102, 1, 102, 4, 102, 1105, 1,
+        2, 99,
+    ];
+
+    /// Reassembles `(address, x, y)` from every interface and sends `(x, y)`
+    /// out on the port for that address.
+    #[derive(Default)]
+    struct Switch {
+        partial: Vec<Vec<isize>>,
+    }
+
+    impl Node for Switch {
+        fn receive(&mut self, port: usize, value: isize) {
+            if self.partial.len() <= port {
+                self.partial.resize(port + 1, Vec::new());
+            }
+            self.partial[port].push(value);
+        }
+
+        fn run(&mut self, outbox: &mut Outbox) -> Result<State, Error> {
+            for partial in &mut self.partial {
+                while partial.len() >= 3 {
+                    let packet = partial.drain(..3).collect::<Vec<_>>();
+                    outbox.send_to(packet[0] as usize, packet[1]);
+                    outbox.send_to(packet[0] as usize, packet[2]);
+                }
+            }
+            Ok(State::Blocked)
+        }
+    }
+
+    /// Holds on to the last packet for 255, and sends it to address 0 when
+    /// the network is idle. Stops the network on the idle after `wakeups`.
+    struct Nat {
+        packet: Vec<isize>,
+        delivered: Vec<isize>,
+        wakeups: usize,
+    }
+
+    impl Node for Nat {
+        fn receive(&mut self, _port: usize, value: isize) {
+            if self.packet.len() == 2 {
+                self.packet.clear();
+            }
+            self.packet.push(value);
+        }
+
+        fn run(&mut self, _outbox: &mut Outbox) -> Result<State, Error> {
+            Ok(State::Blocked)
+        }
+
+        fn idle(&mut self, outbox: &mut Outbox) {
+            if self.delivered.len() == self.wakeups {
+                outbox.stop();
+                return;
+            }
+            outbox.send(self.packet[0]);
+            outbox.send(self.packet[1]);
+            self.delivered.push(self.packet[1]);
+        }
+    }
+
+    #[test]
+    fn test_packet_network() {
+        let mut network = Network::new();
+        let switch = network.add(Switch::default());
+        let nat = network.add(Nat {
+            packet: Vec::new(),
+            delivered: Vec::new(),
+            wakeups: 2,
+        });
+        network.connect_ports(switch, 255, nat, 0);
+        let interfaces = (0..50)
+            .map(|address| {
+                let machine = Machine::with_input(RELAY.to_vec(), [address]);
+                let id = network.add(NonBlocking::new(machine, -1));
+                network.connect_ports(id, 0, switch, address as usize);
+                network.connect_ports(switch, address as usize, id, 0);
+                id
+            })
+            .collect::<Vec<_>>();
+        network.connect(nat, interfaces[0]);
+        network.send(interfaces[0], 0, 7);
+        network.send(interfaces[0], 0, 0);
+
+        assert_eq!(network.run(), Ok(Outcome::Stopped(nat)));
+        let nat = network.node::<Nat>(nat).unwrap();
+        assert_eq!(nat.delivered, vec![50, 100]);
+        assert_eq!(nat.packet, vec![7, 150]);
+        for id in interfaces {
+            assert_eq!(network.state(id), State::Idle);
+        }
+    }
+}
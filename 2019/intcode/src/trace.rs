@@ -0,0 +1,530 @@
+//! Recording everything a program does, and making sense of it afterwards.
+//!
+//! A [`Tracer`] runs a [`Machine`] and logs a [`Record`] for every instruction
+//! it executes, every value that instruction reads from or writes to memory,
+//! and every value it takes as input or writes as output. The log starts with
+//! the program as it was when tracing started, so it makes sense on its own.
+//!
+//! A [`Profile`] reads a log back and counts how often every address ran, was
+//! read and was written, and which loops went round the most. Its coverage
+//! map is the disassembly of the program with those counts next to it.
+//!
+//! A record is a tag byte followed by its numbers in LEB128. Signed numbers
+//! are zigzag encoded first, so small negative numbers stay small too.
+
+use crate::{
+    disasm::disassemble,
+    machine::{Machine, Status},
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Read, Write},
+};
+
+/// How many entries the lists in a [`Profile`] report show.
+pub const REPORT_LENGTH: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Record {
+    /// Memory when tracing started, always the first record
+    Program(Vec<isize>),
+    /// The instruction at `ip` ran, the records up to the next one are what
+    /// it did
+    Execute {
+        ip: usize,
+    },
+    Read {
+        address: usize,
+        value: isize,
+    },
+    Write {
+        address: usize,
+        value: isize,
+    },
+    Input(isize),
+    Output(isize),
+}
+
+impl Record {
+    /// Appends the record to `log`.
+    ///
+    /// # Errors
+    /// If writing fails.
+    pub fn write(&self, log: &mut impl Write) -> io::Result<()> {
+        match self {
+            Record::Program(memory) => {
+                log.write_all(&[0])?;
+                write_unsigned(log, memory.len() as u64)?;
+                memory
+                    .iter()
+                    .try_for_each(|&value| write_signed(log, value))
+            }
+            Record::Execute { ip } => {
+                log.write_all(&[1])?;
+                write_unsigned(log, *ip as u64)
+            }
+            Record::Read { address, value } => {
+                log.write_all(&[2])?;
+                write_unsigned(log, *address as u64)?;
+                write_signed(log, *value)
+            }
+            Record::Write { address, value } => {
+                log.write_all(&[3])?;
+                write_unsigned(log, *address as u64)?;
+                write_signed(log, *value)
+            }
+            Record::Input(value) => {
+                log.write_all(&[4])?;
+                write_signed(log, *value)
+            }
+            Record::Output(value) => {
+                log.write_all(&[5])?;
+                write_signed(log, *value)
+            }
+        }
+    }
+
+    /// Reads the next record from `log`, or `None` at the end of it.
+    ///
+    /// # Errors
+    /// If reading fails, or `log` ends in the middle of a record or holds
+    /// something that isn't one.
+    pub fn read(log: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut tag = [0];
+        match log.read_exact(&mut tag) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let record = match tag[0] {
+            0 => Record::Program(
+                (0..read_usize(log)?)
+                    .map(|_| read_signed(log))
+                    .collect::<io::Result<_>>()?,
+            ),
+            1 => Record::Execute {
+                ip: read_usize(log)?,
+            },
+            2 => Record::Read {
+                address: read_usize(log)?,
+                value: read_signed(log)?,
+            },
+            3 => Record::Write {
+                address: read_usize(log)?,
+                value: read_signed(log)?,
+            },
+            4 => Record::Input(read_signed(log)?),
+            5 => Record::Output(read_signed(log)?),
+            tag => return Err(invalid_data(format!("unknown record tag {tag}"))),
+        };
+        Ok(Some(record))
+    }
+}
+
+fn write_unsigned(log: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return log.write_all(&[byte]);
+        }
+        log.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn write_signed(log: &mut impl Write, value: isize) -> io::Result<()> {
+    let value = value as i64;
+    write_unsigned(log, ((value << 1) ^ (value >> 63)) as u64)
+}
+
+fn read_unsigned(log: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        log.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("number too long".to_string()))
+}
+
+fn read_usize(log: &mut impl Read) -> io::Result<usize> {
+    let value = read_unsigned(log)?;
+    usize::try_from(value).map_err(|_| invalid_data(format!("{value} is too big")))
+}
+
+fn read_signed(log: &mut impl Read) -> io::Result<isize> {
+    let value = read_unsigned(log)?;
+    let value = (value >> 1) as i64 ^ -((value & 1) as i64);
+    isize::try_from(value).map_err(|_| invalid_data(format!("{value} is too big")))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A machine that logs what it does. The log gets written a few bytes at a
+/// time, so it had better be buffered.
+#[derive(Debug)]
+pub struct Tracer<W> {
+    machine: Machine,
+    log: W,
+}
+
+impl<W: Write> Tracer<W> {
+    /// Starts the log with the program as it is in `machine` now.
+    ///
+    /// # Errors
+    /// If writing to the log fails.
+    pub fn new(machine: Machine, mut log: W) -> io::Result<Self> {
+        Record::Program(machine.memory().to_vec()).write(&mut log)?;
+        Ok(Self { machine, log })
+    }
+
+    #[must_use]
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// The machine itself, for queueing input and taking output. Changes
+    /// made to memory through here aren't logged.
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    pub fn into_log(self) -> W {
+        self.log
+    }
+
+    /// Executes one instruction like [`Machine::step`] and logs what it did.
+    /// Nothing is logged for a read that has to wait for input, or for an
+    /// instruction that fails.
+    ///
+    /// # Errors
+    /// If writing to the log fails, or the program does something wrong, in
+    /// which case the error wraps the machine's [`crate::Error`].
+    pub fn step(&mut self) -> io::Result<Status> {
+        let machine = &self.machine;
+        let mut records = vec![Record::Execute { ip: machine.ip() }];
+        let mut target = None;
+        if let Ok(instruction) = machine.decode() {
+            for index in 0..instruction.opcode.params() {
+                // Immediate parameters don't touch memory, anything else that
+                // has no address makes the step fail
+                let Ok(address) = machine.address(instruction, index) else {
+                    continue;
+                };
+                if instruction.opcode.written_param() == Some(index) {
+                    target = Some(address);
+                } else {
+                    let value = machine.read(address);
+                    records.push(Record::Read { address, value });
+                }
+            }
+        }
+        let (inputs, next_input) = (machine.input().len(), machine.input().front().copied());
+        let outputs = machine.output().len();
+
+        let status = self.machine.step().map_err(io::Error::other)?;
+        if status == Status::NeedInput {
+            return Ok(status);
+        }
+        if let Some(value) = next_input.filter(|_| self.machine.input().len() < inputs) {
+            records.push(Record::Input(value));
+        }
+        if let Some(address) = target {
+            let value = self.machine.read(address);
+            records.push(Record::Write { address, value });
+        }
+        if self.machine.output().len() > outputs {
+            if let Some(&value) = self.machine.output().back() {
+                records.push(Record::Output(value));
+            }
+        }
+        for record in records {
+            record.write(&mut self.log)?;
+        }
+        Ok(status)
+    }
+
+    /// Runs until the program wants input that isn't there, or exits, see
+    /// [`Machine::run_until_input`].
+    ///
+    /// # Errors
+    /// See [`Tracer::step`].
+    pub fn run_until_input(&mut self) -> io::Result<Status> {
+        loop {
+            match self.step()? {
+                Status::Running => {}
+                status => return Ok(status),
+            }
+        }
+    }
+}
+
+/// What a log says about where a program spends its time.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// Memory when tracing started
+    pub program: Vec<isize>,
+    /// How many times the instruction at every address ran
+    pub hits: BTreeMap<usize, usize>,
+    /// How many times every address was read by an instruction
+    pub reads: BTreeMap<usize, usize>,
+    /// How many times every address was written by an instruction
+    pub writes: BTreeMap<usize, usize>,
+    /// How many times execution went back from the end of a loop to its
+    /// start, by start and end address
+    pub loops: BTreeMap<(usize, usize), usize>,
+    pub inputs: usize,
+    pub outputs: usize,
+}
+
+impl Profile {
+    /// Counts everything in `log`, which had better be buffered.
+    ///
+    /// # Errors
+    /// If the log can't be read, see [`Record::read`].
+    pub fn read(mut log: impl Read) -> io::Result<Self> {
+        let mut profile = Profile::default();
+        let mut last_ip = None;
+        while let Some(record) = Record::read(&mut log)? {
+            match record {
+                Record::Program(program) => profile.program = program,
+                Record::Execute { ip } => {
+                    *profile.hits.entry(ip).or_default() += 1;
+                    if let Some(last_ip) = last_ip.replace(ip) {
+                        if ip <= last_ip {
+                            *profile.loops.entry((ip, last_ip)).or_default() += 1;
+                        }
+                    }
+                }
+                Record::Read { address, .. } => *profile.reads.entry(address).or_default() += 1,
+                Record::Write { address, .. } => *profile.writes.entry(address).or_default() += 1,
+                Record::Input(_) => profile.inputs += 1,
+                Record::Output(_) => profile.outputs += 1,
+            }
+        }
+        Ok(profile)
+    }
+
+    /// How many instructions ran in total.
+    #[must_use]
+    pub fn executed(&self) -> usize {
+        self.hits.values().sum()
+    }
+
+    /// Loops as start and end address, most often repeated first.
+    #[must_use]
+    pub fn hot_loops(&self) -> Vec<((usize, usize), usize)> {
+        most_first(&self.loops)
+    }
+
+    /// The disassembly of the program with how many times every line ran in
+    /// front of it, `-` for never. Code that the disassembler took for data
+    /// shows up as data lines that ran.
+    #[must_use]
+    pub fn coverage(&self) -> String {
+        disassemble(&self.program).annotated(10, |item| {
+            let hits = (item.address()..item.address() + item.size())
+                .filter_map(|address| self.hits.get(&address))
+                .sum::<usize>();
+            if hits == 0 {
+                "-".to_string()
+            } else {
+                hits.to_string()
+            }
+        })
+    }
+}
+
+/// Entries with the highest counts first, then by key.
+fn most_first<K: Copy + Ord>(counts: &BTreeMap<K, usize>) -> Vec<(K, usize)> {
+    let mut counts = counts
+        .iter()
+        .map(|(&key, &count)| (key, count))
+        .collect::<Vec<_>>();
+    counts.sort_by_key(|&(key, count)| (std::cmp::Reverse(count), key));
+    counts
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} instructions, {} inputs, {} outputs",
+            self.executed(),
+            self.inputs,
+            self.outputs
+        )?;
+        for (title, counts) in [
+            ("most run", &self.hits),
+            ("most read", &self.reads),
+            ("most written", &self.writes),
+        ] {
+            writeln!(f, "\n{title}:")?;
+            for (address, count) in most_first(counts).into_iter().take(REPORT_LENGTH) {
+                writeln!(f, "{count:>10}  at {address}")?;
+            }
+        }
+        writeln!(f, "\nhottest loops:")?;
+        for ((start, end), count) in self.hot_loops().into_iter().take(REPORT_LENGTH) {
+            writeln!(f, "{count:>10}  from {start} to {end}")?;
+        }
+        write!(f, "\ncoverage:\n{}", self.coverage())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::assemble, machine::Error};
+
+    const COUNTDOWN: &str = "
+                in   [n]
+        loop:   out  [n]
+                add  [n], #-1, [n]
+                jnz  [n], #loop
+                hlt
+        n:      data 0";
+
+    fn trace(input: isize) -> Vec<u8> {
+        let machine = Machine::with_input(assemble(COUNTDOWN).unwrap(), [input]);
+        let mut tracer = Tracer::new(machine, Vec::new()).unwrap();
+        assert_eq!(tracer.run_until_input().unwrap(), Status::Halted);
+        tracer.into_log()
+    }
+
+    #[test]
+    fn test_records() {
+        let records = [
+            Record::Program(vec![isize::MIN, -1, 0, 1, isize::MAX]),
+            Record::Execute { ip: usize::MAX },
+            Record::Read {
+                address: 127,
+                value: -64,
+            },
+            Record::Write {
+                address: 128,
+                value: 64,
+            },
+            Record::Input(-65),
+            Record::Output(1 << 40),
+        ];
+        let mut log = Vec::new();
+        for record in &records {
+            record.write(&mut log).unwrap();
+        }
+        let mut reader = log.as_slice();
+        for record in records {
+            assert_eq!(Record::read(&mut reader).unwrap(), Some(record));
+        }
+        assert_eq!(Record::read(&mut reader).unwrap(), None);
+
+        log.pop();
+        let mut reader = log.as_slice();
+        let error = std::iter::from_fn(|| Record::read(&mut reader).transpose())
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let error = Record::read(&mut [6].as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_tracer() {
+        let log = trace(1);
+        let mut reader = log.as_slice();
+        let records = std::iter::from_fn(|| Record::read(&mut reader).unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            [
+                Record::Program(assemble(COUNTDOWN).unwrap()),
+                Record::Execute { ip: 0 },
+                Record::Input(1),
+                Record::Write {
+                    address: 12,
+                    value: 1
+                },
+                Record::Execute { ip: 2 },
+                Record::Read {
+                    address: 12,
+                    value: 1
+                },
+                Record::Output(1),
+                Record::Execute { ip: 4 },
+                Record::Read {
+                    address: 12,
+                    value: 1
+                },
+                Record::Write {
+                    address: 12,
+                    value: 0
+                },
+                Record::Execute { ip: 8 },
+                Record::Read {
+                    address: 12,
+                    value: 0
+                },
+                Record::Execute { ip: 11 },
+            ]
+        );
+
+        let mut tracer = Tracer::new(Machine::new(vec![3, 0, 42]), Vec::new()).unwrap();
+        assert_eq!(tracer.step().unwrap(), Status::NeedInput);
+        tracer.machine_mut().push_input(7);
+        assert_eq!(tracer.step().unwrap(), Status::Running);
+        let error = tracer.step().unwrap_err();
+        let error = error.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert_eq!(*error, Error::InvalidInstruction { ip: 2, value: 42 });
+        let records = [
+            Record::Program(vec![3, 0, 42]),
+            Record::Execute { ip: 0 },
+            Record::Input(7),
+            Record::Write {
+                address: 0,
+                value: 7,
+            },
+        ];
+        let mut log = Vec::new();
+        for record in records {
+            record.write(&mut log).unwrap();
+        }
+        assert_eq!(tracer.into_log(), log);
+    }
+
+    #[test]
+    fn test_profile() {
+        let profile = Profile::read(trace(3).as_slice()).unwrap();
+        assert_eq!(profile.executed(), 1 + 3 * 3 + 1);
+        assert_eq!(profile.hits[&2], 3);
+        assert_eq!(profile.reads[&12], 9);
+        assert_eq!(profile.writes[&12], 4);
+        assert_eq!(profile.hot_loops(), [((2, 8), 2)]);
+        assert_eq!((profile.inputs, profile.outputs), (1, 3));
+        assert_eq!(
+            profile.coverage(),
+            "         1     0  in   [12]
+          L2:
+         3     2  out  [12]
+         3     4  add  [12], #-1, [12]
+         3     8  jnz  [12], #L2
+         1    11  hlt
+         -    12  data 0
+"
+        );
+        assert!(profile.to_string().starts_with(
+            "11 instructions, 1 inputs, 3 outputs
+
+most run:
+         3  at 2
+         3  at 4
+         3  at 8
+         1  at 0
+         1  at 11
+"
+        ));
+    }
+}
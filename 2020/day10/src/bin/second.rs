@@ -1,4 +1,5 @@
 use anyhow::Result;
+use aoc_common::memo::Memo;
 use std::fs;
 
 fn do_the_thing(input: &str) -> Result<usize> {
@@ -9,25 +10,17 @@ fn do_the_thing(input: &str) -> Result<usize> {
     joltages.push(0);
     joltages.sort();
     joltages.push(joltages.last().unwrap() + 3);
-    let mut options = joltages.into_iter().fold(
-        Vec::<(usize, usize)>::new(),
-        |mut options, current| match current {
-            0 => {
-                options.push((0, 1));
-                options
-            }
-            _ => {
-                let mut options: Vec<_> = options
-                    .into_iter()
-                    .filter(|(value, _)| *value + 3 >= current)
-                    .collect();
-                let alternatives = options.iter().map(|(_, alternatives)| *alternatives).sum();
-                options.push((current, alternatives));
-                options
-            }
-        },
-    );
-    Ok(options.pop().unwrap().1)
+    // Ways to get from the adapter at `index` to the device
+    let arrangements = |memo: &mut Memo<usize, usize>, index: usize| {
+        if index == joltages.len() - 1 {
+            return 1;
+        }
+        (index + 1..joltages.len())
+            .take_while(|&next| joltages[next] <= joltages[index] + 3)
+            .map(|next| memo.get(next))
+            .sum()
+    };
+    Ok(Memo::new(&arrangements).get(0))
 }
 
 fn main() -> Result<()> {
@@ -1,59 +1,49 @@
 use anyhow::Result;
-use std::{cmp::min, fs, thread::sleep, time::Duration};
+use aoc_common::automaton::{Automaton, Dense, Moore, Rule};
+use std::{fs, thread::sleep, time::Duration};
 
 #[derive(Debug, PartialEq)]
 struct SeatMap(Vec<Vec<char>>);
 
 impl SeatMap {
-    fn occupied_around(&self, x: usize, y: usize) -> usize {
-        let min_x = if x == 0 { 0 } else { x - 1 };
-        let min_y = if y == 0 { 0 } else { y - 1 };
-        let max_x = min(x + 1, self.0[0].len() - 1);
-        let max_y = min(y + 1, self.0.len() - 1);
-        self.0[min_y..=max_y]
-            .iter()
-            .map(|r| r[min_x..=max_x].iter().filter(|&&s| s == '#').count())
-            .sum::<usize>()
-            - if self.0[y][x] == '#' { 1 } else { 0 }
-    }
-
     fn print(&self) {
         for r in &self.0 {
             println!("{}", r.iter().collect::<String>());
         }
     }
-}
 
-fn apply_rules(input: &SeatMap) -> SeatMap {
-    SeatMap(
-        input
-            .0
+    fn seats(&self) -> Vec<(usize, usize)> {
+        self.0
             .iter()
             .enumerate()
-            .map(|(y, row)| {
+            .flat_map(|(y, row)| {
                 row.iter()
                     .enumerate()
-                    .map(|(x, &seat)| match seat {
-                        'L' => {
-                            if input.occupied_around(x, y) == 0 {
-                                '#'
-                            } else {
-                                'L'
-                            }
-                        }
-                        '#' => {
-                            if input.occupied_around(x, y) >= 4 {
-                                'L'
-                            } else {
-                                '#'
-                            }
-                        }
-                        _ => '.',
-                    })
-                    .collect()
+                    .filter(|(_, &seat)| seat != '.')
+                    .map(move |(x, _)| (x, y))
             })
-            .collect(),
-    )
+            .collect()
+    }
+
+    /// Each seat is a cell, occupied seats are alive
+    fn automaton(&self) -> Dense {
+        let cells = self
+            .seats()
+            .into_iter()
+            .map(|(x, y)| ([x as isize, y as isize], self.0[y][x] == '#'))
+            .collect::<Vec<_>>();
+        // Empty seats get filled when there's nobody around, occupied seats
+        // are left when 4 or more neighbours are occupied
+        Dense::new(&cells, &Moore, Rule::new(&[0], &[0, 1, 2, 3]))
+    }
+
+    fn with_occupied(&self, automaton: &Dense) -> SeatMap {
+        let mut map = self.0.clone();
+        for ((x, y), &occupied) in self.seats().into_iter().zip(automaton.alive()) {
+            map[y][x] = if occupied { '#' } else { 'L' };
+        }
+        SeatMap(map)
+    }
 }
 
 fn parse_seatmap(input: &str) -> SeatMap {
@@ -66,19 +56,14 @@ fn parse_seatmap(input: &str) -> SeatMap {
 }
 
 fn do_the_thing(input: &str) -> usize {
-    let mut map = parse_seatmap(input);
-    while {
-        let oldmap = map;
-        map = apply_rules(&oldmap);
-        map.print();
+    let map = parse_seatmap(input);
+    let mut automaton = map.automaton();
+    while automaton.step() {
+        map.with_occupied(&automaton).print();
         println!();
         sleep(Duration::from_millis(100));
-        map != oldmap
-    } {}
-    map.0
-        .into_iter()
-        .map(|r| r.into_iter().filter(|&s| s == '#').count())
-        .sum()
+    }
+    automaton.population()
 }
 
 fn main() -> Result<()> {
@@ -127,21 +112,33 @@ L.L.L..L..
 
     #[test]
     fn test_rules() {
-        let map1 = parse_seatmap(EXAMPLE_INITIAL);
-        let map2 = parse_seatmap(EXAMPLE_FIRST_ITERATION);
-        let map3 = parse_seatmap(EXAMPLE_SECOND_ITERATION);
-        assert_eq!(apply_rules(&map1), map2);
-        assert_eq!(apply_rules(&map2), map3);
+        let map = parse_seatmap(EXAMPLE_INITIAL);
+        let mut automaton = map.automaton();
+        automaton.step();
+        assert_eq!(
+            map.with_occupied(&automaton),
+            parse_seatmap(EXAMPLE_FIRST_ITERATION)
+        );
+        automaton.step();
+        assert_eq!(
+            map.with_occupied(&automaton),
+            parse_seatmap(EXAMPLE_SECOND_ITERATION)
+        );
+    }
+
+    fn occupied_around(map: &SeatMap, x: usize, y: usize) -> usize {
+        let seat = map.seats().iter().position(|&seat| seat == (x, y));
+        map.automaton().live_neighbors(seat.unwrap())
     }
 
     #[test]
     fn test_occupied() {
         let map1 = parse_seatmap(EXAMPLE_INITIAL);
         let map2 = parse_seatmap(EXAMPLE_FIRST_ITERATION);
-        assert_eq!(map1.occupied_around(0, 0), 0);
-        assert_eq!(map2.occupied_around(0, 0), 2);
-        assert_eq!(map2.occupied_around(9, 9), 2);
-        assert_eq!(map2.occupied_around(4, 8), 8);
+        assert_eq!(occupied_around(&map1, 0, 0), 0);
+        assert_eq!(occupied_around(&map2, 0, 0), 2);
+        assert_eq!(occupied_around(&map2, 9, 9), 2);
+        assert_eq!(occupied_around(&map2, 4, 8), 8);
     }
 
     #[test]
@@ -1,12 +1,19 @@
 use anyhow::Result;
+use aoc_common::automaton::{Automaton, Dense, Rule};
 use itertools::Itertools;
-use std::{fs, thread::sleep, time::Duration};
+use std::{collections::HashMap, fs, thread::sleep, time::Duration};
 
 #[derive(Debug, PartialEq)]
 struct SeatMap(Vec<Vec<char>>);
 
 impl SeatMap {
-    fn occupied_in_direction(&self, x: usize, y: usize, x_offset: isize, y_offset: isize) -> bool {
+    fn seat_in_direction(
+        &self,
+        x: usize,
+        y: usize,
+        x_offset: isize,
+        y_offset: isize,
+    ) -> Option<(usize, usize)> {
         let mut x = x as isize;
         let mut y = y as isize;
         let width = self.0[0].len() as isize;
@@ -15,26 +22,22 @@ impl SeatMap {
             x += x_offset;
             y += y_offset;
             if x < 0 || x >= width || y < 0 || y >= height {
-                return false;
+                return None;
             }
-            match self.0[y as usize][x as usize] {
-                'L' => return false,
-                '#' => return true,
-                _ => (),
+            if self.0[y as usize][x as usize] != '.' {
+                return Some((x as usize, y as usize));
             }
         }
     }
 
-    fn occupied_around(&self, x: usize, y: usize) -> usize {
-        let directions: Vec<(isize, isize)> = vec![-1, 0, 1]
+    fn visible_seats(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        vec![-1, 0, 1]
             .into_iter()
             .cartesian_product(vec![-1, 0, 1])
             .filter(|d| *d != (0, 0))
-            .collect();
-        directions
-            .into_iter()
-            .filter(|(x_offset, y_offset)| self.occupied_in_direction(x, y, *x_offset, *y_offset))
-            .count()
+            .filter_map(move |(x_offset, y_offset)| {
+                self.seat_in_direction(x, y, x_offset, y_offset)
+            })
     }
 
     fn print(&self) {
@@ -42,38 +45,46 @@ impl SeatMap {
             println!("{}", r.iter().collect::<String>());
         }
     }
-}
 
-fn apply_rules(input: &SeatMap) -> SeatMap {
-    SeatMap(
-        input
-            .0
+    fn seats(&self) -> Vec<(usize, usize)> {
+        self.0
             .iter()
             .enumerate()
-            .map(|(y, row)| {
+            .flat_map(|(y, row)| {
                 row.iter()
                     .enumerate()
-                    .map(|(x, &seat)| match seat {
-                        'L' => {
-                            if input.occupied_around(x, y) == 0 {
-                                '#'
-                            } else {
-                                'L'
-                            }
-                        }
-                        '#' => {
-                            if input.occupied_around(x, y) >= 5 {
-                                'L'
-                            } else {
-                                '#'
-                            }
-                        }
-                        _ => '.',
-                    })
-                    .collect()
+                    .filter(|(_, &seat)| seat != '.')
+                    .map(move |(x, _)| (x, y))
             })
-            .collect(),
-    )
+            .collect()
+    }
+
+    /// Each seat is a cell, occupied seats are alive, and the neighbours are
+    /// the first seats visible in each direction
+    fn automaton(&self) -> Dense {
+        let seats = self.seats();
+        let index = seats
+            .iter()
+            .enumerate()
+            .map(|(index, &seat)| (seat, index))
+            .collect::<HashMap<_, _>>();
+        let neighbors = seats
+            .iter()
+            .map(|&(x, y)| self.visible_seats(x, y).map(|seat| index[&seat]).collect())
+            .collect();
+        let occupied = seats.iter().map(|&(x, y)| self.0[y][x] == '#').collect();
+        // Empty seats get filled when there's nobody in sight, occupied seats
+        // are left when 5 or more visible seats are occupied
+        Dense::from_graph(occupied, neighbors, Rule::new(&[0], &[0, 1, 2, 3, 4]))
+    }
+
+    fn with_occupied(&self, automaton: &Dense) -> SeatMap {
+        let mut map = self.0.clone();
+        for ((x, y), &occupied) in self.seats().into_iter().zip(automaton.alive()) {
+            map[y][x] = if occupied { '#' } else { 'L' };
+        }
+        SeatMap(map)
+    }
 }
 
 fn parse_seatmap(input: &str) -> SeatMap {
@@ -86,19 +97,14 @@ fn parse_seatmap(input: &str) -> SeatMap {
 }
 
 fn do_the_thing(input: &str) -> usize {
-    let mut map = parse_seatmap(input);
-    while {
-        let oldmap = map;
-        map = apply_rules(&oldmap);
-        map.print();
+    let map = parse_seatmap(input);
+    let mut automaton = map.automaton();
+    while automaton.step() {
+        map.with_occupied(&automaton).print();
         println!();
         sleep(Duration::from_millis(100));
-        map != oldmap
-    } {}
-    map.0
-        .into_iter()
-        .map(|r| r.into_iter().filter(|&s| s == '#').count())
-        .sum()
+    }
+    automaton.population()
 }
 
 fn main() -> Result<()> {
@@ -147,21 +153,33 @@ LLLLLLLLL#
 
     #[test]
     fn test_rules() {
-        let map1 = parse_seatmap(EXAMPLE_INITIAL);
-        let map2 = parse_seatmap(EXAMPLE_FIRST_ITERATION);
-        let map3 = parse_seatmap(EXAMPLE_SECOND_ITERATION);
-        assert_eq!(apply_rules(&map1), map2);
-        assert_eq!(apply_rules(&map2), map3);
+        let map = parse_seatmap(EXAMPLE_INITIAL);
+        let mut automaton = map.automaton();
+        automaton.step();
+        assert_eq!(
+            map.with_occupied(&automaton),
+            parse_seatmap(EXAMPLE_FIRST_ITERATION)
+        );
+        automaton.step();
+        assert_eq!(
+            map.with_occupied(&automaton),
+            parse_seatmap(EXAMPLE_SECOND_ITERATION)
+        );
+    }
+
+    fn occupied_around(map: &SeatMap, x: usize, y: usize) -> usize {
+        let seat = map.seats().iter().position(|&seat| seat == (x, y));
+        map.automaton().live_neighbors(seat.unwrap())
     }
 
     #[test]
     fn test_occupied() {
         let map1 = parse_seatmap(EXAMPLE_INITIAL);
         let map2 = parse_seatmap(EXAMPLE_FIRST_ITERATION);
-        assert_eq!(map1.occupied_around(0, 0), 0);
-        assert_eq!(map2.occupied_around(0, 0), 3);
-        assert_eq!(map2.occupied_around(9, 9), 3);
-        assert_eq!(map2.occupied_around(4, 6), 7);
+        assert_eq!(occupied_around(&map1, 0, 0), 0);
+        assert_eq!(occupied_around(&map2, 0, 0), 3);
+        assert_eq!(occupied_around(&map2, 9, 9), 3);
+        assert_eq!(occupied_around(&map2, 4, 6), 7);
     }
 
     #[test]
@@ -1,27 +1,10 @@
 use anyhow::Result;
-use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    combinator::{iterator, value},
-    IResult,
-};
+use aoc_common::hex::{self, Hex};
 use std::{collections::HashSet, fs};
 
-fn parse_direction(input: &str) -> IResult<&str, (isize, isize)> {
-    alt((
-        value((2, 0), tag("e")),
-        value((1, -1), tag("ne")),
-        value((1, 1), tag("se")),
-        value((-2, 0), tag("w")),
-        value((-1, -1), tag("nw")),
-        value((-1, 1), tag("sw")),
-    ))(input)
-}
-
-fn walk(input: &str) -> (isize, isize) {
-    iterator(input, parse_direction).fold((0, 0), |(x, y), (x_offset, y_offset)| {
-        (x + x_offset, y + y_offset)
-    })
+fn walk(input: &str) -> Hex {
+    let (_, directions) = hex::path(input).unwrap();
+    Hex::ORIGIN.walk(directions)
 }
 fn do_the_thing(input: &str) -> usize {
     let black_tiles = input
@@ -73,7 +56,7 @@ wseweeenwnesenwwwswnew" => 10)]
     }
     #[test]
     fn walker() {
-        assert_eq!(walk("esew"), (1, 1));
-        assert_eq!(walk("nwwswee"), (0, 0));
+        assert_eq!(walk("esew"), Hex::new(0, 1));
+        assert_eq!(walk("nwwswee"), Hex::ORIGIN);
     }
 }
@@ -1,3 +1,4 @@
+use aoc_common::ocr;
 use std::fs;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -87,7 +88,9 @@ fn do_the_thing(input: &str) -> String {
 
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
-    println!("{}", do_the_thing(&input));
+    let picture = do_the_thing(&input);
+    println!("{picture}");
+    println!("{}", ocr::recognize_str(&picture).expect("unreadable letters"));
 }
 
 #[cfg(test)]
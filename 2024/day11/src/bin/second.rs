@@ -1,5 +1,6 @@
 #![feature(test)]
 
+use aoc_common::memo::Memo;
 use nom::{
     character::complete::{char, line_ending, u64},
     combinator::eof,
@@ -7,7 +8,7 @@ use nom::{
     sequence::{terminated, tuple},
     IResult,
 };
-use std::{collections::HashMap, fs};
+use std::fs;
 
 fn parse_u64_list(input: &str) -> IResult<&str, Vec<u64>> {
     terminated(separated_list1(char(' '), u64), tuple((line_ending, eof)))(input)
@@ -18,32 +19,27 @@ fn parse_input(input: &str) -> Vec<u64> {
     result
 }
 
-fn solve_stone(cache: &mut HashMap<(u64, usize), usize>, stone: u64, blinks: usize) -> usize {
+fn solve_stone(memo: &mut Memo<(u64, usize), usize>, (stone, blinks): (u64, usize)) -> usize {
     if blinks == 0 {
         return 1;
     }
-    if let Some(&result) = cache.get(&(stone, blinks)) {
-        return result;
-    }
-    let result = if stone == 0 {
-        solve_stone(cache, 1, blinks - 1)
+    if stone == 0 {
+        memo.get((1, blinks - 1))
     } else {
         let digits = stone.ilog10() + 1;
         if digits % 2 == 0 {
             let half = digits / 2;
             let divider = 10u64.pow(half);
-            solve_stone(cache, stone / divider, blinks - 1) + solve_stone(cache, stone % divider, blinks - 1)
+            memo.get((stone / divider, blinks - 1)) + memo.get((stone % divider, blinks - 1))
         } else {
-            solve_stone(cache, stone * 2024, blinks - 1)
+            memo.get((stone * 2024, blinks - 1))
         }
-    };
-    cache.insert((stone, blinks), result);
-    result
+    }
 }
 
 fn solve(input: &[u64], blinks: usize) -> usize {
-    let mut cache = HashMap::new();
-    input.iter().map(|&stone| solve_stone(&mut cache, stone, blinks)).sum()
+    let mut memo = Memo::new(&solve_stone);
+    input.iter().map(|&stone| memo.get((stone, blinks))).sum()
 }
 
 #[must_use]
@@ -87,5 +83,5 @@ mod tests {
     #[test_case(&[125, 17], 25 => 55312; "example 2")]
     fn test_solve(input: &[u64], blinks: usize) -> usize {
         solve(input, blinks)
-    } 
+    }
 }
@@ -1,9 +1,12 @@
 #![feature(test)]
 
+use aoc_common::parse::{key_value, parse_all, unsigned, ParseError};
 use nom::{
     bytes::complete::tag,
-    character::complete::{char as nom_char, line_ending, u64 as nom_u64, u8},
+    character::complete::{char as nom_char, line_ending},
+    combinator::map,
     multi::separated_list1,
+    sequence::{pair, separated_pair, terminated, tuple},
     IResult,
 };
 use std::fs;
@@ -15,32 +18,27 @@ struct Registers {
     c: u64,
 }
 
-fn parse_input(input: &str) -> IResult<&str, (Registers, Vec<u8>)> {
-    let (input, _) = tag("Register A: ")(input)?;
-    let (input, register_a) = nom_u64(input)?;
-    let (input, _) = line_ending(input)?;
-    let (input, _) = tag("Register B: ")(input)?;
-    let (input, register_b) = nom_u64(input)?;
-    let (input, _) = line_ending(input)?;
-    let (input, _) = tag("Register C: ")(input)?;
-    let (input, register_c) = nom_u64(input)?;
-    let (input, _) = line_ending(input)?;
-    let (input, _) = line_ending(input)?;
-    let (input, _) = tag("Program: ")(input)?;
-    let (input, program) = separated_list1(nom_char(','), u8)(input)?;
-    let (input, _) = line_ending(input)?;
-    assert_eq!(input, "");
-    Ok((
+fn parse_field<'a, O>(
+    name: &'a str,
+    value: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    map(key_value(tag(name), ": ", value), |(_, value)| value)
+}
+
+fn parse_input(input: &str) -> Result<(Registers, Vec<u8>), ParseError> {
+    let registers = map(
+        tuple((
+            terminated(parse_field("Register A", unsigned), line_ending),
+            terminated(parse_field("Register B", unsigned), line_ending),
+            parse_field("Register C", unsigned),
+        )),
+        |(a, b, c)| Registers { a, b, c },
+    );
+    let program = parse_field("Program", separated_list1(nom_char(','), unsigned));
+    parse_all(
+        separated_pair(registers, pair(line_ending, line_ending), program),
         input,
-        (
-            Registers {
-                a: register_a,
-                b: register_b,
-                c: register_c,
-            },
-            program,
-        ),
-    ))
+    )
 }
 
 fn get_combo(operand: u8, registers: &Registers) -> u64 {
@@ -121,8 +119,7 @@ fn solve(registers: &mut Registers, program: &[u8]) -> String {
 #[allow(clippy::missing_panics_doc)]
 #[must_use]
 pub fn run(input: &str) -> String {
-    let (input, (mut registers, program)) = parse_input(input).unwrap();
-    assert_eq!(input, "");
+    let (mut registers, program) = parse_input(input).unwrap();
     solve(&mut registers, &program)
 }
 
@@ -148,8 +145,7 @@ mod tests {
     #[bench]
     fn bench_solve(b: &mut Bencher) {
         let input = fs::read_to_string("input.txt").unwrap();
-        let (input, (registers, program)) = parse_input(&input).unwrap();
-        assert_eq!(input, "");
+        let (registers, program) = parse_input(&input).unwrap();
         let registers = black_box(&registers);
         let program = black_box(&program);
         b.iter(|| {
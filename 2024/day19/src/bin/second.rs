@@ -1,6 +1,7 @@
 #![feature(test)]
 
-use std::{collections::HashMap, fs};
+use aoc_common::memo::Memo;
+use std::fs;
 
 fn parse_input(input: &str) -> (Vec<&str>, Vec<&str>) {
     let (towels, designs) = input.split_once("\n\n").unwrap();
@@ -9,37 +10,26 @@ fn parse_input(input: &str) -> (Vec<&str>, Vec<&str>) {
     (towels, designs)
 }
 
-fn arrangements<'a>(
-    towels: &[&str],
-    design: &'a str,
-    cache: &mut HashMap<&'a str, usize>,
-) -> usize {
+fn arrangements<'a>(towels: &[&str], memo: &mut Memo<&'a str, usize>, design: &'a str) -> usize {
     if design.is_empty() {
         return 1;
     }
-    if let Some(&count) = cache.get(design) {
-        return count;
-    }
-    let count = towels
+    towels
         .iter()
         .filter_map(|&towel| {
             if design.starts_with(towel) {
-                Some(arrangements(towels, &design[towel.len()..], cache))
+                Some(memo.get(&design[towel.len()..]))
             } else {
                 None
             }
         })
-        .sum();
-    cache.insert(design, count);
-    count
+        .sum()
 }
 
 fn solve(towels: &[&str], designs: &[&str]) -> usize {
-    let mut cache = HashMap::new();
-    designs
-        .iter()
-        .map(|&design| arrangements(towels, design, &mut cache))
-        .sum()
+    let with_towels = |memo: &mut Memo<_, _>, design| arrangements(towels, memo, design);
+    let mut memo = Memo::new(&with_towels);
+    designs.iter().map(|&design| memo.get(design)).sum()
 }
 
 #[allow(clippy::missing_panics_doc)]
@@ -99,6 +89,6 @@ bbrgwb
     #[test_case(TOWELS, "bwurrg" => 1; "example 5")]
     #[test_case(TOWELS, "brgr" => 2; "example 6")]
     fn test_single_design(towels: &[&str], design: &str) -> usize {
-        arrangements(towels, design, &mut HashMap::new())
+        Memo::new(&|memo, design| arrangements(towels, memo, design)).get(design)
     }
 }
@@ -0,0 +1,416 @@
+//! Life-like cellular automata: a cell is born or survives depending on how
+//! many of its neighbours are alive.
+//!
+//! [`Sparse`] keeps a set of live cells on an unbounded grid of any coordinate
+//! type, with the neighbours coming from a [`Neighborhood`]. It fits puzzles
+//! where the live cells spread out, like 2020 day 17 and day 24. [`Dense`]
+//! works on a fixed set of cells with arbitrary neighbour lists, which also
+//! covers things like the line-of-sight seats in 2020 day 11.
+//!
+//! Both step all cells in parallel.
+
+use crate::hex::Hex;
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// Which neighbour counts make a dead cell come alive, and which keep a live
+/// cell alive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    birth: u64,
+    survival: u64,
+}
+
+impl Rule {
+    /// Conway's Game of Life: born with 3, survives with 2 or 3.
+    pub const LIFE: Rule = Rule {
+        birth: 1 << 3,
+        survival: 1 << 2 | 1 << 3,
+    };
+
+    /// # Panics
+    /// If any of the counts is 64 or more.
+    #[must_use]
+    pub fn new(birth: &[usize], survival: &[usize]) -> Self {
+        let mask = |counts: &[usize]| {
+            counts.iter().fold(0, |mask, &count| {
+                assert!(count < 64, "neighbour counts need to be below 64");
+                mask | 1 << count
+            })
+        };
+        Self {
+            birth: mask(birth),
+            survival: mask(survival),
+        }
+    }
+
+    /// Whether a cell is alive next generation, given whether it is alive now
+    /// and how many live neighbours it has.
+    #[must_use]
+    pub fn next(self, alive: bool, live_neighbors: usize) -> bool {
+        let mask = if alive { self.survival } else { self.birth };
+        live_neighbors < 64 && mask & 1 << live_neighbors != 0
+    }
+}
+
+/// Which cells count as neighbours of a cell.
+pub trait Neighborhood<C> {
+    fn neighbors(&self, cell: C) -> impl Iterator<Item = C>;
+}
+
+/// All cells that differ by at most one on every axis, so 8 in 2D, 26 in 3D
+/// and 80 in 4D.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Moore;
+
+impl<const N: usize> Neighborhood<[isize; N]> for Moore {
+    fn neighbors(&self, cell: [isize; N]) -> impl Iterator<Item = [isize; N]> {
+        let count = 3_usize.pow(N as u32);
+        // Offset -1, 0 or 1 for each axis are the digits of `index` in base 3,
+        // so the cell itself sits exactly in the middle.
+        (0..count)
+            .filter(move |&index| index != count / 2)
+            .map(move |mut index| {
+                let mut neighbor = cell;
+                for coordinate in &mut neighbor {
+                    *coordinate += (index % 3) as isize - 1;
+                    index /= 3;
+                }
+                neighbor
+            })
+    }
+}
+
+/// Cells one step away along a single axis, so 4 in 2D and 6 in 3D.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VonNeumann;
+
+impl<const N: usize> Neighborhood<[isize; N]> for VonNeumann {
+    fn neighbors(&self, cell: [isize; N]) -> impl Iterator<Item = [isize; N]> {
+        (0..N).flat_map(move |axis| {
+            [-1, 1].into_iter().map(move |offset| {
+                let mut neighbor = cell;
+                neighbor[axis] += offset;
+                neighbor
+            })
+        })
+    }
+}
+
+/// The six tiles around a hexagonal tile.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hexagonal;
+
+impl Neighborhood<Hex> for Hexagonal {
+    fn neighbors(&self, cell: Hex) -> impl Iterator<Item = Hex> {
+        cell.neighbors()
+    }
+}
+
+/// Stepping and generation counting, shared by both backends.
+pub trait Automaton {
+    /// Advances one generation. Returns whether any cell changed.
+    fn step(&mut self) -> bool;
+
+    /// The number of steps taken so far.
+    fn generation(&self) -> usize;
+
+    /// The number of live cells.
+    fn population(&self) -> usize;
+
+    /// Advances `generations` steps.
+    fn run(&mut self, generations: usize) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    /// Steps until nothing changes anymore. Returns the generation at which
+    /// that happened. Never returns for patterns that don't settle.
+    fn run_until_stable(&mut self) -> usize {
+        while self.step() {}
+        self.generation()
+    }
+}
+
+/// An unbounded automaton that only stores the live cells.
+///
+/// Dead cells without live neighbours are never looked at, so a rule that
+/// makes cells come alive with 0 neighbours doesn't work here. Surviving with
+/// 0 neighbours is fine.
+#[derive(Clone, Debug)]
+pub struct Sparse<C, N> {
+    live: HashSet<C>,
+    neighborhood: N,
+    rule: Rule,
+    generation: usize,
+}
+
+impl<C, N> Sparse<C, N>
+where
+    C: Copy + Eq + Hash + Send + Sync,
+    N: Neighborhood<C> + Sync,
+{
+    #[must_use]
+    pub fn new(live: impl IntoIterator<Item = C>, neighborhood: N, rule: Rule) -> Self {
+        Self {
+            live: live.into_iter().collect(),
+            neighborhood,
+            rule,
+            generation: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn live(&self) -> &HashSet<C> {
+        &self.live
+    }
+
+    #[must_use]
+    pub fn is_alive(&self, cell: &C) -> bool {
+        self.live.contains(cell)
+    }
+
+    fn neighbor_counts(&self) -> HashMap<C, usize> {
+        self.live
+            .par_iter()
+            .fold(HashMap::new, |mut counts, &cell| {
+                for neighbor in self.neighborhood.neighbors(cell) {
+                    *counts.entry(neighbor).or_insert(0) += 1;
+                }
+                counts
+            })
+            .reduce(HashMap::new, |mut counts, mut other| {
+                if counts.len() < other.len() {
+                    std::mem::swap(&mut counts, &mut other);
+                }
+                for (cell, count) in other {
+                    *counts.entry(cell).or_insert(0) += count;
+                }
+                counts
+            })
+    }
+}
+
+impl<C, N> Automaton for Sparse<C, N>
+where
+    C: Copy + Eq + Hash + Send + Sync,
+    N: Neighborhood<C> + Sync,
+{
+    fn step(&mut self) -> bool {
+        let counts = self.neighbor_counts();
+        let mut live = counts
+            .par_iter()
+            .filter(|(cell, &count)| self.rule.next(self.live.contains(cell), count))
+            .map(|(&cell, _)| cell)
+            .collect::<HashSet<_>>();
+        // Live cells without live neighbours don't show up in the counts
+        if self.rule.next(true, 0) {
+            live.extend(self.live.iter().filter(|cell| !counts.contains_key(cell)));
+        }
+        self.generation += 1;
+        let changed = live != self.live;
+        self.live = live;
+        changed
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn population(&self) -> usize {
+        self.live.len()
+    }
+}
+
+/// An automaton over a fixed set of cells, numbered from 0, each with its own
+/// list of neighbours.
+#[derive(Clone, Debug)]
+pub struct Dense {
+    alive: Vec<bool>,
+    neighbors: Vec<Vec<usize>>,
+    rule: Rule,
+    generation: usize,
+}
+
+impl Dense {
+    /// Numbers `cells` in the order given. Neighbours that aren't in `cells`
+    /// are ignored, so the edges of the universe count as dead.
+    #[must_use]
+    pub fn new<C: Copy + Eq + Hash>(
+        cells: &[(C, bool)],
+        neighborhood: &impl Neighborhood<C>,
+        rule: Rule,
+    ) -> Self {
+        let index = cells
+            .iter()
+            .enumerate()
+            .map(|(index, &(cell, _))| (cell, index))
+            .collect::<HashMap<_, _>>();
+        let neighbors = cells
+            .iter()
+            .map(|&(cell, _)| {
+                neighborhood
+                    .neighbors(cell)
+                    .filter_map(|neighbor| index.get(&neighbor).copied())
+                    .collect()
+            })
+            .collect();
+        let alive = cells.iter().map(|&(_, alive)| alive).collect();
+        Self::from_graph(alive, neighbors, rule)
+    }
+
+    /// Cell `i` starts out as `alive[i]`, and has the cells in `neighbors[i]`
+    /// as neighbours.
+    ///
+    /// # Panics
+    /// If `alive` and `neighbors` differ in length, or a neighbour doesn't
+    /// exist.
+    #[must_use]
+    pub fn from_graph(alive: Vec<bool>, neighbors: Vec<Vec<usize>>, rule: Rule) -> Self {
+        assert_eq!(alive.len(), neighbors.len());
+        assert!(neighbors.iter().flatten().all(|&n| n < alive.len()));
+        Self {
+            alive,
+            neighbors,
+            rule,
+            generation: 0,
+        }
+    }
+
+    /// Whether each cell is alive, in the order the cells were given.
+    #[must_use]
+    pub fn alive(&self) -> &[bool] {
+        &self.alive
+    }
+
+    /// How many of the neighbours of `cell` are alive.
+    #[must_use]
+    pub fn live_neighbors(&self, cell: usize) -> usize {
+        self.neighbors[cell]
+            .iter()
+            .filter(|&&neighbor| self.alive[neighbor])
+            .count()
+    }
+}
+
+impl Automaton for Dense {
+    fn step(&mut self) -> bool {
+        let alive = (0..self.alive.len())
+            .into_par_iter()
+            .map(|cell| self.rule.next(self.alive[cell], self.live_neighbors(cell)))
+            .collect::<Vec<_>>();
+        self.generation += 1;
+        let changed = alive != self.alive;
+        self.alive = alive;
+        changed
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn population(&self) -> usize {
+        self.alive.iter().filter(|&&alive| alive).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const GLIDER: [[isize; 2]; 5] = [[1, 0], [2, 1], [0, 2], [1, 2], [2, 2]];
+
+    #[test]
+    fn test_neighbor_count() {
+        assert_eq!(Moore.neighbors([0; 2]).count(), 8);
+        assert_eq!(
+            Moore.neighbors([5, -3, 1]).collect::<HashSet<_>>().len(),
+            26
+        );
+        assert_eq!(Moore.neighbors([0; 4]).count(), 80);
+        assert!(!Moore.neighbors([0; 3]).any(|neighbor| neighbor == [0; 3]));
+        assert_eq!(VonNeumann.neighbors([0; 3]).count(), 6);
+    }
+
+    #[test]
+    fn test_rule() {
+        assert!(Rule::LIFE.next(false, 3));
+        assert!(!Rule::LIFE.next(false, 2));
+        assert!(Rule::LIFE.next(true, 2));
+        assert!(!Rule::LIFE.next(true, 4));
+        assert!(!Rule::LIFE.next(true, 100));
+        assert_eq!(Rule::new(&[3], &[2, 3]), Rule::LIFE);
+    }
+
+    #[test]
+    fn test_sparse_glider() {
+        let mut life = Sparse::new(GLIDER, Moore, Rule::LIFE);
+        life.run(4);
+        assert_eq!(life.generation(), 4);
+        let moved = GLIDER.map(|[x, y]| [x + 1, y + 1]);
+        assert_eq!(life.live(), &moved.into_iter().collect());
+    }
+
+    #[test]
+    fn test_dense_matches_sparse() {
+        let cells = (0..8)
+            .flat_map(|y| (0..8).map(move |x| ([x, y], GLIDER.contains(&[x, y]))))
+            .collect::<Vec<_>>();
+        let mut dense = Dense::new(&cells, &Moore, Rule::LIFE);
+        let mut sparse = Sparse::new(GLIDER, Moore, Rule::LIFE);
+        for _ in 0..8 {
+            assert!(dense.step());
+            sparse.step();
+            let alive = cells
+                .iter()
+                .zip(dense.alive())
+                .filter(|(_, &alive)| alive)
+                .map(|(&(cell, _), _)| cell)
+                .collect::<HashSet<_>>();
+            assert_eq!(&alive, sparse.live());
+        }
+    }
+
+    #[test]
+    fn test_run_until_stable() {
+        // A lonely pair starves, after which nothing changes anymore
+        let mut dense = Dense::from_graph(
+            vec![true, true, false],
+            vec![vec![1], vec![0, 2], vec![1]],
+            Rule::LIFE,
+        );
+        assert_eq!(dense.live_neighbors(1), 1);
+        assert_eq!(dense.run_until_stable(), 2);
+        assert_eq!(dense.population(), 0);
+
+        let block = [[0, 0], [0, 1], [1, 0], [1, 1]];
+        let mut sparse = Sparse::new(block, Moore, Rule::LIFE);
+        assert_eq!(sparse.run_until_stable(), 1);
+        assert_eq!(sparse.population(), 4);
+    }
+
+    #[test]
+    fn test_sparse_lonely_survivor() {
+        let rule = Rule::new(&[3], &[0, 2, 3]);
+        let mut life = Sparse::new([[0, 0], [10, 10], [11, 10]], Moore, rule);
+        life.step();
+        // The pair starves, but the cell on its own is happy like that
+        assert_eq!(life.live(), &[[0, 0]].into_iter().collect());
+        assert!(!life.step());
+    }
+
+    #[test]
+    fn test_hexagonal() {
+        let rule = Rule::new(&[2], &[1, 2]);
+        let mut tiles = Sparse::new([Hex::ORIGIN, Hex::new(1, 1)], Hexagonal, rule);
+        tiles.step();
+        // Both tiles die, the two touching both of them are born
+        assert_eq!(
+            tiles.live(),
+            &[Hex::new(1, 0), Hex::new(0, 1)].into_iter().collect()
+        );
+    }
+}
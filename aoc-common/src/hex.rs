@@ -0,0 +1,220 @@
+//! Hexagonal grids with pointy-topped tiles, so neighbours are east, west,
+//! and four diagonals (2020 day 24).
+//!
+//! Tiles are stored in axial coordinates: `q` goes east, `r` goes south-east.
+//! The third cube coordinate `s` is implied by `q + r + s == 0`.
+
+use nom::{branch::alt, bytes::complete::tag, combinator::value, multi::many1, IResult};
+use std::ops::{Add, Sub};
+
+/// One of the six directions to a neighbouring tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    East,
+    SouthEast,
+    SouthWest,
+    West,
+    NorthWest,
+    NorthEast,
+}
+
+impl Direction {
+    /// All directions, clockwise starting at east.
+    pub const ALL: [Direction; 6] = [
+        Direction::East,
+        Direction::SouthEast,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+        Direction::NorthEast,
+    ];
+
+    /// The tile one step in this direction from the origin.
+    #[must_use]
+    pub fn offset(self) -> Hex {
+        match self {
+            Direction::East => Hex::new(1, 0),
+            Direction::SouthEast => Hex::new(0, 1),
+            Direction::SouthWest => Hex::new(-1, 1),
+            Direction::West => Hex::new(-1, 0),
+            Direction::NorthWest => Hex::new(0, -1),
+            Direction::NorthEast => Hex::new(1, -1),
+        }
+    }
+
+    /// Turns 60 degrees clockwise `steps` times. Negative steps turn
+    /// counterclockwise.
+    #[must_use]
+    pub fn rotate(self, steps: isize) -> Direction {
+        let index = Direction::ALL.iter().position(|&d| d == self).unwrap();
+        Direction::ALL[(index as isize + steps).rem_euclid(6) as usize]
+    }
+}
+
+/// Parses one of `e`, `se`, `sw`, `w`, `nw` or `ne`.
+///
+/// # Errors
+/// If the input doesn't start with a direction.
+pub fn direction(input: &str) -> IResult<&str, Direction> {
+    alt((
+        value(Direction::East, tag("e")),
+        value(Direction::SouthEast, tag("se")),
+        value(Direction::SouthWest, tag("sw")),
+        value(Direction::West, tag("w")),
+        value(Direction::NorthWest, tag("nw")),
+        value(Direction::NorthEast, tag("ne")),
+    ))(input)
+}
+
+/// Parses a run of directions without separators, like `esenee`.
+///
+/// # Errors
+/// If the input doesn't start with a direction.
+pub fn path(input: &str) -> IResult<&str, Vec<Direction>> {
+    many1(direction)(input)
+}
+
+/// A tile, in axial coordinates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hex {
+    pub q: isize,
+    pub r: isize,
+}
+
+impl Hex {
+    pub const ORIGIN: Hex = Hex { q: 0, r: 0 };
+
+    #[must_use]
+    pub const fn new(q: isize, r: isize) -> Self {
+        Self { q, r }
+    }
+
+    /// Converts from cube coordinates.
+    ///
+    /// # Panics
+    /// If `q + r + s != 0`.
+    #[must_use]
+    pub fn from_cube(q: isize, r: isize, s: isize) -> Self {
+        assert_eq!(q + r + s, 0, "cube coordinates need to add up to 0");
+        Self { q, r }
+    }
+
+    /// The implied third cube coordinate.
+    #[must_use]
+    pub const fn s(self) -> isize {
+        -self.q - self.r
+    }
+
+    /// Cube coordinates `(q, r, s)`.
+    #[must_use]
+    pub const fn cube(self) -> (isize, isize, isize) {
+        (self.q, self.r, self.s())
+    }
+
+    #[must_use]
+    pub fn neighbor(self, direction: Direction) -> Self {
+        self + direction.offset()
+    }
+
+    /// The six surrounding tiles, clockwise starting at east.
+    pub fn neighbors(self) -> impl Iterator<Item = Hex> {
+        Direction::ALL.into_iter().map(move |d| self.neighbor(d))
+    }
+
+    /// Follows `directions` starting from this tile.
+    #[must_use]
+    pub fn walk(self, directions: impl IntoIterator<Item = Direction>) -> Self {
+        directions.into_iter().fold(self, Hex::neighbor)
+    }
+
+    /// Number of steps to get to `other`.
+    #[must_use]
+    pub fn distance(self, other: Hex) -> usize {
+        let (q, r, s) = (other - self).cube();
+        (q.unsigned_abs() + r.unsigned_abs() + s.unsigned_abs()) / 2
+    }
+
+    /// Rotates around `center` by 60 degrees clockwise `steps` times. Negative
+    /// steps turn counterclockwise.
+    #[must_use]
+    pub fn rotate(self, center: Hex, steps: isize) -> Self {
+        let (mut q, mut r, mut s) = (self - center).cube();
+        for _ in 0..steps.rem_euclid(6) {
+            (q, r, s) = (-r, -s, -q);
+        }
+        center + Hex::from_cube(q, r, s)
+    }
+}
+
+impl Add for Hex {
+    type Output = Hex;
+
+    fn add(self, other: Hex) -> Hex {
+        Hex::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl Sub for Hex {
+    type Output = Hex;
+
+    fn sub(self, other: Hex) -> Hex {
+        Hex::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("esew" => Hex::ORIGIN.neighbor(Direction::SouthEast); "example 1")]
+    #[test_case("nwwswee" => Hex::ORIGIN; "example 2")]
+    fn test_walk(input: &str) -> Hex {
+        let (rest, directions) = path(input).unwrap();
+        assert_eq!(rest, "");
+        Hex::ORIGIN.walk(directions)
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let center = Hex::new(3, -7);
+        for neighbor in center.neighbors() {
+            assert_eq!(center.distance(neighbor), 1);
+        }
+        assert_eq!(
+            center
+                .neighbors()
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            6
+        );
+    }
+
+    #[test_case(Hex::ORIGIN, Hex::new(3, 0) => 3; "straight east")]
+    #[test_case(Hex::ORIGIN, Hex::new(2, 2) => 4; "south-east then east")]
+    #[test_case(Hex::new(-1, 3), Hex::new(2, -1) => 4; "both ways")]
+    fn test_distance(from: Hex, to: Hex) -> usize {
+        from.distance(to)
+    }
+
+    #[test]
+    fn test_rotate() {
+        let east = Direction::East.offset();
+        assert_eq!(east.rotate(Hex::ORIGIN, 1), Direction::SouthEast.offset());
+        assert_eq!(east.rotate(Hex::ORIGIN, -1), Direction::NorthEast.offset());
+        for direction in Direction::ALL {
+            assert_eq!(
+                direction.offset().rotate(Hex::ORIGIN, 2),
+                direction.rotate(2).offset()
+            );
+        }
+        let center = Hex::new(5, 5);
+        let tile = Hex::new(7, 4);
+        assert_eq!(tile.rotate(center, 6), tile);
+        assert_eq!(tile.rotate(center, 3), Hex::new(3, 6));
+        assert_eq!(
+            tile.rotate(center, 1).distance(center),
+            tile.distance(center)
+        );
+    }
+}
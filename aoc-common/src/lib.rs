@@ -0,0 +1,7 @@
+//! Helpers shared between the daily solutions.
+
+pub mod automaton;
+pub mod hex;
+pub mod memo;
+pub mod ocr;
+pub mod parse;
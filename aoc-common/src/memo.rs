@@ -0,0 +1,102 @@
+//! Caching for pure recursive functions, like counting the ways to arrange
+//! something (2020 day 10, 2024 day 11 and day 19).
+//!
+//! The function gets the [`Memo`] as its first argument and recurses through
+//! [`Memo::get`] instead of calling itself, so every call in the tree shares
+//! one cache. The cache lives as long as the `Memo`, so a fresh one starts
+//! from scratch.
+
+use std::{collections::HashMap, hash::Hash};
+
+/// How often [`Memo::get`] found its answer in the cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+pub struct Memo<'f, K, V> {
+    function: &'f dyn Fn(&mut Memo<'f, K, V>, K) -> V,
+    cache: HashMap<K, V>,
+    stats: Stats,
+}
+
+impl<'f, K, V> Memo<'f, K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    /// Wraps `function`, which takes all of its arguments as the key `K`.
+    #[must_use]
+    pub fn new(function: &'f dyn Fn(&mut Self, K) -> V) -> Self {
+        Self {
+            function,
+            cache: HashMap::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    /// Returns the cached result for `key`, or calls the function to get it.
+    pub fn get(&mut self, key: K) -> V {
+        if let Some(value) = self.cache.get(&key) {
+            self.stats.hits += 1;
+            return value.clone();
+        }
+        self.stats.misses += 1;
+        let function = self.function;
+        let value = function(self, key.clone());
+        self.cache.insert(key, value.clone());
+        value
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn fibonacci(memo: &mut Memo<u64, u64>, n: u64) -> u64 {
+        if n < 2 {
+            n
+        } else {
+            memo.get(n - 1) + memo.get(n - 2)
+        }
+    }
+
+    #[test_case(10 => (55, Stats { hits: 8, misses: 11 }); "small")]
+    #[test_case(90 => (2_880_067_194_370_816_120, Stats { hits: 88, misses: 91 }); "large")]
+    fn test_fibonacci(n: u64) -> (u64, Stats) {
+        let mut memo = Memo::new(&fibonacci);
+        let result = memo.get(n);
+        (result, memo.stats())
+    }
+
+    #[test]
+    fn test_captures_context() {
+        // Ways to climb `height` steps, taking any of `strides` at a time
+        let strides = [1, 2, 3];
+        let climb = |memo: &mut Memo<usize, usize>, height: usize| {
+            if height == 0 {
+                return 1;
+            }
+            strides
+                .iter()
+                .filter(|&&stride| stride <= height)
+                .map(|stride| memo.get(height - stride))
+                .sum()
+        };
+        let mut memo = Memo::new(&climb);
+        assert_eq!(memo.get(4), 7);
+        assert_eq!(memo.get(30), 53_798_080);
+        assert_eq!(memo.get(4), 7);
+        assert_eq!(memo.stats().misses, 31);
+
+        let fresh = Memo::new(&climb);
+        assert_eq!(fresh.stats(), Stats::default());
+    }
+}
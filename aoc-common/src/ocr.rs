@@ -0,0 +1,235 @@
+//! Reads the block letters some puzzles answer with, like 2022 day 10 or
+//! 2019 day 8 and 11.
+//!
+//! Both the regular 4x6 font and the large 6x10 font (2018 day 10) are known.
+//! The font is picked by the height of the picture, after trimming blank rows
+//! and columns, so it doesn't matter where on the screen the letters ended up.
+
+const SMALL_HEIGHT: usize = 6;
+const LARGE_HEIGHT: usize = 10;
+
+const SMALL_FONT: &[(char, [&str; SMALL_HEIGHT])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', ["###", ".#.", ".#.", ".#.", ".#.", "###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+#[rustfmt::skip]
+const LARGE_FONT: &[(char, [&str; LARGE_HEIGHT])] = &[
+    ('A', ["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+    ('B', ["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+    ('C', [".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+    ('E', ["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('F', ["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('G', [".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+    ('H', ["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+    ('J', ["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+    ('K', ["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+    ('L', ["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('N', ["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+    ('P', ["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('R', ["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+    ('X', ["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+    ('Z', ["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+];
+
+/// A letter as a list of columns, each column a bitmask with the top row in
+/// the lowest bit.
+struct Glyph {
+    letter: char,
+    columns: Vec<u16>,
+}
+
+fn glyphs(font: &[(char, impl AsRef<[&'static str]>)]) -> Vec<Glyph> {
+    let mut glyphs = font
+        .iter()
+        .map(|(letter, rows)| {
+            let rows = rows.as_ref();
+            let columns = (0..rows[0].len())
+                .map(|x| {
+                    rows.iter()
+                        .enumerate()
+                        .filter(|(_, row)| row.as_bytes()[x] == b'#')
+                        .fold(0, |column, (y, _)| column | 1 << y)
+                })
+                .collect();
+            Glyph {
+                letter: *letter,
+                columns,
+            }
+        })
+        .collect::<Vec<_>>();
+    // Try wide letters first, so a narrow one can't match just the start of it
+    glyphs.sort_by_key(|glyph| std::cmp::Reverse(glyph.columns.len()));
+    glyphs
+}
+
+/// Reads the letters in `pixels`, indexed as `pixels[y][x]`. Rows may differ
+/// in length, missing pixels are off.
+///
+/// Returns `None` if the picture isn't 6 or 10 pixels tall (not counting blank
+/// rows), or if some part of it isn't a letter in the matching font.
+#[must_use]
+pub fn recognize(pixels: &[Vec<bool>]) -> Option<String> {
+    let mut lit_rows = pixels
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| row.contains(&true))
+        .map(|(y, _)| y);
+    let top = lit_rows.next()?;
+    let height = lit_rows.next_back().unwrap_or(top) - top + 1;
+    let glyphs = match height {
+        SMALL_HEIGHT => glyphs(SMALL_FONT),
+        LARGE_HEIGHT => glyphs(LARGE_FONT),
+        _ => return None,
+    };
+    let rows = &pixels[top..top + height];
+    let width = rows.iter().map(Vec::len).max()?;
+    let columns = (0..width)
+        .map(|x| {
+            rows.iter()
+                .enumerate()
+                .filter(|(_, row)| row.get(x) == Some(&true))
+                .fold(0_u16, |column, (y, _)| column | 1 << y)
+        })
+        .collect::<Vec<_>>();
+
+    let mut letters = String::new();
+    let mut x = 0;
+    while x < columns.len() {
+        if columns[x] == 0 {
+            x += 1;
+            continue;
+        }
+        let rest = &columns[x..];
+        let matches = |glyph: &&Glyph| rest.starts_with(&glyph.columns);
+        // Letters are normally followed by a blank column, but in 5 pixel wide
+        // cells a `Y` runs straight into the next letter.
+        let glyph = glyphs
+            .iter()
+            .filter(matches)
+            .find(|glyph| rest.get(glyph.columns.len()).is_none_or(|&c| c == 0))
+            .or_else(|| glyphs.iter().find(matches))?;
+        letters.push(glyph.letter);
+        x += glyph.columns.len();
+    }
+    Some(letters)
+}
+
+/// Reads the letters in a picture drawn with `#` for lit pixels. Any other
+/// character, like `.` or a space, is off.
+///
+/// Returns `None` under the same conditions as [`recognize`].
+#[must_use]
+pub fn recognize_str(picture: &str) -> Option<String> {
+    let pixels = picture
+        .lines()
+        .map(|line| line.chars().map(|c| c == '#').collect())
+        .collect::<Vec<_>>();
+    recognize(&pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn render<const N: usize>(font: &[(char, [&str; N])], text: &str, spacing: usize) -> String {
+        (0..N)
+            .map(|y| {
+                text.chars()
+                    .map(|letter| {
+                        let (_, rows) = font.iter().find(|(l, _)| *l == letter).unwrap();
+                        let cell_width = font[0].1[0].len() + spacing;
+                        format!("{:.<cell_width$}", rows[y])
+                    })
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_every_small_letter() {
+        let text = SMALL_FONT
+            .iter()
+            .map(|(letter, _)| letter)
+            .collect::<String>();
+        assert_eq!(recognize_str(&render(SMALL_FONT, &text, 1)), Some(text));
+    }
+
+    #[test]
+    fn test_every_large_letter() {
+        let text = LARGE_FONT
+            .iter()
+            .map(|(letter, _)| letter)
+            .collect::<String>();
+        assert_eq!(recognize_str(&render(LARGE_FONT, &text, 2)), Some(text));
+    }
+
+    #[test_case("
+ ###  #  #  ##  ####
+ #  # #  # #  # #
+ #  # #### #    ###
+ ###  #  # #    #
+ #    #  # #  # #
+ #    #  #  ##  ####
+" => Some("PHCE".to_string()); "spaces with margins")]
+    #[test_case("\
+#...#.###.
+#...#.#..#
+.#.#..###.
+..#...#..#
+..#...#..#
+..#...###.
+" => Some("YB".to_string()); "wide Y")]
+    #[test_case("\
+#...####
+#...#...
+#...###.
+#...#...
+#...#...
+#####...
+" => Some("LF".to_string()); "touching letters")]
+    #[test_case("\
+####
+#..#
+####
+" => None; "wrong height")]
+    #[test_case("\
+#..#.
+#..#.
+####.
+#..#.
+#..##
+#..##
+" => None; "garbage after letter")]
+    fn test_recognize_str(picture: &str) -> Option<String> {
+        recognize_str(picture)
+    }
+
+    #[test]
+    fn test_recognize_bools() {
+        let pixels = render(SMALL_FONT, "JK", 1)
+            .lines()
+            .map(|line| line.chars().map(|c| c == '#').collect())
+            .collect::<Vec<_>>();
+        assert_eq!(recognize(&pixels), Some("JK".to_string()));
+        assert_eq!(recognize(&[]), None);
+    }
+}
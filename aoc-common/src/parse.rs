@@ -0,0 +1,265 @@
+//! nom parsers for the input shapes that keep coming back: numbers, one item
+//! per line, blank-line separated blocks, character grids and `key: value`
+//! records.
+//!
+//! None of the list combinators eat the final line ending, so they nest
+//! (lines inside paragraphs). [`parse_all`] takes care of the trailing
+//! newline at the very end of the input.
+
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag},
+    character::complete::{digit1, line_ending, none_of, one_of},
+    combinator::{map_res, not, opt, recognize, verify},
+    error::Error,
+    multi::{many1, separated_list1},
+    sequence::{pair, separated_pair, terminated},
+    IResult, Parser,
+};
+use std::{fmt, str::FromStr};
+
+/// Parses an unsigned number, like `42`.
+///
+/// # Errors
+/// If the input doesn't start with a digit, or the number doesn't fit in `T`.
+pub fn unsigned<T: FromStr>(input: &str) -> IResult<&str, T> {
+    map_res(digit1, str::parse)(input)
+}
+
+/// Parses a number with an optional sign, like `-42`, `+42` or `42`.
+///
+/// # Errors
+/// If the input doesn't start with a number, or the number doesn't fit in `T`.
+pub fn signed<T: FromStr>(input: &str) -> IResult<&str, T> {
+    map_res(recognize(pair(opt(one_of("+-")), digit1)), str::parse)(input)
+}
+
+/// Parses a run of anything but whitespace, like the `#623a2f` in `hcl:#623a2f`.
+///
+/// # Errors
+/// If the input starts with whitespace or is empty.
+pub fn token(input: &str) -> IResult<&str, &str> {
+    is_not(" \t\r\n")(input)
+}
+
+/// Parses one `item` per line. The line ending after the last item is left
+/// alone.
+pub fn lines<'a, O, F>(item: F) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>>
+where
+    F: Parser<&'a str, O, Error<&'a str>>,
+{
+    separated_list1(line_ending, item)
+}
+
+/// Parses blocks separated by a blank line, like the elves in 2022 day 1 or
+/// the passports in 2020 day 4.
+pub fn paragraphs<'a, O, F>(block: F) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>>
+where
+    F: Parser<&'a str, O, Error<&'a str>>,
+{
+    separated_list1(pair(line_ending, line_ending), block)
+}
+
+/// Parses a rectangular grid of cells, one row per line.
+pub fn grid<'a, O, F>(cell: F) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<Vec<O>>>
+where
+    F: Parser<&'a str, O, Error<&'a str>>,
+{
+    verify(lines(many1(cell)), |rows: &[Vec<O>]| {
+        rows.iter().all(|row| row.len() == rows[0].len())
+    })
+}
+
+/// Parses a rectangular grid of characters, one row per line.
+///
+/// # Errors
+/// If the input is empty or the rows differ in length.
+pub fn char_grid(input: &str) -> IResult<&str, Vec<Vec<char>>> {
+    grid(none_of("\r\n"))(input)
+}
+
+/// Parses `key`, then the literal `separator`, then `value`. Handles both
+/// `byr:1937` and `Register A: 729`.
+pub fn key_value<'a, K, V, FK, FV>(
+    key: FK,
+    separator: &'a str,
+    value: FV,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (K, V)>
+where
+    FK: Parser<&'a str, K, Error<&'a str>>,
+    FV: Parser<&'a str, V, Error<&'a str>>,
+{
+    separated_pair(key, tag(separator), value)
+}
+
+/// Parses fields separated by single spaces or line breaks, as in the 2020
+/// day 4 passports. A blank line ends the record.
+pub fn record<'a, O, F>(field: F) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>>
+where
+    F: Parser<&'a str, O, Error<&'a str>>,
+{
+    separated_list1(alt((tag(" "), terminated(line_ending, not(line_ending)))), field)
+}
+
+/// Where and why [`parse_all`] gave up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, in characters
+    pub column: usize,
+    pub message: String,
+    /// The full text of the offending line
+    pub text: String,
+}
+
+impl ParseError {
+    fn new(input: &str, remaining: &str, message: String) -> Self {
+        let consumed = &input[..input.len() - remaining.len()];
+        let line_start = consumed.rfind('\n').map_or(0, |index| index + 1);
+        Self {
+            line: consumed.matches('\n').count() + 1,
+            column: consumed[line_start..].chars().count() + 1,
+            message,
+            text: input[line_start..].lines().next().unwrap_or("").to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "line {}, column {}: {}", self.line, self.column, self.message)?;
+        writeln!(f, "{}", self.text)?;
+        write!(f, "{:>width$}", "^", width = self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Runs `parser` over the whole of `input`. Trailing whitespace (usually the
+/// final newline) is fine, anything else left over is an error.
+///
+/// # Errors
+/// If `parser` fails, or doesn't consume everything but trailing whitespace.
+/// The error points at the line and column where things went wrong.
+pub fn parse_all<'a, O, F>(mut parser: F, input: &'a str) -> Result<O, ParseError>
+where
+    F: Parser<&'a str, O, Error<&'a str>>,
+{
+    match parser.parse(input) {
+        Ok((remaining, output)) => {
+            let remaining = remaining.trim_start();
+            if remaining.is_empty() {
+                Ok(output)
+            } else {
+                Err(ParseError::new(
+                    input,
+                    remaining,
+                    "unexpected trailing input".to_string(),
+                ))
+            }
+        }
+        Err(nom::Err::Error(error) | nom::Err::Failure(error)) => Err(ParseError::new(
+            input,
+            error.input,
+            format!("expected {}", error.code.description()),
+        )),
+        Err(nom::Err::Incomplete(_)) => Err(ParseError::new(
+            input,
+            "",
+            "unexpected end of input".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::character::complete::{alpha1, anychar, char as nom_char};
+    use test_case::test_case;
+
+    #[test_case("42" => Ok(("", 42)); "plain")]
+    #[test_case("-42,1" => Ok((",1", -42)); "negative")]
+    #[test_case("+42" => Ok(("", 42)); "explicit plus")]
+    fn test_signed(input: &str) -> Result<(&str, i64), String> {
+        signed(input).map_err(|e| e.to_string())
+    }
+
+    #[test_case("-42" => true; "negative")]
+    #[test_case("300" => true; "overflow")]
+    #[test_case("200" => false; "fits")]
+    fn test_unsigned_rejects(input: &str) -> bool {
+        unsigned::<u8>(input).is_err()
+    }
+
+    #[test_case("1\n2\n3\n" => Ok(vec![1, 2, 3]); "trailing newline")]
+    #[test_case("1\r\n2" => Ok(vec![1, 2]); "crlf")]
+    fn test_lines(input: &str) -> Result<Vec<u32>, ParseError> {
+        parse_all(lines(unsigned), input)
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let input = "1000\n2000\n3000\n\n4000\n\n5000\n6000\n";
+        assert_eq!(
+            parse_all(paragraphs(lines(unsigned::<u32>)), input),
+            Ok(vec![vec![1000, 2000, 3000], vec![4000], vec![5000, 6000]])
+        );
+    }
+
+    #[test]
+    fn test_char_grid() {
+        assert_eq!(
+            parse_all(char_grid, "#.\n.#\n"),
+            Ok(vec![vec!['#', '.'], vec!['.', '#']])
+        );
+        assert!(parse_all(char_grid, "#.\n.\n").is_err());
+    }
+
+    #[test]
+    fn test_passports() {
+        let input = "ecl:gry pid:860033327
+hcl:#fffffd
+
+iyr:2013 ecl:amb
+";
+        let passports =
+            parse_all(paragraphs(record(key_value(alpha1, ":", token))), input).unwrap();
+        assert_eq!(
+            passports,
+            vec![
+                vec![("ecl", "gry"), ("pid", "860033327"), ("hcl", "#fffffd")],
+                vec![("iyr", "2013"), ("ecl", "amb")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_registers() {
+        let input = "Register A: 729\nRegister B: 0\n";
+        let register = pair(tag("Register "), anychar).map(|(_, name)| name);
+        assert_eq!(
+            parse_all(lines(key_value(register, ": ", unsigned::<u64>)), input),
+            Ok(vec![('A', 729), ('B', 0)])
+        );
+    }
+
+    #[test]
+    fn test_error_position() {
+        let error = parse_all(lines(unsigned::<u32>), "1\n2\n3x\n").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 2));
+        assert_eq!(error.text, "3x");
+        assert_eq!(error.to_string(), "line 3, column 2: unexpected trailing input\n3x\n ^");
+
+        let error = parse_all(separated_list1(nom_char(','), signed::<i32>), "1,-").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 2));
+
+        let error = parse_all(pair(unsigned::<u32>, line_ending), "1
+x").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+
+        let error = parse_all(key_value(alpha1, ": ", unsigned::<u32>), "ab: x").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 5));
+        assert_eq!(error.message, "expected Digit");
+    }
+}